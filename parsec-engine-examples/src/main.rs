@@ -181,7 +181,7 @@ fn game_startup(ctx: Ctx) -> Result<(), ParsecError> {
     Ok(())
 }
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, Copy, Component)]
 struct CameraController {
     yaw: f32,
     target_yaw: f32,
@@ -198,7 +198,9 @@ fn camera_controller(ctx: Ctx) -> Result<(), ParsecError> {
     let input = ctx.resources.get::<Input>().none_err()?;
     let time = ctx.resources.get::<Time>().none_err()?;
 
-    for (_, (transform, camera, camera_controller)) in cameras.iter() {
+    for (_, (mut transform, mut camera, mut camera_controller)) in
+        cameras.iter()
+    {
         let delta = input.mouse.positon_delta();
         camera_controller.target_yaw += -delta.x / window.width() as f32 * 10.0;
         camera_controller.target_pitch +=
@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{
-    DeriveInput, Ident, LitInt, Token,
+    DeriveInput, Ident, LitInt, LitStr, Path, Token,
     parse::{Parse, ParseStream, Result},
     parse_macro_input,
     punctuated::Punctuated,
@@ -31,6 +31,8 @@ pub fn impl_spawn(input: TokenStream) -> TokenStream {
     let mut bundle_types = Vec::new();
     let mut archetype_adds = Vec::new();
     let mut archetype_ids = Vec::new();
+    let mut component_ids = Vec::new();
+    let mut fixed = Vec::new();
     let mut bundle_deconstruction = Vec::new();
     let mut id = Vec::new();
     for (i, t) in types.iter().enumerate() {
@@ -41,7 +43,11 @@ pub fn impl_spawn(input: TokenStream) -> TokenStream {
         let dec_name = format_ident!("value_{}", i);
         id.push(quote! { ret = ret.merge_with(self.#i.archetype_id()?)?; });
         bundle_deconstruction.push(quote! { #dec_name });
-        archetype_adds.push(quote! { self.#i.spawn(archetype)?; });
+        archetype_adds.push(quote! {
+            self.#i.spawn(archetype, sparse_sets, entity, tick)?;
+        });
+        component_ids.push(quote! { self.#i.component_ids(ids); });
+        fixed.push(quote! { #t::is_archetype_fixed() });
     }
 
     let output = quote! {
@@ -51,10 +57,61 @@ pub fn impl_spawn(input: TokenStream) -> TokenStream {
                 #(#id)*
                 Ok(ret)
             }
-            fn spawn(&self, archetype: &mut Archetype) -> Result<(), ArchetypeError> {
+            fn component_ids(&self, ids: &mut Vec<ComponentId>) {
+                #(#component_ids)*
+            }
+            fn is_archetype_fixed() -> bool {
+                #(#fixed)&&*
+            }
+            fn spawn(self, archetype: &mut Archetype, sparse_sets: &mut SparseSets, entity: Entity, tick: Tick) -> Result<(), ArchetypeError> {
                 #(#archetype_adds)*
                 Ok(())
             }
+            fn spawn_boxed(self: Box<Self>, archetype: &mut Archetype, sparse_sets: &mut SparseSets, entity: Entity, tick: Tick) -> Result<(), ArchetypeError> {
+                (*self).spawn(archetype, sparse_sets, entity, tick)
+            }
+        }
+    };
+
+    TokenStream::from(output)
+}
+
+#[proc_macro]
+pub fn impl_remove_component(input: TokenStream) -> TokenStream {
+    let ImplSpawnInput { types } = parse_macro_input!(input as ImplSpawnInput);
+
+    if types.len() == 1 {
+        return TokenStream::new();
+    }
+
+    let mut impl_types = Vec::new();
+    let mut bundle_types = Vec::new();
+    let mut id = Vec::new();
+    let mut remove_sparse = Vec::new();
+    let mut component_ids = Vec::new();
+    for t in types.iter() {
+        impl_types.push(quote! { #t: RemoveComponent });
+        bundle_types.push(quote! { #t });
+        id.push(quote! { ret = ret.merge_with(#t::archetype_id()?)?; });
+        remove_sparse
+            .push(quote! { #t::remove_sparse(sparse_sets, entity)?; });
+        component_ids.push(quote! { #t::component_ids(ids); });
+    }
+
+    let output = quote! {
+        impl<#(#impl_types),*> RemoveComponent for (#(#bundle_types),*) {
+            fn archetype_id() -> Result<ArchetypeId, ArchetypeError> {
+                let mut ret = ArchetypeId::new(Vec::new())?;
+                #(#id)*
+                Ok(ret)
+            }
+            fn component_ids(ids: &mut Vec<ComponentId>) {
+                #(#component_ids)*
+            }
+            fn remove_sparse(sparse_sets: &mut SparseSets, entity: Entity) -> Result<(), ArchetypeError> {
+                #(#remove_sparse)*
+                Ok(())
+            }
         }
     };
 
@@ -81,42 +138,54 @@ pub fn impl_fetch(input: TokenStream) -> TokenStream {
         return TokenStream::new();
     }
 
-    let t_one = types.get(0).unwrap();
     let mut impl_types = Vec::new();
     let mut bundle_types = Vec::new();
     let mut state_types = Vec::new();
     let mut item_types = Vec::new();
+    let mut chunk_types = Vec::new();
     let mut prepare = Vec::new();
     let mut release = Vec::new();
     let mut get = Vec::new();
-    let mut id = Vec::new();
+    let mut get_chunk = Vec::new();
+    let mut contains_row = Vec::new();
+    let mut matches = Vec::new();
     for (i, t) in types.iter().enumerate() {
         impl_types.push(quote! { #t: Fetch });
         bundle_types.push(quote! { #t });
         item_types.push(quote! { #t::Item<'a> });
+        chunk_types.push(quote! { #t::Chunk<'a> });
         state_types.push(quote! { #t::State });
-        prepare.push(quote! { #t::prepare(archetype)? });
-        id.push(quote! { ret = ret.merge_with(#t::archetype_id()?)?; });
+        prepare.push(quote! { #t::prepare(archetype, sparse_sets, ticks)? });
+        matches.push(quote! { #t::matches(archetype_id) });
         let i = syn::Index::from(i);
         release.push(quote! { #t::release(state.#i)? });
+        contains_row.push(quote! { #t::contains_row(&state.#i, row) });
         get.push(quote! { #t::get(state.#i.clone(), row) });
+        get_chunk.push(quote! { #t::get_chunk(state.#i, len)? });
     }
 
     let output = quote! {
         impl<#(#impl_types),*> Fetch for (#(#bundle_types),*) {
             type Item<'a> = (#(#item_types),*) where Self: 'a;
+            type Chunk<'a> = (#(#chunk_types),*) where Self: 'a;
             type State = (#(#state_types),*);
 
-            fn archetype_id() -> Result<ArchetypeId, ArchetypeError> {
-                let mut ret = ArchetypeId::new(Vec::new())?;
-                #(#id)*
-                Ok(ret)
+            fn matches(archetype_id: &ArchetypeId) -> bool {
+                #(#matches)&&*
+            }
+
+            fn access(access: &mut SystemAccess) {
+                #(#bundle_types::access(access);)*
             }
 
-            fn prepare(archetype: &Archetype) -> Result<Self::State, ArchetypeError> {
+            fn prepare(archetype: &Archetype, sparse_sets: &SparseSets, ticks: SystemTicks) -> Result<Self::State, ArchetypeError> {
                 Ok((#(#prepare),*))
             }
 
+            fn contains_row(state: &Self::State, row: usize) -> bool {
+                #(#contains_row)&&*
+            }
+
             fn release(state: Self::State) -> Result<(), ArchetypeError> {
                 (#(#release),*);
                 Ok(())
@@ -126,8 +195,279 @@ pub fn impl_fetch(input: TokenStream) -> TokenStream {
                 (#(#get),*)
             }
 
-            fn len(state: &Self::State) -> usize {
-                #t_one::len(&state.0)
+            fn get_chunk<'a>(state: Self::State, len: usize) -> Option<Self::Chunk<'a>> {
+                Some((#(#get_chunk),*))
+            }
+        }
+    };
+
+    TokenStream::from(output)
+}
+
+struct ImplFilterInput {
+    types: Punctuated<Ident, Token![,]>,
+}
+
+impl Parse for ImplFilterInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let types: Punctuated<Ident, Token![,]> =
+            Punctuated::parse_terminated(input)?;
+        Ok(ImplFilterInput { types })
+    }
+}
+
+#[proc_macro]
+pub fn impl_filter(input: TokenStream) -> TokenStream {
+    let ImplFilterInput { types } =
+        parse_macro_input!(input as ImplFilterInput);
+
+    if types.len() == 1 {
+        return TokenStream::new();
+    }
+
+    let mut impl_types = Vec::new();
+    let mut bundle_types = Vec::new();
+    let mut state_types = Vec::new();
+    let mut matches = Vec::new();
+    let mut prepare = Vec::new();
+    let mut filter_row = Vec::new();
+    for (i, t) in types.iter().enumerate() {
+        impl_types.push(quote! { #t: Filter });
+        bundle_types.push(quote! { #t });
+        state_types.push(quote! { #t::State });
+        matches.push(quote! { #t::matches(archetype_id) });
+        prepare.push(quote! { #t::prepare(archetype, sparse_sets, ticks)? });
+        let i = syn::Index::from(i);
+        filter_row.push(quote! { #t::filter_row(&state.#i, row) });
+    }
+
+    let output = quote! {
+        impl<#(#impl_types),*> Filter for (#(#bundle_types),*) {
+            type State = (#(#state_types),*);
+
+            fn matches(archetype_id: &ArchetypeId) -> bool {
+                #(#matches)&&*
+            }
+
+            fn access(access: &mut SystemAccess) {
+                #(#bundle_types::access(access);)*
+            }
+
+            fn prepare(archetype: &Archetype, sparse_sets: &SparseSets, ticks: SystemTicks) -> Result<Self::State, ArchetypeError> {
+                Ok((#(#prepare),*))
+            }
+
+            fn filter_row(state: &Self::State, row: usize) -> bool {
+                #(#filter_row)&&*
+            }
+
+            fn is_archetypal() -> bool {
+                #(#bundle_types::is_archetypal())&&*
+            }
+        }
+    };
+
+    TokenStream::from(output)
+}
+
+#[proc_macro]
+pub fn impl_or_filter(input: TokenStream) -> TokenStream {
+    let ImplFilterInput { types } =
+        parse_macro_input!(input as ImplFilterInput);
+
+    if types.len() == 1 {
+        return TokenStream::new();
+    }
+
+    let mut impl_types = Vec::new();
+    let mut bundle_types = Vec::new();
+    let mut state_types = Vec::new();
+    let mut matches = Vec::new();
+    let mut prepare = Vec::new();
+    let mut filter_row = Vec::new();
+    for (i, t) in types.iter().enumerate() {
+        impl_types.push(quote! { #t: Filter });
+        bundle_types.push(quote! { #t });
+        state_types.push(quote! { Option<#t::State> });
+        matches.push(quote! { #t::matches(archetype_id) });
+        prepare.push(quote! {
+            if #t::matches(archetype.id()) {
+                Some(#t::prepare(archetype, sparse_sets, ticks)?)
+            } else {
+                None
+            }
+        });
+        let i = syn::Index::from(i);
+        filter_row.push(quote! {
+            state.#i.as_ref().is_some_and(|state| #t::filter_row(state, row))
+        });
+    }
+
+    let output = quote! {
+        impl<#(#impl_types),*> Filter for Or<(#(#bundle_types),*)> {
+            type State = (#(#state_types),*);
+
+            fn matches(archetype_id: &ArchetypeId) -> bool {
+                #(#matches)||*
+            }
+
+            fn access(access: &mut SystemAccess) {
+                #(#bundle_types::access(access);)*
+            }
+
+            fn prepare(archetype: &Archetype, sparse_sets: &SparseSets, ticks: SystemTicks) -> Result<Self::State, ArchetypeError> {
+                Ok((#(#prepare),*))
+            }
+
+            fn filter_row(state: &Self::State, row: usize) -> bool {
+                #(#filter_row)||*
+            }
+
+            fn is_archetypal() -> bool {
+                #(#bundle_types::is_archetypal())&&*
+            }
+        }
+    };
+
+    TokenStream::from(output)
+}
+
+#[proc_macro]
+pub fn impl_system_param(input: TokenStream) -> TokenStream {
+    let ImplFetchInput { types } = parse_macro_input!(input as ImplFetchInput);
+
+    let mut impl_types = Vec::new();
+    let mut bundle_types = Vec::new();
+    let mut state_types = Vec::new();
+    let mut item_types = Vec::new();
+    let mut init_state = Vec::new();
+    let mut states = Vec::new();
+    let mut get_param = Vec::new();
+    let mut access = Vec::new();
+    let mut apply = Vec::new();
+    for (i, t) in types.iter().enumerate() {
+        let state = format_ident!("state_{}", i);
+        impl_types.push(quote! { #t: SystemParam });
+        bundle_types.push(quote! { #t });
+        state_types.push(quote! { #t::State });
+        item_types.push(quote! { #t::Item<'w, 's> });
+        init_state.push(quote! { #t::init_state() });
+        get_param.push(quote! { #t::get_param(#state, ctx)? });
+        access.push(quote! { #t::access(access); });
+        apply.push(quote! { #t::apply(#state, commands); });
+        states.push(state);
+    }
+
+    let output = quote! {
+        impl<#(#impl_types),*> SystemParam for (#(#bundle_types,)*) {
+            type State = (#(#state_types,)*);
+            type Item<'w, 's> = (#(#item_types,)*);
+
+            fn init_state() -> Self::State {
+                (#(#init_state,)*)
+            }
+
+            fn access(access: &mut SystemAccess) {
+                #(#access)*
+            }
+
+            fn get_param<'w, 's>(
+                state: &'s mut Self::State,
+                ctx: SharedCtx<'w>,
+            ) -> Result<Self::Item<'w, 's>, ParsecError> {
+                let (#(#states,)*) = state;
+                Ok((#(#get_param,)*))
+            }
+
+            fn apply(state: &mut Self::State, commands: &mut Commands) {
+                let (#(#states,)*) = state;
+                #(#apply)*
+            }
+        }
+    };
+
+    TokenStream::from(output)
+}
+
+#[proc_macro]
+pub fn impl_into_system(input: TokenStream) -> TokenStream {
+    let ImplFetchInput { types } = parse_macro_input!(input as ImplFetchInput);
+
+    let mut impl_types = Vec::new();
+    let mut bundle_types = Vec::new();
+    let mut item_types = Vec::new();
+    let mut params = Vec::new();
+    for (i, t) in types.iter().enumerate() {
+        impl_types.push(quote! { #t: SystemParam + 'static });
+        bundle_types.push(quote! { #t });
+        item_types.push(quote! { #t::Item<'w, 's> });
+        params.push(format_ident!("param_{}", i));
+    }
+
+    let function_bounds = quote! {
+        Func: FnMut(#(#bundle_types),*) -> Out
+            + for<'w, 's> FnMut(#(#item_types),*) -> Out
+            + Send
+            + Sync
+            + 'static,
+        Out: SystemOutput + 'static,
+    };
+
+    let output = quote! {
+        impl<Func, Out, #(#impl_types),*> IntoSystem<fn(#(#bundle_types),*) -> Out> for Func
+        where
+            #function_bounds
+        {
+            type ResultingSystem = ParamFunctionSystem<Func, (#(#bundle_types,)*), Out>;
+            fn into_system(self) -> Self::ResultingSystem {
+                ParamFunctionSystem::new(self)
+            }
+        }
+
+        impl<Func, Out, #(#impl_types),*> System for ParamFunctionSystem<Func, (#(#bundle_types,)*), Out>
+        where
+            #function_bounds
+        {
+            fn run<'a>(&mut self, ctx: Ctx<'a>) -> Result<(), ParsecError> {
+                let shared_ctx = SharedCtx {
+                    world: ctx.world,
+                    resources: ctx.resources,
+                    assets: ctx.assets,
+                    ticks: ctx.world.system_ticks(),
+                };
+                self.run_shared(shared_ctx, ctx.commands)
+            }
+
+            fn access(&self) -> SystemAccess {
+                let mut access = SystemAccess::new();
+                <(#(#bundle_types,)*) as SystemParam>::access(&mut access);
+                access
+            }
+
+            fn run_shared<'a>(
+                &mut self,
+                ctx: SharedCtx<'a>,
+                commands: &mut Commands,
+            ) -> Result<(), ParsecError> {
+                // Calls `function` with parameter items instead of parameters.
+                #[allow(clippy::too_many_arguments)]
+                fn call_inner<Out, #(#bundle_types),*>(
+                    mut function: impl FnMut(#(#bundle_types),*) -> Out,
+                    #(#params: #bundle_types),*
+                ) -> Out {
+                    function(#(#params),*)
+                }
+
+                let state = self
+                    .state
+                    .get_or_insert_with(<(#(#bundle_types,)*) as SystemParam>::init_state);
+                let result = {
+                    let (#(#params,)*) =
+                        <(#(#bundle_types,)*) as SystemParam>::get_param(state, ctx)?;
+                    call_inner(&mut self.function, #(#params),*).into_result()
+                };
+                <(#(#bundle_types,)*) as SystemParam>::apply(state, commands);
+                result
             }
         }
     };
@@ -166,19 +506,76 @@ pub fn multiple_tuples(input: TokenStream) -> TokenStream {
     TokenStream::from(quote! { #(#output)* })
 }
 
-#[proc_macro_derive(Component)]
+#[proc_macro_derive(Component, attributes(component))]
 pub fn derive_component(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let ident = input.ident;
 
-    let expanded = quote! {
-        impl Copy for #ident {}
-        impl Clone for #ident {
-            fn clone(&self) -> Self {
-                *self
+    let mut storage = None;
+    let mut clone = None;
+    for attr in input.attrs.iter() {
+        if !attr.path().is_ident("component") {
+            continue;
+        }
+        let parsed = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("clone") {
+                clone = Some(if meta.input.peek(Token![=]) {
+                    let value = meta.value()?.parse::<LitStr>()?;
+                    Some(value.parse::<Path>()?)
+                } else {
+                    None
+                });
+                return Ok(());
+            }
+            if !meta.path.is_ident("storage") {
+                return Err(meta.error("unsupported component attribute"));
             }
+            let value = meta.value()?.parse::<LitStr>()?;
+            storage = Some(match value.value().as_str() {
+                "table" => format_ident!("Table"),
+                "sparse" => format_ident!("SparseSet"),
+                _ => {
+                    return Err(
+                        meta.error("expected `\"table\"` or `\"sparse\"`")
+                    );
+                },
+            });
+            Ok(())
+        });
+        if let Err(e) = parsed {
+            return TokenStream::from(e.to_compile_error());
+        }
+    }
+
+    let storage = storage.map(|storage| {
+        quote! {
+            const STORAGE: ::parsec_engine::ecs::world::component::StorageType =
+                ::parsec_engine::ecs::world::component::StorageType::#storage;
+        }
+    });
+    let clone = clone.map(|clone| {
+        let clone = match clone {
+            Some(path) => quote! {{
+                unsafe fn clone(src: *const u8, dst: *mut u8) {
+                    let value = unsafe { &*src.cast::<#ident>() };
+                    unsafe { dst.cast::<#ident>().write(#path(value)) }
+                }
+                clone
+            }},
+            None => quote! {
+                ::parsec_engine::ecs::world::component::clone_component::<#ident>
+            },
+        };
+        quote! {
+            const CLONE: Option<::parsec_engine::ecs::world::component::CloneFn> =
+                Some(#clone);
+        }
+    });
+    let expanded = quote! {
+        impl Component for #ident {
+            #storage
+            #clone
         }
-        impl Component for #ident {}
     };
 
     TokenStream::from(expanded)
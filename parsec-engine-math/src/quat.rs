@@ -21,15 +21,12 @@ impl Mul for Quat {
             + self.i * self.i
             + self.j * self.j
             + self.k * self.k;
+        let product = self.product(rhs);
         Quat::new(
-            (self.r * rhs.r - self.i * rhs.i - self.j * rhs.j - self.k * rhs.k)
-                / magnitude,
-            (self.r * rhs.i + self.i * rhs.r - self.j * rhs.k + self.k * rhs.j)
-                / magnitude,
-            (self.r * rhs.j + self.j * rhs.r + self.i * rhs.k - self.k * rhs.i)
-                / magnitude,
-            (self.r * rhs.k + self.k * rhs.r - self.i * rhs.j + self.j * rhs.i)
-                / magnitude,
+            product.r / magnitude,
+            product.i / magnitude,
+            product.j / magnitude,
+            product.k / magnitude,
         )
     }
 }
@@ -42,7 +39,7 @@ impl Mul<Vec3f> for Quat {
     type Output = Vec3f;
     fn mul(self, rhs: Vec3f) -> Self::Output {
         let vec_quat = Quat::new(0.0, rhs.x, rhs.y, rhs.z);
-        let quat_mul = self * vec_quat * self.invert();
+        let quat_mul = self.product(vec_quat).product(self.invert());
         Vec3f::new(quat_mul.i, quat_mul.j, quat_mul.k)
     }
 }
@@ -51,7 +48,7 @@ impl Mul<Quat> for Vec3f {
     type Output = Vec3f;
     fn mul(self, rhs: Quat) -> Self::Output {
         let vec_quat = Quat::new(0.0, self.x, self.y, self.z);
-        let quat_mul = rhs.invert() * vec_quat * rhs;
+        let quat_mul = rhs.invert().product(vec_quat).product(rhs);
         Vec3f::new(quat_mul.i, quat_mul.j, quat_mul.k)
     }
 }
@@ -72,6 +69,17 @@ impl Quat {
 
     pub fn invert(self) -> Quat { Quat::new(self.r, -self.i, -self.j, -self.k) }
 
+    /// Hamilton product, not normalized unlike [`Mul`], so that rotating
+    /// vectors keeps their length and works for zero vectors.
+    fn product(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.r * rhs.r - self.i * rhs.i - self.j * rhs.j - self.k * rhs.k,
+            self.r * rhs.i + self.i * rhs.r - self.j * rhs.k + self.k * rhs.j,
+            self.r * rhs.j + self.j * rhs.r + self.i * rhs.k - self.k * rhs.i,
+            self.r * rhs.k + self.k * rhs.r - self.i * rhs.j + self.j * rhs.i,
+        )
+    }
+
     pub fn into_matrix(&self) -> Matrix4f {
         Matrix4f::new([
             [
@@ -112,3 +120,34 @@ impl Quat {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3f, b: Vec3f) {
+        let mut difference = a - b;
+        assert!(difference.length() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn rotating_vectors() {
+        let rotation =
+            Quat::from_euler(Vec3f::new(0.0, 0.0, std::f32::consts::FRAC_PI_2));
+
+        // The zero vector stays zero instead of turning into NaN.
+        let zero = Vec3f::new(0.0, 0.0, 0.0);
+        assert_eq!(rotation * zero, zero);
+        assert_eq!(zero * rotation, zero);
+
+        // Non-unit vectors keep their length.
+        let mut rotated = rotation * Vec3f::new(3.0, 0.0, 0.0);
+        assert!((rotated.length() - 3.0).abs() < 1e-5);
+        assert!(rotated.x.abs() < 1e-5 && rotated.z.abs() < 1e-5);
+        assert_close(rotated * rotation, Vec3f::new(3.0, 0.0, 0.0));
+
+        let vector = Vec3f::new(0.5, -2.0, 4.0);
+        assert_close((rotation * vector) * rotation, vector);
+        assert_close(Quat::IDENTITY * vector, vector);
+    }
+}
@@ -0,0 +1,334 @@
+//! Modele responsible managing the lifecycle of an application.
+
+use parsec_engine_math::vec::Vec2f;
+
+use crate::{
+    app::sub_app::SubApp,
+    assets::AssetLibrary,
+    ecs::{
+        event::{Event, Events},
+        resources::Resources,
+        scene::TypeRegistry,
+        system::{
+            SystemTrigger, Systems,
+            state::{NextState, State, States},
+        },
+        world::World,
+    },
+    graphics::ActiveEventLoop,
+    input::{
+        key::StorageKeyCode,
+        keys::KeyboardInputEvent,
+        mouse::{MouseButtonEvent, MouseMovementEvent, MouseWheelEvent},
+    },
+};
+
+pub mod sub_app;
+
+#[allow(unused)]
+pub struct App {
+    pub systems: Systems,
+    world: World,
+    resources: Resources,
+    assets: AssetLibrary,
+    /// Functions updating registered [`Events`] at the start of every frame.
+    event_updates: Vec<fn(&mut Resources)>,
+    /// Isolated worlds updated after the main one, along with their labels.
+    sub_apps: Vec<(&'static str, SubApp)>,
+}
+
+impl Default for App {
+    fn default() -> Self { Self::new() }
+}
+
+impl App {
+    pub fn new() -> App {
+        let mut app = App {
+            systems: Systems::new(),
+            world: World::new(),
+            resources: Resources::new(),
+            assets: AssetLibrary::new(),
+            event_updates: Vec::new(),
+            sub_apps: Vec::new(),
+        };
+        app.add_event::<KeyboardInputEvent>();
+        app.add_event::<MouseMovementEvent>();
+        app.add_event::<MouseButtonEvent>();
+        app.add_event::<MouseWheelEvent>();
+        app.resources.add(TypeRegistry::new());
+        app
+    }
+
+    /// Registers events of type `E` that can be sent and read with
+    /// [`EventWriter`][crate::ecs::event::EventWriter] and
+    /// [`EventReader`][crate::ecs::event::EventReader].
+    /// Events are dropped two frames after being sent.
+    pub fn add_event<E: Event>(&mut self) -> &mut App {
+        if self.resources.get::<Events<E>>().is_none() {
+            self.resources.add(Events::<E>::new());
+            self.event_updates.push(Events::<E>::update_resource);
+        }
+        self
+    }
+
+    /// Registers states of type `S`, starting in `initial`. The current state
+    /// is stored in the [`State`] resource and transitions are queued with the
+    /// [`NextState`] resource.
+    pub fn add_state<S: States>(&mut self, initial: S) -> &mut App {
+        self.resources.add(State::new(initial));
+        self.resources.add(NextState::<S>::new());
+        self.systems.add_state::<S>();
+        self
+    }
+
+    /// Adds `sub_app` under `label`, replacing the sub-app previously added
+    /// under the same label.
+    pub fn add_sub_app(
+        &mut self,
+        label: &'static str,
+        sub_app: SubApp,
+    ) -> &mut App {
+        match self.sub_app_mut(label) {
+            Some(old) => *old = sub_app,
+            None => self.sub_apps.push((label, sub_app)),
+        }
+        self
+    }
+
+    /// Gets the sub-app added under `label`.
+    pub fn sub_app(&self, label: &'static str) -> Option<&SubApp> {
+        self.sub_apps
+            .iter()
+            .find(|(sub_label, _)| *sub_label == label)
+            .map(|(_, sub_app)| sub_app)
+    }
+
+    /// Gets the sub-app added under `label` mutably.
+    pub fn sub_app_mut(&mut self, label: &'static str) -> Option<&mut SubApp> {
+        self.sub_apps
+            .iter_mut()
+            .find(|(sub_label, _)| *sub_label == label)
+            .map(|(_, sub_app)| sub_app)
+    }
+
+    /// Sends `event` and executes systems registered for `system_trigger`.
+    fn send_event<E: Event>(
+        &mut self,
+        event: E,
+        system_trigger: SystemTrigger,
+    ) {
+        if let Some(mut events) = self.resources.get_mut::<Events<E>>() {
+            events.send(event);
+        }
+        self.execute_system(system_trigger);
+    }
+
+    pub fn run(&mut self) {
+        self.execute_system(SystemTrigger::Start);
+
+        let event_loop =
+            winit::event_loop::EventLoop::new().expect("Valid event loop");
+        event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+        event_loop.run_app(self).unwrap();
+    }
+
+    /// Executes systems registered for `system_trigger`, first in the main app
+    /// and then in all sub-apps. Sub-apps fire [frame
+    /// triggers][SystemTrigger::FRAME] only in [`App::update_sub_apps`].
+    pub fn execute_system(&mut self, system_trigger: SystemTrigger) {
+        if let Err(err) = self.systems.fire_trigger(
+            system_trigger,
+            &mut self.world,
+            &mut self.resources,
+            &mut self.assets,
+        ) {
+            panic!(
+                "System triggered with {:?} returned: {}",
+                system_trigger, err
+            );
+        }
+        if SystemTrigger::FRAME.contains(&system_trigger) {
+            return;
+        }
+        for (label, sub_app) in self.sub_apps.iter_mut() {
+            if let Err(err) =
+                sub_app.fire_trigger(system_trigger, &mut self.assets)
+            {
+                panic!(
+                    "System of sub-app {} triggered with {:?} returned: {}",
+                    label, system_trigger, err
+                );
+            }
+        }
+    }
+
+    /// Extracts data from the main app into all sub-apps and runs their update
+    /// systems. See [`SubApp::update`].
+    pub fn update_sub_apps(&mut self) {
+        for (label, sub_app) in self.sub_apps.iter_mut() {
+            if let Err(err) =
+                sub_app.update(&self.world, &self.resources, &mut self.assets)
+            {
+                panic!("Sub-app {} failed to update: {}", label, err);
+            }
+        }
+    }
+}
+
+impl winit::application::ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.resources.add(ActiveEventLoop::new(event_loop));
+        self.execute_system(SystemTrigger::LateStart);
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: winit::event::DeviceEvent,
+    ) {
+        if let winit::event::DeviceEvent::MouseMotion { delta } = event {
+            self.send_event(
+                MouseMovementEvent::delta(Vec2f::new(
+                    delta.0 as f32,
+                    delta.1 as f32,
+                )),
+                SystemTrigger::MouseMovement,
+            );
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        _id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        match event {
+            winit::event::WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        state, logical_key, ..
+                    },
+                ..
+            } => {
+                let key_code = match logical_key {
+                    winit::keyboard::Key::Named(named) => {
+                        StorageKeyCode::Noncharacter(named)
+                    },
+                    winit::keyboard::Key::Character(char) => {
+                        StorageKeyCode::Character(char.to_lowercase().into())
+                    },
+                    _ => return,
+                };
+
+                self.send_event(
+                    KeyboardInputEvent::new(key_code, state),
+                    SystemTrigger::KeyboardInput,
+                );
+            },
+            winit::event::WindowEvent::CursorLeft { device_id: _ } => {
+                self.execute_system(SystemTrigger::WindowCursorLeft);
+            },
+            winit::event::WindowEvent::Resized(_) => {
+                self.execute_system(SystemTrigger::WindowResized);
+            },
+            winit::event::WindowEvent::CursorMoved {
+                device_id: _,
+                position,
+            } => {
+                self.send_event(
+                    MouseMovementEvent::position(Vec2f::new(
+                        position.x as f32,
+                        position.y as f32,
+                    )),
+                    SystemTrigger::MouseMovement,
+                );
+            },
+            winit::event::WindowEvent::MouseInput {
+                device_id: _,
+                state,
+                button,
+            } => {
+                self.send_event(
+                    MouseButtonEvent::new(button, state),
+                    SystemTrigger::MouseButton,
+                );
+            },
+            winit::event::WindowEvent::MouseWheel {
+                device_id: _,
+                delta,
+                phase: _,
+            } => {
+                let processed_delta = match delta {
+                    winit::event::MouseScrollDelta::PixelDelta(
+                        winit::dpi::PhysicalPosition { x, y },
+                    ) => Vec2f::new(x as f32, y as f32),
+                    winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                        Vec2f::new(x, y)
+                    },
+                };
+
+                self.send_event(
+                    MouseWheelEvent::new(processed_delta),
+                    SystemTrigger::MouseWheel,
+                );
+            },
+            winit::event::WindowEvent::CloseRequested => {
+                self.resources.remove::<ActiveEventLoop>().unwrap();
+                self.execute_system(SystemTrigger::End);
+                event_loop.exit();
+            },
+            winit::event::WindowEvent::RedrawRequested => {
+                self.execute_system(SystemTrigger::Render);
+            },
+            _ => (),
+        }
+    }
+
+    fn about_to_wait(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+    ) {
+        for update in self.event_updates.iter() {
+            update(&mut self.resources);
+        }
+        for system_trigger in SystemTrigger::FRAME {
+            self.execute_system(system_trigger);
+        }
+        self.update_sub_apps();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::system::{
+        condition::in_state, config::IntoSystemConfig, param::ResMut,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Phase {
+        Loading,
+        Running,
+    }
+
+    fn finish_loading(mut next: ResMut<NextState<Phase>>) {
+        next.set(Phase::Running);
+    }
+
+    #[test]
+    fn states_without_transition_systems() {
+        let mut app = App::new();
+        app.add_state(Phase::Loading);
+        app.systems.add(
+            SystemTrigger::Update,
+            finish_loading.run_if(in_state(Phase::Loading)),
+        );
+
+        app.execute_system(SystemTrigger::Update);
+        app.execute_system(SystemTrigger::Update);
+        let state = app.resources.get::<State<Phase>>().unwrap();
+        assert_eq!(*state.get(), Phase::Running);
+    }
+}
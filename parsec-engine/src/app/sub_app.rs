@@ -0,0 +1,263 @@
+//! Module responsible for worlds isolated from the main one.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    assets::AssetLibrary,
+    ecs::{
+        entity::Entity,
+        resources::Resources,
+        system::{SystemTrigger, Systems},
+        world::{World, WorldError, component::Component},
+    },
+    error::ParsecError,
+};
+
+/// Copies data from the main app into a [`SubApp`].
+type ExtractFn = Box<dyn FnMut(&mut Extract) -> Result<(), ParsecError>>;
+
+/// Data given to extract functions of a [`SubApp`].
+pub struct Extract<'a> {
+    /// World of the main app.
+    pub main_world: &'a World,
+    /// Resources of the main app.
+    pub main_resources: &'a Resources,
+    /// World of the sub-app.
+    pub world: &'a mut World,
+    /// Resources of the sub-app.
+    pub resources: &'a mut Resources,
+    entities: &'a mut HashMap<Entity, Entity>,
+}
+
+impl Extract<'_> {
+    /// Gets the entity of the sub-app world mirroring `main_entity`, spawning
+    /// a new one if there is none yet.
+    /// Mirrors are deleted together with their main entities.
+    ///
+    /// # Errors
+    ///
+    /// - If the mirror couldn't be spawned.
+    pub fn entity(
+        &mut self,
+        main_entity: Entity,
+    ) -> Result<Entity, WorldError> {
+        if let Some(entity) = self.entities.get(&main_entity) {
+            return Ok(*entity);
+        }
+        let entity = self.world.spawn(())?;
+        self.entities.insert(main_entity, entity);
+        Ok(entity)
+    }
+
+    /// Gets the entity of the sub-app world mirroring `main_entity`, if there
+    /// is one.
+    pub fn get_entity(&self, main_entity: Entity) -> Option<Entity> {
+        self.entities.get(&main_entity).copied()
+    }
+}
+
+/// Isolated [`World`] with its own [`Resources`] and [`Systems`], added to an
+/// [`App`][crate::app::App] with
+/// [`App::add_sub_app`][crate::app::App::add_sub_app].
+/// Assets are shared with the main app.
+///
+/// Every frame, after the main app updates, the extract functions copy data
+/// from the main app and then the update systems of the sub-app run.
+/// Other triggers are fired right after the main app fires them.
+pub struct SubApp {
+    pub systems: Systems,
+    world: World,
+    resources: Resources,
+    extract_fns: Vec<ExtractFn>,
+    /// Main app entities mapped to the entities mirroring them.
+    entities: HashMap<Entity, Entity>,
+}
+
+impl Default for SubApp {
+    fn default() -> Self { Self::new() }
+}
+
+impl SubApp {
+    pub fn new() -> SubApp {
+        SubApp {
+            systems: Systems::new(),
+            world: World::new(),
+            resources: Resources::new(),
+            extract_fns: Vec::new(),
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn world(&self) -> &World { &self.world }
+
+    pub fn world_mut(&mut self) -> &mut World { &mut self.world }
+
+    pub fn resources(&self) -> &Resources { &self.resources }
+
+    pub fn resources_mut(&mut self) -> &mut Resources { &mut self.resources }
+
+    /// Registers `extract` to copy data from the main app every frame.
+    /// Extract functions run in registration order.
+    pub fn add_extract(
+        &mut self,
+        extract: impl FnMut(&mut Extract) -> Result<(), ParsecError> + 'static,
+    ) -> &mut SubApp {
+        self.extract_fns.push(Box::new(extract));
+        self
+    }
+
+    /// Copies components of type `T` to [mirrors][Extract::entity] of their
+    /// entities every frame. Components removed in the main world are
+    /// removed from the mirrors too.
+    pub fn extract_component<T: Component + Clone>(&mut self) -> &mut SubApp {
+        let mut extracted = HashSet::new();
+        self.add_extract(move |extract| {
+            let main_world = extract.main_world;
+            let mut current = HashSet::new();
+            for (main_entity, value) in main_world.query::<T>().iter() {
+                let entity = extract.entity(main_entity)?;
+                if !extract.world.has::<T>(entity) {
+                    extract.world.add_components(entity, value.clone())?;
+                } else {
+                    *extract.world.get_mut::<T>(entity)? = value.clone();
+                }
+                current.insert(main_entity);
+            }
+            for main_entity in extracted.difference(&current) {
+                if let Some(entity) = extract.get_entity(*main_entity)
+                    && extract.world.has::<T>(entity)
+                {
+                    extract.world.remove_components::<T>(entity)?;
+                }
+            }
+            extracted = current;
+            Ok(())
+        })
+    }
+
+    /// Deletes mirrors of deleted main entities and runs all extract
+    /// functions.
+    ///
+    /// # Errors
+    ///
+    /// - If any extract function fails.
+    pub fn extract(
+        &mut self,
+        main_world: &World,
+        main_resources: &Resources,
+    ) -> Result<(), ParsecError> {
+        let mut result = Ok(());
+        self.entities.retain(|main_entity, entity| {
+            if main_world.contains(*main_entity) {
+                return true;
+            }
+            if self.world.contains(*entity)
+                && let Err(err) = self.world.delete(*entity)
+            {
+                result = Err(err);
+            }
+            false
+        });
+        result?;
+
+        let mut extract = Extract {
+            main_world,
+            main_resources,
+            world: &mut self.world,
+            resources: &mut self.resources,
+            entities: &mut self.entities,
+        };
+        for extract_fn in self.extract_fns.iter_mut() {
+            extract_fn(&mut extract)?;
+        }
+        Ok(())
+    }
+
+    /// Executes all the systems of the sub-app registered for
+    /// `system_trigger`. See [`Systems::fire_trigger`].
+    ///
+    /// # Errors
+    ///
+    /// - If any system fails.
+    pub fn fire_trigger(
+        &mut self,
+        system_trigger: SystemTrigger,
+        assets: &mut AssetLibrary,
+    ) -> Result<(), ParsecError> {
+        self.systems.fire_trigger(
+            system_trigger,
+            &mut self.world,
+            &mut self.resources,
+            assets,
+        )
+    }
+
+    /// Extracts data from the main app and runs the update systems.
+    ///
+    /// # Errors
+    ///
+    /// - If extraction or any system fails.
+    pub fn update(
+        &mut self,
+        main_world: &World,
+        main_resources: &Resources,
+        assets: &mut AssetLibrary,
+    ) -> Result<(), ParsecError> {
+        self.extract(main_world, main_resources)?;
+        for system_trigger in SystemTrigger::FRAME {
+            self.fire_trigger(system_trigger, assets)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ctx::Ctx;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Position(u32);
+    impl Component for Position {}
+
+    #[test]
+    fn component_extraction() {
+        let mut main_world = World::new();
+        let main_resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut sub_app = SubApp::new();
+        sub_app.resources_mut().add(0_usize);
+        sub_app.extract_component::<Position>();
+        sub_app.systems.add(SystemTrigger::Update, |ctx: Ctx| {
+            let count = ctx.world.query::<Position>().iter().count();
+            *ctx.resources.get_mut::<usize>().unwrap() = count;
+        });
+
+        let a = main_world.spawn((Position(1), 1_u32)).unwrap();
+        let b = main_world.spawn(Position(2)).unwrap();
+        main_world.spawn(3_u32).unwrap();
+        let mut update = |sub_app: &mut SubApp, main_world: &World| {
+            sub_app
+                .update(main_world, &main_resources, &mut assets)
+                .unwrap();
+        };
+        update(&mut sub_app, &main_world);
+        assert_eq!(*sub_app.resources().get::<usize>().unwrap(), 2);
+        assert_eq!(sub_app.world().entity_count(), 2);
+        assert_eq!(sub_app.world().query::<u32>().iter().count(), 0);
+
+        *main_world.get_mut::<Position>(a).unwrap() = Position(10);
+        main_world.remove_components::<Position>(b).unwrap();
+        update(&mut sub_app, &main_world);
+        let mut query = sub_app.world().query::<Position>();
+        let values = query.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>();
+        assert_eq!(values, vec![Position(10)]);
+        drop(query);
+
+        main_world.delete(a).unwrap();
+        main_world.delete(b).unwrap();
+        update(&mut sub_app, &main_world);
+        assert_eq!(sub_app.world().entity_count(), 0);
+        assert_eq!(*sub_app.resources().get::<usize>().unwrap(), 0);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod mesh;
+pub mod scene;
 pub mod shader;
 pub mod texture;
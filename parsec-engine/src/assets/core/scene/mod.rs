@@ -0,0 +1,171 @@
+use crate::{
+    assets::{Asset, AssetHandle, AssetLibrary},
+    ecs::{
+        resources::Resources,
+        scene::{EntityMap, SceneData, SceneEntity, SceneError, TypeRegistry},
+        world::World,
+    },
+};
+
+/// Scene with component values stored as JSON text, which postcard can encode.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CookedScene {
+    entities: Vec<SceneEntity<String>>,
+}
+
+/// Entities and components that can be spawned into a [`World`] any number of
+/// times. Cooked from a `.scene` file in the JSON form written by
+/// [`World::save_scene`]. Assets are referenced by name.
+#[derive(Debug)]
+pub struct Scene {
+    data: SceneData<serde_json::Value>,
+}
+
+impl Asset for Scene {
+    type Cooked = CookedScene;
+
+    const ASSET_TYPE: &'static str = "scene";
+    const EXTENSIONS: &'static [&'static str] = &["scene"];
+
+    fn cook(data: &[u8], _extension: &str) -> Self::Cooked {
+        let scene = serde_json::from_slice::<SceneData<serde_json::Value>>(data)
+            .expect("invalid scene description");
+        let entities = scene
+            .entities
+            .into_iter()
+            .map(|entity| SceneEntity {
+                entity: entity.entity,
+                components: entity
+                    .components
+                    .into_iter()
+                    .map(|(name, value)| (name, value.to_string()))
+                    .collect(),
+            })
+            .collect();
+        CookedScene { entities }
+    }
+
+    fn load(cooked: Self::Cooked, _resources: &mut Resources) -> Self {
+        let entities = cooked
+            .entities
+            .into_iter()
+            .map(|entity| SceneEntity {
+                entity: entity.entity,
+                components: entity
+                    .components
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = serde_json::from_str(&value)
+                            .expect("cooked scene is valid JSON");
+                        (name, value)
+                    })
+                    .collect(),
+            })
+            .collect();
+        Scene {
+            data: SceneData { entities },
+        }
+    }
+}
+
+impl Scene {
+    /// Spawns a new copy of all entities of `self` into `world`.
+    /// Returns the map from scene entities to spawned ones.
+    ///
+    /// # Errors
+    ///
+    /// - If the scene contains components that aren't registered in `registry`.
+    /// - If any component fails to deserialize.
+    pub fn instantiate(
+        &self,
+        world: &mut World,
+        registry: &TypeRegistry,
+    ) -> Result<EntityMap, SceneError> {
+        world.spawn_scene_json(registry, self.data.clone())
+    }
+}
+
+impl World {
+    /// Spawns a new copy of the scene loaded under `scene`.
+    /// See [`Scene::instantiate`].
+    ///
+    /// # Errors
+    ///
+    /// - If the scene isn't loaded into `assets`.
+    /// - If the scene contains components that aren't registered in `registry`.
+    /// - If any component fails to deserialize.
+    pub fn instantiate_scene(
+        &mut self,
+        scene: AssetHandle<Scene>,
+        assets: &AssetLibrary,
+        registry: &TypeRegistry,
+    ) -> Result<EntityMap, SceneError> {
+        assets
+            .get(scene)
+            .ok_or(SceneError::SceneNotLoaded)?
+            .instantiate(self, registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::hierarchy::Parent,
+        renderer::components::{
+            mesh_renderer::MeshRenderer, transform::Transform,
+        },
+    };
+
+    #[test]
+    fn cook_and_instantiate() {
+        let description = br#"{"entities": [
+            {
+                "entity": {"id": 0, "generation": 0},
+                "components": {
+                    "Transform": {
+                        "position": {"x": 0.0, "y": 1.0, "z": 0.0},
+                        "scale": {"x": 1.0, "y": 1.0, "z": 1.0},
+                        "rotation": {"i": 0.0, "j": 0.0, "k": 0.0, "r": 1.0}
+                    },
+                    "MeshRenderer": {"mesh": "ship", "material_id": 0},
+                    "Children": {
+                        "first": {"id": 1, "generation": 0},
+                        "last": {"id": 1, "generation": 0},
+                        "len": 1
+                    }
+                }
+            },
+            {
+                "entity": {"id": 1, "generation": 0},
+                "components": {
+                    "Parent": {
+                        "parent": {"id": 0, "generation": 0},
+                        "next_sibling": null
+                    }
+                }
+            }
+        ]}"#;
+        let cooked = postcard::to_stdvec(&Scene::cook(description, "scene"))
+            .unwrap();
+        let cooked = postcard::from_bytes::<CookedScene>(&cooked).unwrap();
+        let scene = Scene::load(cooked, &mut Resources::new());
+
+        let registry = TypeRegistry::new();
+        let mut world = World::new();
+        let first = scene.instantiate(&mut world, &registry).unwrap();
+        let second = scene.instantiate(&mut world, &registry).unwrap();
+        assert_eq!(world.entity_count(), 4);
+
+        for map in [first, second] {
+            let (_, ship) =
+                map.iter().find(|(saved, _)| saved.id() == 0).unwrap();
+            let mesh = world.get::<MeshRenderer>(ship).unwrap().mesh;
+            assert_eq!(serde_json::to_string(&mesh).unwrap(), r#""ship""#);
+            assert_eq!(world.get::<Transform>(ship).unwrap().position.y, 1.0);
+            let children = world.children(ship).collect::<Vec<_>>();
+            assert_eq!(children.len(), 1);
+            assert_eq!(world.get::<Parent>(children[0]).unwrap().get(), ship);
+        }
+    }
+}
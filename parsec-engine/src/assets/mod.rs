@@ -1,10 +1,11 @@
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fs::File,
     io::BufReader,
     marker::PhantomData,
     path::PathBuf,
+    sync::Mutex,
     time::SystemTime,
 };
 
@@ -36,10 +37,41 @@ impl<T: Asset> AssetHandle<T> {
     }
 }
 
+/// Gets a `'static` copy of `name`. Every distinct name is leaked only once.
+fn intern_name(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_owned().into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// Handles are saved as asset names.
+impl<T: Asset> serde::Serialize for AssetHandle<T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<'de, T: Asset> serde::Deserialize<'de> for AssetHandle<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(AssetHandle::new(intern_name(&name)))
+    }
+}
+
 #[derive(Debug)]
 pub struct AssetLibrary {
     manifest: Manifest,
-    assets: HashMap<TypeId, Vec<(&'static str, Box<dyn Any>)>>,
+    assets: HashMap<TypeId, Vec<(&'static str, Box<dyn Any + Send + Sync>)>>,
 }
 
 impl AssetLibrary {
@@ -72,7 +104,7 @@ impl AssetLibrary {
         let asset = T::load(cooked, resources);
         let asset_vec =
             self.assets.entry(TypeId::of::<T>()).or_insert(Vec::new());
-        asset_vec.push((name, Box::new(asset) as Box<dyn Any>));
+        asset_vec.push((name, Box::new(asset) as Box<dyn Any + Send + Sync>));
         Ok(AssetHandle::new(name))
     }
 
@@ -84,7 +116,7 @@ impl AssetLibrary {
     }
 }
 
-pub trait Asset: 'static {
+pub trait Asset: Send + Sync + 'static {
     type Cooked: serde::Serialize + serde::de::DeserializeOwned + 'static;
 
     const ASSET_TYPE: &'static str;
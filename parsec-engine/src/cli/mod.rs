@@ -10,7 +10,10 @@ use std::{
 use clap::Parser;
 
 use crate::{
-    assets::{Asset, AssetDescription, Manifest, core::{mesh::Mesh, shader::Shader}},
+    assets::{
+        Asset, AssetDescription, Manifest,
+        core::{mesh::Mesh, scene::Scene, shader::Shader},
+    },
     error::{OptionNoneErr, ParsecError},
 };
 
@@ -147,6 +150,7 @@ pub fn run_cli(mut cooker: Cooker) {
     let mut manifest = Manifest::load();
     cooker.register::<Mesh>();
     cooker.register::<Shader>();
+    cooker.register::<Scene>();
 
     match args.command {
         Commands::Add { name, path } => {
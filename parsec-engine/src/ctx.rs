@@ -1,10 +1,25 @@
 use crate::{
     assets::AssetLibrary,
-    ecs::{resources::Resources, world::World},
+    ecs::{
+        commands::Commands,
+        resources::Resources,
+        world::{World, change_detection::SystemTicks},
+    },
 };
 
 pub struct Ctx<'a> {
     pub world: &'a mut World,
     pub resources: &'a mut Resources,
     pub assets: &'a mut AssetLibrary,
+    pub commands: &'a mut Commands,
+}
+
+/// Read-only counterpart of [`Ctx`] given to systems that can run in parallel.
+#[derive(Clone, Copy)]
+pub struct SharedCtx<'a> {
+    pub world: &'a World,
+    pub resources: &'a Resources,
+    pub assets: &'a AssetLibrary,
+    /// Ticks of the current run of the system.
+    pub ticks: SystemTicks,
 }
@@ -0,0 +1,202 @@
+//! Module responsible for deferring structural changes to the world.
+
+use std::fmt::Debug;
+
+use crate::{
+    ecs::{
+        entity::Entity,
+        resources::{ResourceMarker, Resources},
+        world::{
+            World, add_component::AddComponent,
+            remove_component::RemoveComponent, spawn::Spawn,
+        },
+    },
+    error::ParsecError,
+};
+
+/// A single deferred operation.
+pub type Command = Box<
+    dyn FnOnce(&mut World, &mut Resources) -> Result<(), ParsecError>
+        + Send
+        + Sync,
+>;
+
+/// Queue of operations applied to [`World`] and [`Resources`] at a later point.
+/// Useful for spawning or deleting entities while a
+/// [`Query`][crate::ecs::world::query::Query] is borrowing the archetypes.
+///
+/// [`Systems`][crate::ecs::system::Systems] apply the queue after every system.
+#[derive(Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Debug for Commands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Commands")
+            .field("len", &self.queue.len())
+            .finish()
+    }
+}
+
+impl Commands {
+    pub fn new() -> Commands { Commands::default() }
+
+    /// Queues a custom operation.
+    pub fn push(
+        &mut self,
+        command: impl FnOnce(&mut World, &mut Resources) -> Result<(), ParsecError>
+        + Send
+        + Sync
+        + 'static,
+    ) {
+        self.queue.push(Box::new(command));
+    }
+
+    /// Queues spawning a new entity.
+    pub fn spawn<T: Spawn>(&mut self, bundle: T) {
+        self.push(move |world, _| {
+            world.spawn(bundle)?;
+            Ok(())
+        });
+    }
+
+    /// Queues deleting `entity`.
+    pub fn delete(&mut self, entity: Entity) {
+        self.push(move |world, _| Ok(world.delete(entity)?));
+    }
+
+    /// Queues deleting `entity` along with all its descendants.
+    pub fn delete_recursive(&mut self, entity: Entity) {
+        self.push(move |world, _| Ok(world.delete_recursive(entity)?));
+    }
+
+    /// Queues making `child` the last child of `parent`.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.push(move |world, _| Ok(world.set_parent(child, parent)?));
+    }
+
+    /// Queues adding components to `entity`.
+    pub fn add_components<T: AddComponent>(
+        &mut self,
+        entity: Entity,
+        bundle_extension: T,
+    ) {
+        self.push(move |world, _| {
+            Ok(world.add_components(entity, bundle_extension)?)
+        });
+    }
+
+    /// Queues removing components from `entity`.
+    pub fn remove_components<T: RemoveComponent>(&mut self, entity: Entity) {
+        self.push(move |world, _| Ok(world.remove_components::<T>(entity)?));
+    }
+
+    /// Queues adding a resource. If a resource of type `R` already exists it is replaced.
+    pub fn add_resource<R: ResourceMarker>(&mut self, resource: R) {
+        self.push(move |_, resources| {
+            resources.add(resource);
+            Ok(())
+        });
+    }
+
+    /// Queues removing the resource of type `R`.
+    pub fn remove_resource<R: ResourceMarker>(&mut self) {
+        self.push(move |_, resources| Ok(resources.remove::<R>()?));
+    }
+
+    /// Moves all operations queued in `other` to the end of `self`.
+    pub fn append(&mut self, other: &mut Commands) {
+        self.queue.append(&mut other.queue);
+    }
+
+    /// Gets the number of queued operations.
+    pub fn len(&self) -> usize { self.queue.len() }
+
+    pub fn is_empty(&self) -> bool { self.queue.is_empty() }
+
+    /// Applies all queued operations in the order they were queued, followed
+    /// by operations queued by [hooks][World::on_add] in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// - If any operation fails. The remaining operations are still applied
+    ///   and the first error is returned.
+    pub fn apply(
+        &mut self,
+        world: &mut World,
+        resources: &mut Resources,
+    ) -> Result<(), ParsecError> {
+        let mut result = Ok(());
+        let mut queued = std::mem::take(self);
+        loop {
+            for command in queued.queue.drain(..) {
+                let applied = command(world, resources);
+                if result.is_ok() {
+                    result = applied;
+                }
+            }
+            // Operations queued by hooks can trigger more hooks.
+            queued = world.take_commands();
+            if queued.is_empty() {
+                return result;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::world::fetch::Mut;
+
+    #[test]
+    fn deferred_spawn() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut commands = Commands::new();
+        let a = world.spawn((1_u32, 'a')).unwrap();
+        world.spawn((2_u32, 'b')).unwrap();
+
+        for (entity, (mut value, _)) in world.query::<(Mut<u32>, char)>().iter()
+        {
+            *value += 1;
+            commands.spawn(*value * 10);
+            if entity == a {
+                commands.delete(entity);
+            }
+        }
+        commands.add_resource(1.0_f32);
+        assert_eq!(commands.len(), 4);
+
+        commands.apply(&mut world, &mut resources).unwrap();
+        assert!(commands.is_empty());
+        assert!(!world.contains(a));
+        assert_eq!(*resources.get::<f32>().unwrap(), 1.0);
+
+        let mut query = world.query::<u32>();
+        let mut values = query.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![3, 20, 30]);
+    }
+
+    #[test]
+    fn failing_commands() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut commands = Commands::new();
+        world.on_add::<char>(|_, _, commands| commands.spawn(1_u32));
+        let a = world.spawn(true).unwrap();
+
+        commands.delete(a);
+        commands.delete(a);
+        commands.spawn('a');
+        commands.remove_resource::<f32>();
+        assert!(commands.apply(&mut world, &mut resources).is_err());
+        assert!(commands.is_empty());
+        assert!(world.take_commands().is_empty());
+        assert!(!world.contains(a));
+        assert_eq!(world.query::<char>().iter().count(), 1);
+        assert_eq!(world.query::<u32>().iter().count(), 1);
+    }
+}
@@ -1,13 +1,117 @@
 //! Module responsible for entity identification.
 
 /// Holds a unique id for an entity.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The generation distinguishes entities reusing the id of a deleted one.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub struct Entity {
     id: u32,
+    generation: u32,
 }
 
 impl Entity {
-    pub fn new(id: u32) -> Entity { Entity { id } }
+    pub fn new(id: u32, generation: u32) -> Entity { Entity { id, generation } }
 
     pub fn id(&self) -> u32 { self.id }
+
+    pub fn generation(&self) -> u32 { self.generation }
+}
+
+/// Position of an entity's components inside a [`World`][crate::ecs::world::World].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityLocation {
+    /// Index of the archetype storing the entity.
+    pub archetype: usize,
+    /// Row inside the archetype.
+    pub row: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EntityMeta {
+    generation: u32,
+    location: Option<EntityLocation>,
+}
+
+/// Allocates entity ids and keeps track of where entities are stored.
+#[derive(Debug, Default)]
+pub struct Entities {
+    meta: Vec<EntityMeta>,
+    /// Ids of deleted entities that can be reused.
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl Entities {
+    pub fn new() -> Entities { Entities::default() }
+
+    /// Allocates a new entity. Ids of freed entities are reused with a new generation.
+    pub fn alloc(&mut self) -> Entity {
+        self.len += 1;
+        match self.free.pop() {
+            Some(id) => Entity::new(id, self.meta[id as usize].generation),
+            None => {
+                let id = self.meta.len() as u32;
+                self.meta.push(EntityMeta {
+                    generation: 0,
+                    location: None,
+                });
+                Entity::new(id, 0)
+            },
+        }
+    }
+
+    /// Makes sure `additional` more entities can be allocated without
+    /// reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.meta
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// Frees `entity` making all handles to it stale. Returns its last location.
+    pub fn free(&mut self, entity: Entity) -> Option<EntityLocation> {
+        let meta = self.meta.get_mut(entity.id as usize)?;
+        if meta.generation != entity.generation {
+            return None;
+        }
+        meta.generation = meta.generation.wrapping_add(1);
+        self.free.push(entity.id);
+        self.len -= 1;
+        meta.location.take()
+    }
+
+    /// Checks if `entity` is alive.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.location(entity).is_some()
+    }
+
+    /// Gets the location of `entity` or [`None`] if the handle is stale.
+    pub fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        let meta = self.meta.get(entity.id as usize)?;
+        if meta.generation != entity.generation {
+            return None;
+        }
+        meta.location
+    }
+
+    /// Sets the location of `entity`.
+    pub fn set_location(&mut self, entity: Entity, location: EntityLocation) {
+        if let Some(meta) = self.meta.get_mut(entity.id as usize)
+            && meta.generation == entity.generation
+        {
+            meta.location = Some(location);
+        }
+    }
+
+    /// Gets the number of alive entities.
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
 }
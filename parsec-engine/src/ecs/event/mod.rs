@@ -0,0 +1,234 @@
+//! Module responsible for sending messages between systems.
+
+use std::{
+    iter::{Chain, Skip},
+    slice::Iter,
+};
+
+use crate::{
+    ctx::SharedCtx,
+    ecs::{
+        resources::{
+            ResourceError, Resources,
+            resource::{Resource, ResourceMut},
+        },
+        system::{access::SystemAccess, param::SystemParam},
+    },
+    error::ParsecError,
+};
+
+/// Marks a type that can be sent with [`EventWriter`].
+pub trait Event: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Event for T {}
+
+/// Double-buffered storage of events of type `E`.
+///
+/// Events are kept for two [updates][Events::update], so systems running later
+/// in the frame and in the next frame can read them. [`App`][crate::app::App]
+/// updates events registered with `add_event` at the start of every frame.
+pub struct Events<E: Event> {
+    /// Events sent before the last update.
+    previous: Vec<E>,
+    /// Events sent since the last update.
+    current: Vec<E>,
+    /// Number of events sent before the first event in `previous`.
+    previous_start: usize,
+}
+
+impl<E: Event> Default for Events<E> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<E: Event> Events<E> {
+    pub fn new() -> Events<E> {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+        }
+    }
+
+    /// Sends `event` to all readers.
+    pub fn send(&mut self, event: E) { self.current.push(event); }
+
+    /// Sends all `events` to all readers.
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.current.extend(events);
+    }
+
+    /// Gets the number of events sent since the creation of `self`.
+    pub fn event_count(&self) -> usize {
+        self.previous_start + self.previous.len() + self.current.len()
+    }
+
+    /// Drops events sent before the previous update and starts a new buffer.
+    pub fn update(&mut self) {
+        self.previous_start += self.previous.len();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// Updates the resource storing events of type `E`, if it exists.
+    pub(crate) fn update_resource(resources: &mut Resources) {
+        if let Some(mut events) = resources.get_mut::<Events<E>>() {
+            events.update();
+        }
+    }
+
+    /// Gets stored events with ids starting at `cursor`.
+    fn read_from(&self, cursor: usize) -> EventIter<'_, E> {
+        let previous_skip = cursor.saturating_sub(self.previous_start);
+        let current_skip =
+            cursor.saturating_sub(self.previous_start + self.previous.len());
+        self.previous
+            .iter()
+            .skip(previous_skip)
+            .chain(self.current.iter().skip(current_skip))
+    }
+}
+
+type EventIter<'a, E> = Chain<Skip<Iter<'a, E>>, Skip<Iter<'a, E>>>;
+
+/// System parameter reading events of type `E`.
+/// Every system keeps its own cursor, so each event is read once per system.
+pub struct EventReader<'w, 's, E: Event> {
+    events: Resource<'w, Events<E>>,
+    cursor: &'s mut usize,
+}
+
+impl<'w, 's, E: Event> EventReader<'w, 's, E> {
+    /// Gets events that weren't read by this system yet.
+    pub fn read(&mut self) -> impl Iterator<Item = &E> {
+        let events = self.events.read_from(*self.cursor);
+        *self.cursor = self.events.event_count();
+        events
+    }
+
+    /// Gets the number of events that weren't read by this system yet.
+    pub fn len(&self) -> usize { self.events.read_from(*self.cursor).count() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Marks all events as read.
+    pub fn clear(&mut self) { *self.cursor = self.events.event_count(); }
+}
+
+impl<'a, 'b, E: Event> SystemParam for EventReader<'a, 'b, E> {
+    type State = usize;
+    type Item<'w, 's> = EventReader<'w, 's, E>;
+
+    fn init_state() -> Self::State { 0 }
+
+    fn access(access: &mut SystemAccess) {
+        access.read_resource::<Events<E>>();
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        let events = ctx.resources.get::<Events<E>>().ok_or(
+            ResourceError::ResourceNotFound(std::any::type_name::<Events<E>>()),
+        )?;
+        Ok(EventReader {
+            events,
+            cursor: state,
+        })
+    }
+}
+
+/// System parameter sending events of type `E`.
+pub struct EventWriter<'w, E: Event> {
+    events: ResourceMut<'w, Events<E>>,
+}
+
+impl<'w, E: Event> EventWriter<'w, E> {
+    /// Sends `event` to all readers.
+    pub fn send(&mut self, event: E) { self.events.send(event); }
+
+    /// Sends all `events` to all readers.
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.events.send_batch(events);
+    }
+}
+
+impl<'a, E: Event> SystemParam for EventWriter<'a, E> {
+    type State = ();
+    type Item<'w, 's> = EventWriter<'w, E>;
+
+    fn init_state() -> Self::State {}
+
+    fn access(access: &mut SystemAccess) {
+        access.write_resource::<Events<E>>();
+    }
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        let events = ctx.resources.get_mut::<Events<E>>().ok_or(
+            ResourceError::ResourceNotFound(std::any::type_name::<Events<E>>()),
+        )?;
+        Ok(EventWriter { events })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assets::AssetLibrary,
+        ecs::{
+            system::{SystemTrigger, Systems, param::ResMut},
+            world::World,
+        },
+    };
+
+    struct Damage(u32);
+
+    fn deal_damage(mut writer: EventWriter<Damage>) {
+        writer.send_batch([Damage(1), Damage(2)]);
+    }
+
+    fn take_damage(mut reader: EventReader<Damage>, mut health: ResMut<u32>) {
+        for Damage(damage) in reader.read() {
+            *health -= damage;
+        }
+    }
+
+    #[test]
+    fn event_channels() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut systems = Systems::new();
+        resources.add(Events::<Damage>::new());
+        resources.add(100_u32);
+        systems.add(SystemTrigger::Update, take_damage);
+        systems.add(SystemTrigger::Update, deal_damage);
+        systems.add(SystemTrigger::LateUpdate, take_damage);
+
+        let mut fire = |trigger, resources: &mut Resources| {
+            systems
+                .fire_trigger(trigger, &mut world, resources, &mut assets)
+                .unwrap()
+        };
+        // The first reader gets the events in the next frame,
+        // the second one in the same frame.
+        fire(SystemTrigger::Update, &mut resources);
+        fire(SystemTrigger::LateUpdate, &mut resources);
+        assert_eq!(*resources.get::<u32>().unwrap(), 97);
+
+        Events::<Damage>::update_resource(&mut resources);
+        fire(SystemTrigger::Update, &mut resources);
+        assert_eq!(*resources.get::<u32>().unwrap(), 94);
+
+        // Events older than two updates are dropped.
+        Events::<Damage>::update_resource(&mut resources);
+        Events::<Damage>::update_resource(&mut resources);
+        fire(SystemTrigger::LateUpdate, &mut resources);
+        assert_eq!(*resources.get::<u32>().unwrap(), 94);
+        assert_eq!(resources.get::<Events<Damage>>().unwrap().event_count(), 4);
+    }
+}
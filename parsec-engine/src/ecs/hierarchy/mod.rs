@@ -0,0 +1,281 @@
+//! Module responsible for parent-child relations between entities.
+
+use crate::ecs::{
+    entity::Entity,
+    scene::{EntityMap, MapEntities},
+    world::{World, WorldError, component::Component},
+};
+
+/// Points to the parent of an entity.
+/// Added and removed with [`World::set_parent`] and [`World::remove_parent`].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Component,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Parent {
+    parent: Entity,
+    /// Next child of the same parent.
+    next_sibling: Option<Entity>,
+}
+
+impl Parent {
+    /// Gets the parent entity.
+    pub fn get(&self) -> Entity { self.parent }
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, map: &EntityMap) {
+        self.parent.map_entities(map);
+        self.next_sibling.map_entities(map);
+    }
+}
+
+/// Marks an entity that has children.
+/// Children are iterated with [`World::children`].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Component,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Children {
+    first: Option<Entity>,
+    last: Option<Entity>,
+    len: usize,
+}
+
+impl Children {
+    /// Gets the number of children.
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, map: &EntityMap) {
+        self.first.map_entities(map);
+        self.last.map_entities(map);
+    }
+}
+
+/// Iterator over children of an entity, in the order they were added.
+pub struct ChildrenIter<'a> {
+    world: &'a World,
+    next: Option<Entity>,
+}
+
+impl<'a> Iterator for ChildrenIter<'a> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let child = self.next?;
+        self.next = self
+            .world
+            .get::<Parent>(child)
+            .ok()
+            .and_then(|parent| parent.next_sibling);
+        Some(child)
+    }
+}
+
+impl World {
+    /// Gets children of `entity`, in the order they were added.
+    pub fn children(&self, entity: Entity) -> ChildrenIter<'_> {
+        ChildrenIter {
+            world: self,
+            next: self
+                .get::<Children>(entity)
+                .ok()
+                .and_then(|children| children.first),
+        }
+    }
+
+    /// Makes `child` the last child of `parent`, detaching it from its
+    /// previous parent.
+    ///
+    /// # Errors
+    ///
+    /// - If `child` or `parent` doesn't exist.
+    /// - If `parent` is `child` or one of its descendants.
+    /// - If any of the affected archetypes is already borrowed in some way.
+    pub fn set_parent(
+        &mut self,
+        child: Entity,
+        parent: Entity,
+    ) -> Result<(), WorldError> {
+        self.entity(child)?;
+        self.entity(parent)?;
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            if entity == child {
+                return Err(WorldError::HierarchyCycle);
+            }
+            ancestor = self.get::<Parent>(entity).ok().map(|p| p.get());
+        }
+
+        self.unlink(child)?;
+        let link = Parent {
+            parent,
+            next_sibling: None,
+        };
+        if self.has::<Parent>(child) {
+            *self.get_mut::<Parent>(child)? = link;
+        } else {
+            self.add_components(child, link)?;
+        }
+
+        let last = self.get::<Children>(parent).ok().and_then(|c| c.last);
+        match last {
+            Some(last) => {
+                self.get_mut::<Parent>(last)?.next_sibling = Some(child);
+                let mut children = self.get_mut::<Children>(parent)?;
+                children.last = Some(child);
+                children.len += 1;
+            },
+            None => {
+                let children = Children {
+                    first: Some(child),
+                    last: Some(child),
+                    len: 1,
+                };
+                if self.has::<Children>(parent) {
+                    *self.get_mut::<Children>(parent)? = children;
+                } else {
+                    self.add_components(parent, children)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Detaches `child` from its parent. Does nothing if it has no parent.
+    ///
+    /// # Errors
+    ///
+    /// - If any of the affected archetypes is already borrowed in some way.
+    pub fn remove_parent(&mut self, child: Entity) -> Result<(), WorldError> {
+        if !self.has::<Parent>(child) {
+            return Ok(());
+        }
+        self.unlink(child)?;
+        self.remove_components::<Parent>(child)
+    }
+
+    /// Deletes `entity` along with all its descendants.
+    /// See [`World::delete`] for possible errors.
+    pub fn delete_recursive(
+        &mut self,
+        entity: Entity,
+    ) -> Result<(), WorldError> {
+        let children = self.children(entity).collect::<Vec<_>>();
+        for child in children {
+            self.delete_recursive(child)?;
+        }
+        self.delete(entity)
+    }
+
+    /// Detaches `entity` from its parent and children before it's deleted.
+    /// Children of `entity` become roots.
+    pub(crate) fn detach_hierarchy(
+        &mut self,
+        entity: Entity,
+    ) -> Result<(), WorldError> {
+        let children = self.children(entity).collect::<Vec<_>>();
+        for child in children {
+            self.remove_components::<Parent>(child)?;
+        }
+        self.unlink(entity)
+    }
+
+    /// Removes `child` from the sibling list of its parent.
+    /// The [`Parent`] component of `child` is left in place.
+    fn unlink(&mut self, child: Entity) -> Result<(), WorldError> {
+        let Ok(link) = self.get::<Parent>(child).map(|p| *p) else {
+            return Ok(());
+        };
+        let Ok(mut children) = self.get::<Children>(link.parent).map(|c| *c)
+        else {
+            return Ok(());
+        };
+
+        let mut previous = None;
+        let mut current = children.first;
+        while let Some(sibling) = current {
+            if sibling == child {
+                break;
+            }
+            previous = current;
+            current = self.get::<Parent>(sibling)?.next_sibling;
+        }
+        match previous {
+            Some(previous) => {
+                self.get_mut::<Parent>(previous)?.next_sibling =
+                    link.next_sibling
+            },
+            None => children.first = link.next_sibling,
+        }
+        if children.last == Some(child) {
+            children.last = previous;
+        }
+        children.len -= 1;
+        self.get_mut::<Parent>(child)?.next_sibling = None;
+
+        if children.is_empty() {
+            self.remove_components::<Children>(link.parent)
+        } else {
+            *self.get_mut::<Children>(link.parent)? = children;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchy() {
+        let mut world = World::new();
+        let ship = world.spawn(0_u32).unwrap();
+        let turrets = (0..3)
+            .map(|i| world.spawn(i + 1_u32).unwrap())
+            .collect::<Vec<_>>();
+        for turret in turrets.iter() {
+            world.set_parent(*turret, ship).unwrap();
+        }
+        let gun = world.spawn(4_u32).unwrap();
+        world.set_parent(gun, turrets[0]).unwrap();
+
+        assert_eq!(world.children(ship).collect::<Vec<_>>(), turrets);
+        assert_eq!(world.get::<Children>(ship).unwrap().len(), 3);
+        assert_eq!(
+            world.set_parent(ship, gun),
+            Err(WorldError::HierarchyCycle)
+        );
+
+        world.delete(turrets[1]).unwrap();
+        assert_eq!(world.children(ship).collect::<Vec<_>>(), vec![
+            turrets[0], turrets[2]
+        ]);
+        world.set_parent(turrets[0], turrets[2]).unwrap();
+        assert_eq!(world.children(ship).collect::<Vec<_>>(), vec![turrets[2]]);
+
+        world.delete(turrets[0]).unwrap();
+        assert!(!world.has::<Parent>(gun));
+        assert!(!world.has::<Children>(turrets[2]));
+
+        world.set_parent(gun, turrets[2]).unwrap();
+        world.delete_recursive(ship).unwrap();
+        assert_eq!(world.entity_count(), 0);
+    }
+}
@@ -1,6 +1,10 @@
 //! Module responsible for the ECS subsystem.
 
+pub mod commands;
 pub mod entity;
+pub mod event;
+pub mod hierarchy;
 pub mod resources;
+pub mod scene;
 pub mod system;
 pub mod world;
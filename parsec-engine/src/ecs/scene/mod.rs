@@ -0,0 +1,522 @@
+//! Module responsible for saving and loading worlds.
+
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap},
+};
+
+use thiserror::Error;
+
+use crate::{
+    ecs::{
+        entity::Entity,
+        hierarchy::{Children, Parent},
+        world::{World, WorldError, component::Component},
+    },
+    renderer::components::register_components,
+};
+
+#[derive(Error, Debug)]
+pub enum SceneError {
+    #[error("Component type {0} is not registered")]
+    UnregisteredComponent(String),
+    #[error("Failed to encode or decode a scene as JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to encode or decode a scene with postcard: {0}")]
+    Postcard(#[from] postcard::Error),
+    #[error("Failed to access the world: {0}")]
+    World(#[from] WorldError),
+    #[error("Scene asset is not loaded")]
+    SceneNotLoaded,
+}
+
+/// Encoding of a saved scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    /// Human-readable JSON, suited for authoring levels by hand.
+    Json,
+    /// Compact binary form, suited for save games.
+    Postcard,
+}
+
+/// Maps entities of a saved scene to entities spawned when loading it.
+#[derive(Debug, Default, Clone)]
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    pub fn new() -> EntityMap { EntityMap::default() }
+
+    /// Maps `saved` to `spawned`.
+    pub fn insert(&mut self, saved: Entity, spawned: Entity) {
+        self.map.insert(saved, spawned);
+    }
+
+    /// Gets the entity `saved` was mapped to. Entities that aren't part of the
+    /// scene are returned unchanged.
+    pub fn get(&self, saved: Entity) -> Entity {
+        self.map.get(&saved).copied().unwrap_or(saved)
+    }
+
+    /// Gets the number of mapped entities.
+    pub fn len(&self) -> usize { self.map.len() }
+
+    pub fn is_empty(&self) -> bool { self.map.is_empty() }
+
+    /// Iterates over pairs of saved and spawned entities.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.map.iter().map(|(saved, spawned)| (*saved, *spawned))
+    }
+}
+
+/// Marks a component that references other entities, so that the references
+/// can be remapped when a scene is loaded.
+pub trait MapEntities {
+    /// Replaces every referenced entity with the one it's mapped to.
+    fn map_entities(&mut self, map: &EntityMap);
+}
+
+impl MapEntities for Entity {
+    fn map_entities(&mut self, map: &EntityMap) { *self = map.get(*self); }
+}
+
+impl<T: MapEntities> MapEntities for Option<T> {
+    fn map_entities(&mut self, map: &EntityMap) {
+        if let Some(value) = self {
+            value.map_entities(map);
+        }
+    }
+}
+
+impl<T: MapEntities> MapEntities for Vec<T> {
+    fn map_entities(&mut self, map: &EntityMap) {
+        for value in self.iter_mut() {
+            value.map_entities(map);
+        }
+    }
+}
+
+/// Component values of a single entity, keyed by registered names.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneEntity<V> {
+    pub entity: Entity,
+    pub components: BTreeMap<String, V>,
+}
+
+/// Entities and components of a saved world. Component values are stored as
+/// `V`, which is [`serde_json::Value`] in the JSON form and postcard bytes in
+/// the compact form.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneData<V> {
+    pub entities: Vec<SceneEntity<V>>,
+}
+
+impl<V> Default for SceneData<V> {
+    fn default() -> Self {
+        SceneData {
+            entities: Vec::new(),
+        }
+    }
+}
+
+/// Remaps entities stored in a component of an entity after loading a scene.
+type MapEntitiesFn =
+    fn(&mut World, Entity, &EntityMap) -> Result<(), WorldError>;
+
+/// Functions reading and writing components of a single registered type.
+#[derive(Debug, Clone, Copy)]
+struct ComponentRegistration {
+    name: &'static str,
+    has: fn(&World, Entity) -> bool,
+    to_json: fn(&World, Entity) -> Result<serde_json::Value, SceneError>,
+    to_bytes: fn(&World, Entity) -> Result<Vec<u8>, SceneError>,
+    insert_json:
+        fn(&mut World, Entity, serde_json::Value) -> Result<(), SceneError>,
+    insert_bytes: fn(&mut World, Entity, &[u8]) -> Result<(), SceneError>,
+    /// Set for components implementing [`MapEntities`].
+    map_entities: Option<MapEntitiesFn>,
+}
+
+fn has<T: Component>(world: &World, entity: Entity) -> bool {
+    world.has::<T>(entity)
+}
+
+fn to_json<T: Component + serde::Serialize>(
+    world: &World,
+    entity: Entity,
+) -> Result<serde_json::Value, SceneError> {
+    Ok(serde_json::to_value(&*world.get::<T>(entity)?)?)
+}
+
+fn to_bytes<T: Component + serde::Serialize>(
+    world: &World,
+    entity: Entity,
+) -> Result<Vec<u8>, SceneError> {
+    Ok(postcard::to_stdvec(&*world.get::<T>(entity)?)?)
+}
+
+fn insert_json<T: Component + serde::de::DeserializeOwned>(
+    world: &mut World,
+    entity: Entity,
+    value: serde_json::Value,
+) -> Result<(), SceneError> {
+    let value = serde_json::from_value::<T>(value)?;
+    Ok(world.add_components(entity, value)?)
+}
+
+fn insert_bytes<T: Component + serde::de::DeserializeOwned>(
+    world: &mut World,
+    entity: Entity,
+    bytes: &[u8],
+) -> Result<(), SceneError> {
+    let value = postcard::from_bytes::<T>(bytes)?;
+    Ok(world.add_components(entity, value)?)
+}
+
+fn map_entities<T: Component + MapEntities>(
+    world: &mut World,
+    entity: Entity,
+    map: &EntityMap,
+) -> Result<(), WorldError> {
+    world.get_mut::<T>(entity)?.map_entities(map);
+    Ok(())
+}
+
+/// Stores component types that can be saved in and loaded from scenes.
+/// Components of types that aren't registered are skipped when saving.
+#[derive(Debug, Clone)]
+pub struct TypeRegistry {
+    registrations: Vec<ComponentRegistration>,
+    /// Index into `registrations` for every registered name.
+    names: HashMap<&'static str, usize>,
+    /// Index into `registrations` for every registered type.
+    types: HashMap<TypeId, usize>,
+}
+
+impl Default for TypeRegistry {
+    fn default() -> Self { Self::new() }
+}
+
+impl TypeRegistry {
+    /// Creates a registry with hierarchy and renderer components already
+    /// registered.
+    pub fn new() -> TypeRegistry {
+        let mut registry = TypeRegistry::empty();
+        registry.register_mapped::<Parent>("Parent");
+        registry.register_mapped::<Children>("Children");
+        register_components(&mut registry);
+        registry
+    }
+
+    /// Creates a registry without any registered types.
+    pub fn empty() -> TypeRegistry {
+        TypeRegistry {
+            registrations: Vec::new(),
+            names: HashMap::new(),
+            types: HashMap::new(),
+        }
+    }
+
+    /// Registers components of type `T` under `name`, which identifies them
+    /// inside saved scenes. Registering a type again replaces its name.
+    pub fn register<T>(&mut self, name: &'static str)
+    where
+        T: Component + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.insert::<T>(ComponentRegistration {
+            name,
+            has: has::<T>,
+            to_json: to_json::<T>,
+            to_bytes: to_bytes::<T>,
+            insert_json: insert_json::<T>,
+            insert_bytes: insert_bytes::<T>,
+            map_entities: None,
+        });
+    }
+
+    /// Registers components of type `T` that reference other entities.
+    /// See [`TypeRegistry::register`].
+    pub fn register_mapped<T>(&mut self, name: &'static str)
+    where
+        T: Component
+            + MapEntities
+            + serde::Serialize
+            + serde::de::DeserializeOwned,
+    {
+        self.register::<T>(name);
+        let index = self.types[&TypeId::of::<T>()];
+        self.registrations[index].map_entities = Some(map_entities::<T>);
+    }
+
+    fn insert<T: Component>(&mut self, registration: ComponentRegistration) {
+        match self.types.get(&TypeId::of::<T>()) {
+            Some(index) => {
+                self.names.remove(self.registrations[*index].name);
+                self.names.insert(registration.name, *index);
+                self.registrations[*index] = registration;
+            },
+            None => {
+                let index = self.registrations.len();
+                self.names.insert(registration.name, index);
+                self.types.insert(TypeId::of::<T>(), index);
+                self.registrations.push(registration);
+            },
+        }
+    }
+
+    /// Checks if components of type `T` are registered.
+    pub fn contains<T: Component>(&self) -> bool {
+        self.types.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Gets the registration of components named `name`.
+    fn get(&self, name: &str) -> Result<&ComponentRegistration, SceneError> {
+        self.names
+            .get(name)
+            .map(|index| &self.registrations[*index])
+            .ok_or_else(|| SceneError::UnregisteredComponent(name.to_owned()))
+    }
+}
+
+impl World {
+    /// Collects all entities and their registered components, encoding
+    /// component values with `encode`.
+    fn collect_scene<V>(
+        &self,
+        registry: &TypeRegistry,
+        encode: impl Fn(
+            &ComponentRegistration,
+            &World,
+            Entity,
+        ) -> Result<V, SceneError>,
+    ) -> Result<SceneData<V>, SceneError> {
+        let mut entities = self.iter_entities().collect::<Vec<_>>();
+        entities.sort_by_key(|entity| entity.id());
+
+        let mut scene = SceneData::default();
+        for entity in entities {
+            let mut components = BTreeMap::new();
+            for registration in registry.registrations.iter() {
+                if (registration.has)(self, entity) {
+                    let value = encode(registration, self, entity)?;
+                    components.insert(registration.name.to_owned(), value);
+                }
+            }
+            scene.entities.push(SceneEntity { entity, components });
+        }
+        Ok(scene)
+    }
+
+    /// Collects all entities and their registered components in the JSON form.
+    ///
+    /// # Errors
+    ///
+    /// - If any registered component is borrowed mutably.
+    /// - If any registered component fails to serialize.
+    pub fn scene_json(
+        &self,
+        registry: &TypeRegistry,
+    ) -> Result<SceneData<serde_json::Value>, SceneError> {
+        self.collect_scene(registry, |registration, world, entity| {
+            (registration.to_json)(world, entity)
+        })
+    }
+
+    /// Collects all entities and their registered components in the compact
+    /// form.
+    ///
+    /// # Errors
+    ///
+    /// - If any registered component is borrowed mutably.
+    /// - If any registered component fails to serialize.
+    pub fn scene_bytes(
+        &self,
+        registry: &TypeRegistry,
+    ) -> Result<SceneData<Vec<u8>>, SceneError> {
+        self.collect_scene(registry, |registration, world, entity| {
+            (registration.to_bytes)(world, entity)
+        })
+    }
+
+    /// Saves all entities and their components registered in `registry`.
+    ///
+    /// # Errors
+    ///
+    /// - If any registered component is borrowed mutably.
+    /// - If any registered component fails to serialize.
+    pub fn save_scene(
+        &self,
+        registry: &TypeRegistry,
+        format: SceneFormat,
+    ) -> Result<Vec<u8>, SceneError> {
+        match format {
+            SceneFormat::Json => {
+                Ok(serde_json::to_vec_pretty(&self.scene_json(registry)?)?)
+            },
+            SceneFormat::Postcard => {
+                Ok(postcard::to_stdvec(&self.scene_bytes(registry)?)?)
+            },
+        }
+    }
+
+    /// Spawns entities of `scene`, inserting component values with `insert`.
+    /// Entity references inside components are remapped to spawned entities.
+    fn spawn_scene<V>(
+        &mut self,
+        registry: &TypeRegistry,
+        scene: SceneData<V>,
+        insert: impl Fn(
+            &ComponentRegistration,
+            &mut World,
+            Entity,
+            V,
+        ) -> Result<(), SceneError>,
+    ) -> Result<EntityMap, SceneError> {
+        for scene_entity in scene.entities.iter() {
+            for name in scene_entity.components.keys() {
+                registry.get(name)?;
+            }
+        }
+
+        let mut map = EntityMap::new();
+        for scene_entity in scene.entities.iter() {
+            map.insert(scene_entity.entity, self.spawn(())?);
+        }
+
+        let mut mapped = Vec::new();
+        for scene_entity in scene.entities {
+            let entity = map.get(scene_entity.entity);
+            for (name, value) in scene_entity.components {
+                let registration = registry.get(&name)?;
+                insert(registration, self, entity, value)?;
+                if let Some(map_entities) = registration.map_entities {
+                    mapped.push((map_entities, entity));
+                }
+            }
+        }
+        for (map_entities, entity) in mapped {
+            map_entities(self, entity, &map)?;
+        }
+        Ok(map)
+    }
+
+    /// Spawns entities of a scene in the JSON form.
+    /// Returns the map from saved entities to spawned ones.
+    ///
+    /// # Errors
+    ///
+    /// - If the scene contains components that aren't registered.
+    /// - If any component fails to deserialize.
+    pub fn spawn_scene_json(
+        &mut self,
+        registry: &TypeRegistry,
+        scene: SceneData<serde_json::Value>,
+    ) -> Result<EntityMap, SceneError> {
+        self.spawn_scene(registry, scene, |registration, world, entity, value| {
+            (registration.insert_json)(world, entity, value)
+        })
+    }
+
+    /// Spawns entities of a scene in the compact form.
+    /// Returns the map from saved entities to spawned ones.
+    ///
+    /// # Errors
+    ///
+    /// - If the scene contains components that aren't registered.
+    /// - If any component fails to deserialize.
+    pub fn spawn_scene_bytes(
+        &mut self,
+        registry: &TypeRegistry,
+        scene: SceneData<Vec<u8>>,
+    ) -> Result<EntityMap, SceneError> {
+        self.spawn_scene(registry, scene, |registration, world, entity, value| {
+            (registration.insert_bytes)(world, entity, &value)
+        })
+    }
+
+    /// Spawns entities saved with [`World::save_scene`], next to the entities
+    /// already in `self`. Returns the map from saved entities to spawned ones.
+    ///
+    /// # Errors
+    ///
+    /// - If `data` isn't a scene encoded with `format`.
+    /// - If the scene contains components that aren't registered.
+    /// - If any component fails to deserialize.
+    pub fn load_scene(
+        &mut self,
+        registry: &TypeRegistry,
+        data: &[u8],
+        format: SceneFormat,
+    ) -> Result<EntityMap, SceneError> {
+        match format {
+            SceneFormat::Json => {
+                let scene = serde_json::from_slice(data)?;
+                self.spawn_scene_json(registry, scene)
+            },
+            SceneFormat::Postcard => {
+                let scene = postcard::from_bytes(data)?;
+                self.spawn_scene_bytes(registry, scene)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Component, serde::Serialize, serde::Deserialize)]
+    struct Name(String);
+
+    #[derive(Debug, PartialEq, Component, serde::Serialize, serde::Deserialize)]
+    struct Target(Entity);
+
+    impl MapEntities for Target {
+        fn map_entities(&mut self, map: &EntityMap) {
+            self.0.map_entities(map);
+        }
+    }
+
+    fn registry() -> TypeRegistry {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Name>("Name");
+        registry.register_mapped::<Target>("Target");
+        registry.register::<u32>("u32");
+        registry
+    }
+
+    #[test]
+    fn save_and_load() {
+        let registry = registry();
+        let mut world = World::new();
+        let ship = world.spawn((Name("ship".into()), 10_u32, 'x')).unwrap();
+        let moon = world.spawn(Name("moon".into())).unwrap();
+        world.add_components(ship, Target(moon)).unwrap();
+        world.set_parent(moon, ship).unwrap();
+
+        for format in [SceneFormat::Json, SceneFormat::Postcard] {
+            let data = world.save_scene(&registry, format).unwrap();
+            let mut loaded = World::new();
+            loaded.spawn(0_u32).unwrap();
+            let map = loaded.load_scene(&registry, &data, format).unwrap();
+            assert_eq!(map.len(), 2);
+
+            let (ship, moon) = (map.get(ship), map.get(moon));
+            assert_eq!(loaded.get::<Name>(ship).unwrap().0, "ship");
+            assert_eq!(*loaded.get::<u32>(ship).unwrap(), 10);
+            assert!(!loaded.has::<char>(ship));
+            assert_eq!(*loaded.get::<Target>(ship).unwrap(), Target(moon));
+            assert_eq!(loaded.children(ship).collect::<Vec<_>>(), vec![moon]);
+        }
+
+        let json = br#"{"entities": [{
+            "entity": {"id": 0, "generation": 0},
+            "components": {"Unknown": 1}
+        }]}"#;
+        assert!(matches!(
+            world.load_scene(&registry, json, SceneFormat::Json),
+            Err(SceneError::UnregisteredComponent(_))
+        ));
+    }
+}
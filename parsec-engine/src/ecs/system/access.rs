@@ -0,0 +1,117 @@
+//! Module responsible for describing data accessed by systems.
+
+use std::{any::TypeId, collections::HashSet, hash::Hash};
+
+use crate::ecs::world::component::ComponentId;
+
+/// Set of components and resources a system reads and writes.
+/// Systems with compatible access can run in parallel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemAccess {
+    /// Set when the system needs unique access to everything.
+    exclusive: bool,
+    components_read: HashSet<ComponentId>,
+    components_write: HashSet<ComponentId>,
+    resources_read: HashSet<TypeId>,
+    resources_write: HashSet<TypeId>,
+    /// Set when a component is marked as written and also marked as read or
+    /// written again, e.g. by a single query fetching it twice.
+    aliased: bool,
+}
+
+impl SystemAccess {
+    /// Creates an access that doesn't touch any data.
+    pub fn new() -> SystemAccess { SystemAccess::default() }
+
+    /// Creates an access that conflicts with every other access.
+    pub fn exclusive() -> SystemAccess {
+        SystemAccess {
+            exclusive: true,
+            ..SystemAccess::default()
+        }
+    }
+
+    pub fn is_exclusive(&self) -> bool { self.exclusive }
+
+    /// Marks components of type `T` as read.
+    pub fn read_component<T: 'static>(&mut self) {
+        let component_id = ComponentId::of::<T>();
+        self.aliased |= self.components_write.contains(&component_id);
+        self.components_read.insert(component_id);
+    }
+
+    /// Marks components of type `T` as written.
+    pub fn write_component<T: 'static>(&mut self) {
+        let component_id = ComponentId::of::<T>();
+        self.aliased |= self.components_read.contains(&component_id)
+            || !self.components_write.insert(component_id);
+    }
+
+    /// Marks the resource of type `R` as read.
+    pub fn read_resource<R: 'static>(&mut self) {
+        self.resources_read.insert(TypeId::of::<R>());
+    }
+
+    /// Marks the resource of type `R` as written.
+    pub fn write_resource<R: 'static>(&mut self) {
+        self.resources_write.insert(TypeId::of::<R>());
+    }
+
+    /// Checks if components with id `component_id` are read.
+    pub(crate) fn reads_component(&self, component_id: &ComponentId) -> bool {
+        self.components_read.contains(component_id)
+    }
+
+    /// Checks if components with id `component_id` are written.
+    pub(crate) fn writes_component(&self, component_id: &ComponentId) -> bool {
+        self.components_write.contains(component_id)
+    }
+
+    /// Checks if a component was marked as written and also marked as read or
+    /// written again. Such components can't be borrowed by a single query.
+    pub(crate) fn is_aliased(&self) -> bool { self.aliased }
+
+    /// Adds everything accessed by `other` to `self`.
+    /// Accesses of different parameters may overlap, so it doesn't make
+    /// `self` [aliased][SystemAccess::is_aliased].
+    pub fn extend(&mut self, other: &SystemAccess) {
+        self.exclusive |= other.exclusive;
+        self.components_read.extend(&other.components_read);
+        self.components_write.extend(&other.components_write);
+        self.resources_read.extend(&other.resources_read);
+        self.resources_write.extend(&other.resources_write);
+    }
+
+    /// Checks if systems with accesses `self` and `other` can run at the same time.
+    pub fn is_compatible(&self, other: &SystemAccess) -> bool {
+        if self.exclusive || other.exclusive {
+            return false;
+        }
+
+        fn conflicts<T: Eq + Hash>(
+            writes: &HashSet<T>,
+            reads: &HashSet<T>,
+            other_writes: &HashSet<T>,
+        ) -> bool {
+            !writes.is_disjoint(reads) || !writes.is_disjoint(other_writes)
+        }
+
+        !conflicts(
+            &self.components_write,
+            &other.components_read,
+            &other.components_write,
+        ) && !conflicts(
+            &other.components_write,
+            &self.components_read,
+            &self.components_write,
+        ) && !conflicts(
+            &self.resources_write,
+            &other.resources_read,
+            &other.resources_write,
+        ) && !conflicts(
+            &other.resources_write,
+            &self.resources_read,
+            &self.resources_write,
+        )
+    }
+}
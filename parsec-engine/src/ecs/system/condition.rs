@@ -0,0 +1,118 @@
+//! Module responsible for conditions deciding whether systems run.
+
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    ecs::{
+        resources::{ResourceMarker, Resources},
+        system::state::{State, States},
+    },
+    time::Time,
+};
+
+/// Predicate evaluated before every run of a system.
+/// The system is skipped when it returns `false`.
+pub type RunCondition = Box<dyn FnMut(&Resources) -> bool + Send + Sync>;
+
+/// Runs the system only if a resource of type `R` exists.
+pub fn resource_exists<R: ResourceMarker>(resources: &Resources) -> bool {
+    resources.get::<R>().is_some()
+}
+
+/// Runs the system only while the [`State`] of type `S` equals `state`.
+pub fn in_state<S: States>(
+    state: S,
+) -> impl FnMut(&Resources) -> bool + Send + Sync + 'static {
+    move |resources| {
+        resources
+            .get::<State<S>>()
+            .is_some_and(|current| *current.get() == state)
+    }
+}
+
+/// Runs the system once every `duration`, measured with the [`Time`] resource.
+/// The first run happens `duration` after the condition is first evaluated.
+pub fn on_timer(
+    duration: Duration,
+) -> impl FnMut(&Resources) -> bool + Send + Sync + 'static {
+    let mut last_run: Option<SystemTime> = None;
+    move |resources| {
+        let Some(time) = resources.get::<Time>() else {
+            return false;
+        };
+        let now = time.current_time();
+        let last = *last_run.get_or_insert(now);
+        if now.duration_since(last).unwrap_or_default() < duration {
+            return false;
+        }
+        last_run = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assets::AssetLibrary,
+        ecs::{
+            system::{
+                SystemTrigger, Systems, config::IntoSystemConfig, param::ResMut,
+            },
+            world::World,
+        },
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Mode {
+        Running,
+        Paused,
+    }
+
+    struct Flag;
+
+    fn count(mut counter: ResMut<u32>) { *counter += 1; }
+
+    #[test]
+    fn run_conditions() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut systems = Systems::new();
+        resources.add(0_u32);
+        resources.add(State::new(Mode::Paused));
+        systems.add(
+            SystemTrigger::Update,
+            count
+                .run_if(resource_exists::<Flag>)
+                .run_if(in_state(Mode::Running)),
+        );
+        systems.add(
+            SystemTrigger::Update,
+            count.run_if(|resources: &Resources| {
+                resources.get::<Flag>().is_none()
+            }),
+        );
+
+        let mut fire = |resources: &mut Resources| {
+            systems
+                .fire_trigger(
+                    SystemTrigger::Update,
+                    &mut world,
+                    resources,
+                    &mut assets,
+                )
+                .unwrap()
+        };
+        fire(&mut resources);
+        assert_eq!(*resources.get::<u32>().unwrap(), 1);
+
+        resources.add(Flag);
+        fire(&mut resources);
+        assert_eq!(*resources.get::<u32>().unwrap(), 1);
+
+        resources.add(State::new(Mode::Running));
+        fire(&mut resources);
+        assert_eq!(*resources.get::<u32>().unwrap(), 2);
+    }
+}
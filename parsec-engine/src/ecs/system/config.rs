@@ -0,0 +1,252 @@
+//! Module responsible for configuring the order of systems.
+
+use std::collections::{BTreeSet, HashMap};
+
+use thiserror::Error;
+
+use crate::ecs::{
+    resources::Resources,
+    system::{
+        IntoSystem, System, SystemTrigger, access::SystemAccess,
+        condition::RunCondition,
+    },
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SystemOrderError {
+    #[error(
+        "Systems triggered with {trigger:?} form an ordering cycle: {cycle}"
+    )]
+    OrderingCycle {
+        trigger: SystemTrigger,
+        /// Names of the systems in the cycle, separated with arrows.
+        cycle: String,
+    },
+}
+
+/// Ordering constraints of a single system.
+#[derive(Debug, Clone, Default)]
+pub(super) struct SystemOrder {
+    /// Name used in error messages.
+    pub name: &'static str,
+    /// Labels identifying the system.
+    pub labels: Vec<&'static str>,
+    /// Sets the system belongs to.
+    pub sets: Vec<&'static str>,
+    /// Labels or sets that have to run after the system.
+    pub before: Vec<&'static str>,
+    /// Labels or sets that have to run before the system.
+    pub after: Vec<&'static str>,
+}
+
+/// A system along with its ordering constraints and run conditions.
+/// Created with methods of [`IntoSystemConfig`].
+pub struct SystemConfig {
+    pub(super) system: Box<dyn System>,
+    pub(super) access: SystemAccess,
+    pub(super) order: SystemOrder,
+    pub(super) conditions: Vec<RunCondition>,
+}
+
+/// Represents a type that can be registered in
+/// [`Systems`][crate::ecs::system::Systems] along with ordering constraints
+/// and run conditions.
+/// It is implemented for all types implementing [`IntoSystem`] and [`SystemConfig`].
+///
+/// Labels and sets share a namespace, so [`before`][IntoSystemConfig::before] and
+/// [`after`][IntoSystemConfig::after] accept both. Constraints naming labels that
+/// aren't registered are ignored, so bundles can refer to optional systems.
+pub trait IntoSystemConfig<Marker>: Sized {
+    fn into_config(self) -> SystemConfig;
+
+    /// Identifies the system with `label`.
+    fn label(self, label: &'static str) -> SystemConfig {
+        let mut config = self.into_config();
+        config.order.labels.push(label);
+        config
+    }
+
+    /// Adds the system to the set `set`.
+    fn in_set(self, set: &'static str) -> SystemConfig {
+        let mut config = self.into_config();
+        config.order.sets.push(set);
+        config
+    }
+
+    /// Makes the system run before systems with label or set `label`.
+    fn before(self, label: &'static str) -> SystemConfig {
+        let mut config = self.into_config();
+        config.order.before.push(label);
+        config
+    }
+
+    /// Makes the system run after systems with label or set `label`.
+    fn after(self, label: &'static str) -> SystemConfig {
+        let mut config = self.into_config();
+        config.order.after.push(label);
+        config
+    }
+
+    /// Runs the system only when `condition` returns `true`.
+    /// Multiple conditions all have to be met.
+    /// See [`condition`][crate::ecs::system::condition] for common conditions.
+    fn run_if(
+        self,
+        condition: impl FnMut(&Resources) -> bool + Send + Sync + 'static,
+    ) -> SystemConfig {
+        let mut config = self.into_config();
+        config.conditions.push(Box::new(condition));
+        config
+    }
+}
+
+impl<M, S: IntoSystem<M>> IntoSystemConfig<M> for S {
+    fn into_config(self) -> SystemConfig {
+        let system = self.into_system();
+        SystemConfig {
+            access: system.access(),
+            system: Box::new(system),
+            order: SystemOrder {
+                name: std::any::type_name::<S>(),
+                ..SystemOrder::default()
+            },
+            conditions: Vec::new(),
+        }
+    }
+}
+
+pub struct SystemConfigMarker;
+
+impl IntoSystemConfig<SystemConfigMarker> for SystemConfig {
+    fn into_config(self) -> SystemConfig { self }
+}
+
+/// Ordering constraints shared by all systems of a set.
+#[derive(Debug, Clone)]
+pub struct SetConfig {
+    pub(super) set: &'static str,
+    pub(super) before: Vec<&'static str>,
+    pub(super) after: Vec<&'static str>,
+}
+
+impl SetConfig {
+    pub fn new(set: &'static str) -> SetConfig {
+        SetConfig {
+            set,
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+
+    /// Makes the set run before systems with label or set `label`.
+    pub fn before(mut self, label: &'static str) -> SetConfig {
+        self.before.push(label);
+        self
+    }
+
+    /// Makes the set run after systems with label or set `label`.
+    pub fn after(mut self, label: &'static str) -> SetConfig {
+        self.after.push(label);
+        self
+    }
+}
+
+/// Sorts systems topologically according to their constraints.
+/// Systems without constraints between them keep their registration order.
+/// Returns indices into `orders` in the order the systems should run, along
+/// with the indices of the systems that have to run before every system.
+///
+/// # Errors
+///
+/// - If the constraints form a cycle.
+pub(super) fn sort_systems(
+    trigger: SystemTrigger,
+    orders: &[&SystemOrder],
+    sets: &[SetConfig],
+) -> Result<(Vec<usize>, Vec<Vec<usize>>), SystemOrderError> {
+    let mut named = HashMap::<&str, Vec<usize>>::new();
+    for (i, order) in orders.iter().enumerate() {
+        for name in order.labels.iter().chain(order.sets.iter()) {
+            named.entry(name).or_default().push(i);
+        }
+    }
+
+    // `edges[i]` contains systems that have to run after system `i`.
+    let mut edges = vec![BTreeSet::new(); orders.len()];
+    let mut add_edges = |i: usize, before: &[&str], after: &[&str]| {
+        for j in before.iter().flat_map(|label| named.get(label)).flatten() {
+            if *j != i {
+                edges[i].insert(*j);
+            }
+        }
+        for j in after.iter().flat_map(|label| named.get(label)).flatten() {
+            if *j != i {
+                edges[*j].insert(i);
+            }
+        }
+    };
+    for (i, order) in orders.iter().enumerate() {
+        add_edges(i, &order.before, &order.after);
+        for set in sets.iter().filter(|set| order.sets.contains(&set.set)) {
+            add_edges(i, &set.before, &set.after);
+        }
+    }
+
+    let mut in_degree = vec![0; orders.len()];
+    for j in edges.iter().flatten() {
+        in_degree[*j] += 1;
+    }
+
+    // Always picks the earliest registered system that is ready.
+    let mut ready = (0..orders.len())
+        .filter(|i| in_degree[*i] == 0)
+        .collect::<BTreeSet<_>>();
+    let mut sorted = Vec::with_capacity(orders.len());
+    while let Some(i) = ready.pop_first() {
+        sorted.push(i);
+        for j in edges[i].iter() {
+            in_degree[*j] -= 1;
+            if in_degree[*j] == 0 {
+                ready.insert(*j);
+            }
+        }
+    }
+
+    if sorted.len() == orders.len() {
+        let mut predecessors = vec![Vec::new(); orders.len()];
+        for (i, successors) in edges.iter().enumerate() {
+            for j in successors.iter() {
+                predecessors[*j].push(i);
+            }
+        }
+        return Ok((sorted, predecessors));
+    }
+
+    // Every unsorted system has an unsorted predecessor, so walking back
+    // through predecessors has to end up in a cycle.
+    let predecessor = |j: usize| {
+        (0..orders.len())
+            .find(|i| in_degree[*i] > 0 && edges[*i].contains(&j))
+            .expect("unsorted system without unsorted predecessors")
+    };
+    let mut path = vec![(0..orders.len()).find(|i| in_degree[*i] > 0).unwrap()];
+    loop {
+        let previous = predecessor(*path.last().unwrap());
+        if let Some(start) = path.iter().position(|i| *i == previous) {
+            path.drain(..start);
+            break;
+        }
+        path.push(previous);
+    }
+    path.reverse();
+    path.push(path[0]);
+
+    Err(SystemOrderError::OrderingCycle {
+        trigger,
+        cycle: path
+            .iter()
+            .map(|i| orders[*i].name)
+            .collect::<Vec<_>>()
+            .join(" -> "),
+    })
+}
@@ -1,14 +1,37 @@
 //! Module responsible for systems management.
 
-use std::{collections::HashMap, fmt::Debug, marker::PhantomData};
+use std::{collections::HashMap, fmt::Debug, marker::PhantomData, sync::Arc};
 
 use crate::{
     assets::AssetLibrary,
-    ctx::Ctx,
-    ecs::{resources::Resources, world::World},
-    error::ParsecError,
+    ctx::{Ctx, SharedCtx},
+    ecs::{
+        commands::Commands,
+        resources::Resources,
+        system::{
+            access::SystemAccess,
+            condition::RunCondition,
+            config::{
+                IntoSystemConfig, SetConfig, SystemConfig, SystemOrder,
+                SystemOrderError, sort_systems,
+            },
+            state::{ApplyTransitions, StateSystems, States, Transition},
+        },
+        world::{
+            World,
+            change_detection::{SystemTicks, Tick},
+        },
+    },
+    error::{ParsecError, StrError},
+    utils::thread_pool::ThreadPool,
 };
 
+pub mod access;
+pub mod condition;
+pub mod config;
+pub mod param;
+pub mod state;
+
 /// List of possible actions a system can run on.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum SystemTrigger {
@@ -42,33 +65,241 @@ pub enum SystemTrigger {
     MouseWheel,
 }
 
+impl SystemTrigger {
+    /// Triggers fired every frame, in order.
+    pub const FRAME: [SystemTrigger; 3] = [
+        SystemTrigger::EarlyUpdate,
+        SystemTrigger::Update,
+        SystemTrigger::LateUpdate,
+    ];
+}
+
+/// A registered system along with its bookkeeping data.
+struct SystemEntry {
+    system: Box<dyn System>,
+    /// Data accessed by the system, used for scheduling.
+    access: SystemAccess,
+    /// Ordering constraints of the system.
+    order: SystemOrder,
+    /// Conditions that all have to be met for the system to run.
+    conditions: Vec<RunCondition>,
+    /// Change tick of the previous run, used for change detection.
+    last_run: Tick,
+    /// Positions of systems that have to run before this one, set when the
+    /// systems are sorted.
+    predecessors: Vec<usize>,
+}
+
+impl SystemEntry {
+    /// Evaluates the run conditions of the system.
+    fn should_run(&mut self, resources: &Resources) -> bool {
+        self.conditions
+            .iter_mut()
+            .all(|condition| condition(resources))
+    }
+}
+
+/// Systems registered for a single [`SystemTrigger`].
+#[derive(Default)]
+struct TriggerSystems {
+    /// Systems in registration order or, once sorted, in execution order.
+    entries: Vec<SystemEntry>,
+    /// Ordering constraints of system sets.
+    sets: Vec<SetConfig>,
+    /// Set when `entries` have to be sorted before running.
+    unsorted: bool,
+}
+
+impl TriggerSystems {
+    /// Adds a system configured with `config`.
+    fn push(&mut self, config: SystemConfig) {
+        let SystemConfig {
+            system,
+            access,
+            order,
+            conditions,
+        } = config;
+        self.entries.push(SystemEntry {
+            system,
+            access,
+            order,
+            conditions,
+            last_run: Tick::default(),
+            predecessors: Vec::new(),
+        });
+        self.unsorted = true;
+    }
+
+    /// Sorts `entries` according to their ordering constraints.
+    fn sort(&mut self, trigger: SystemTrigger) -> Result<(), SystemOrderError> {
+        let orders = self
+            .entries
+            .iter()
+            .map(|entry| &entry.order)
+            .collect::<Vec<_>>();
+        let (sorted, predecessors) =
+            sort_systems(trigger, &orders, &self.sets)?;
+        let mut positions = vec![0; sorted.len()];
+        for (position, i) in sorted.iter().enumerate() {
+            positions[*i] = position;
+        }
+        let mut entries = self.entries.drain(..).map(Some).collect::<Vec<_>>();
+        self.entries = sorted
+            .into_iter()
+            .map(|i| {
+                let mut entry = entries[i].take().unwrap();
+                entry.predecessors =
+                    predecessors[i].iter().map(|j| positions[*j]).collect();
+                entry
+            })
+            .collect();
+        self.unsorted = false;
+        Ok(())
+    }
+
+    /// Runs all systems, sorting them first if needed.
+    /// See [`Systems::fire_trigger`].
+    fn run(
+        &mut self,
+        trigger: SystemTrigger,
+        world: &mut World,
+        resources: &mut Resources,
+        assets: &mut AssetLibrary,
+        commands: &mut Commands,
+    ) -> Result<(), ParsecError> {
+        if self.unsorted {
+            self.sort(trigger)?;
+        }
+        let systems = &mut self.entries;
+
+        let mut start = 0;
+        while start < systems.len() {
+            let end = batch_end(systems, start);
+            let batch = &mut systems[start..end];
+            if batch[0].access.is_exclusive() {
+                let entry = &mut batch[0];
+                if !entry.should_run(resources) {
+                    start = end;
+                    continue;
+                }
+                let this_run = world.increment_change_tick();
+                world.set_last_change_tick(entry.last_run);
+                let result = entry.system.run(Ctx {
+                    world: &mut *world,
+                    resources: &mut *resources,
+                    assets: &mut *assets,
+                    commands: &mut *commands,
+                });
+                entry.last_run = this_run;
+                // Commands queued before a failure are applied too, so they
+                // aren't left for the next system.
+                let applied = commands.apply(world, resources);
+                result.and(applied)?;
+            } else {
+                run_batch(batch, world, resources, assets)?;
+            }
+            start = end;
+        }
+        Ok(())
+    }
+}
+
 /// Stores all systems grouped by [`SystemTrigger`].
 pub struct Systems {
-    systems: HashMap<SystemTrigger, Vec<Box<dyn System>>>,
+    systems: HashMap<SystemTrigger, TriggerSystems>,
+    /// Systems run on transitions, one entry for every type of states.
+    states: Vec<Box<dyn ApplyTransitions>>,
+    /// Workers running systems of parallel batches.
+    pool: Arc<ThreadPool>,
+    /// Operations queued by systems, applied after every system.
+    commands: Commands,
 }
 
 impl Systems {
     pub fn new() -> Systems {
         Systems {
             systems: HashMap::new(),
+            states: Vec::new(),
+            pool: ThreadPool::global(),
+            commands: Commands::new(),
+        }
+    }
+
+    /// Creates systems running parallel batches on the workers of `pool`
+    /// instead of the [global][ThreadPool::global] pool.
+    pub fn with_pool(pool: Arc<ThreadPool>) -> Systems {
+        Systems {
+            pool,
+            ..Systems::new()
         }
     }
 
     fn get_systems_by_trigger(
         &mut self,
         system_trigger: SystemTrigger,
-    ) -> &mut Vec<Box<dyn System>> {
+    ) -> &mut TriggerSystems {
         self.systems.entry(system_trigger).or_default()
     }
 
+    fn get_state_systems<S: States>(&mut self) -> &mut StateSystems<S> {
+        let index = match self
+            .states
+            .iter()
+            .position(|states| states.as_any().is::<StateSystems<S>>())
+        {
+            Some(index) => index,
+            None => {
+                self.states.push(Box::new(StateSystems::<S>::new()));
+                self.states.len() - 1
+            },
+        };
+        // UNWRAP: the entry at `index` stores states of type `S`.
+        self.states[index]
+            .as_any_mut()
+            .downcast_mut::<StateSystems<S>>()
+            .unwrap()
+    }
+
     /// Registers a new system to be executed on `system_trigger`.
+    /// Systems run in registration order unless ordered with [`IntoSystemConfig`].
     pub fn add<M>(
         &mut self,
         system_trigger: SystemTrigger,
-        system: impl IntoSystem<M>,
+        system: impl IntoSystemConfig<M>,
+    ) {
+        self.get_systems_by_trigger(system_trigger)
+            .push(system.into_config());
+    }
+
+    /// Adds ordering constraints to all systems in a set run on `system_trigger`.
+    pub fn configure_set(
+        &mut self,
+        system_trigger: SystemTrigger,
+        set_config: SetConfig,
+    ) {
+        let trigger_systems = self.get_systems_by_trigger(system_trigger);
+        trigger_systems.sets.push(set_config);
+        trigger_systems.unsorted = true;
+    }
+
+    /// Registers states of type `S`, so that transitions queued in
+    /// [`NextState`][state::NextState] are applied even without any systems
+    /// run on them.
+    pub fn add_state<S: States>(&mut self) { self.get_state_systems::<S>(); }
+
+    /// Registers a new system to be executed on the state transition
+    /// `schedule`, i.e. [`OnEnter`][state::OnEnter] or
+    /// [`OnExit`][state::OnExit] of a state.
+    /// Systems can be ordered just like with [`Systems::add`].
+    pub fn add_on_transition<M, T: Transition>(
+        &mut self,
+        schedule: T,
+        system: impl IntoSystemConfig<M>,
     ) {
-        let trigger_vec = self.get_systems_by_trigger(system_trigger);
-        trigger_vec.push(Box::new(system.into_system()));
+        let (kind, state) = schedule.into_key();
+        self.get_state_systems::<T::State>()
+            .get_systems(kind, state)
+            .push(system.into_config());
     }
 
     /// Registers an entire [SystemBundle].
@@ -77,6 +308,25 @@ impl Systems {
     }
 
     /// Executes all the systems registered for trigger `system_type`.
+    ///
+    /// # Errors
+    ///
+    /// - If ordering constraints of the systems form a cycle.
+    /// - If any system fails.
+    ///
+    /// Consecutive systems with compatible [access][SystemAccess] and no
+    /// ordering constraints between them run in parallel on the workers of
+    /// the pool of `self`, which is also the [current][ThreadPool::current]
+    /// pool while the systems run.
+    /// [`Commands`] queued by systems are applied right after they finish,
+    /// even if they fail. Systems whose
+    /// [run conditions][IntoSystemConfig::run_if] aren't met are skipped.
+    ///
+    /// [State transitions][state::NextState] are applied only at the start of
+    /// [`SystemTrigger::Update`], running the [`OnExit`][state::OnExit]
+    /// systems of the old state and then the [`OnEnter`][state::OnEnter]
+    /// systems of the new one. Systems entering the initial state run at the
+    /// start of the first update.
     pub fn fire_trigger(
         &mut self,
         system_type: SystemTrigger,
@@ -84,17 +334,115 @@ impl Systems {
         resources: &mut Resources,
         assets: &mut AssetLibrary,
     ) -> Result<(), ParsecError> {
-        if let Some(systems) = self.systems.get_mut(&system_type) {
-            for system in systems.iter_mut() {
-                system.run(Ctx {
-                    world: &mut *world,
-                    resources: &mut *resources,
-                    assets: &mut *assets,
-                })?;
+        let _pool = self.pool.enter();
+        if system_type == SystemTrigger::Update {
+            for state_systems in self.states.iter_mut() {
+                state_systems.apply(
+                    system_type,
+                    world,
+                    resources,
+                    assets,
+                    &mut self.commands,
+                )?;
             }
         }
-        Ok(())
+        let Some(trigger_systems) = self.systems.get_mut(&system_type) else {
+            return Ok(());
+        };
+        trigger_systems.run(
+            system_type,
+            world,
+            resources,
+            assets,
+            &mut self.commands,
+        )
+    }
+}
+
+/// Finds the end of the batch starting at `start`.
+/// A batch is either a single exclusive system or consecutive systems with
+/// compatible access. A system ordered after another system of the batch
+/// starts a new batch, so that it sees the commands of that system applied.
+fn batch_end(systems: &[SystemEntry], start: usize) -> usize {
+    let mut access = systems[start].access.clone();
+    if access.is_exclusive() {
+        return start + 1;
+    }
+
+    let mut end = start + 1;
+    while end < systems.len()
+        && access.is_compatible(&systems[end].access)
+        && systems[end].predecessors.iter().all(|i| *i < start)
+    {
+        access.extend(&systems[end].access);
+        end += 1;
+    }
+    end
+}
+
+/// Runs systems of a batch in parallel on the [current][ThreadPool::current]
+/// pool. The first system runs on the current thread.
+/// Run conditions of all systems are evaluated before any of them runs.
+fn run_batch(
+    batch: &mut [SystemEntry],
+    world: &mut World,
+    resources: &mut Resources,
+    assets: &AssetLibrary,
+) -> Result<(), ParsecError> {
+    let mut batch = batch
+        .iter_mut()
+        .filter_map(|entry| entry.should_run(resources).then_some(entry))
+        .collect::<Vec<_>>();
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let ticks = batch
+        .iter()
+        .map(|entry| SystemTicks {
+            last_run: entry.last_run,
+            this_run: world.increment_change_tick(),
+        })
+        .collect::<Vec<_>>();
+    let mut queues = batch.iter().map(|_| Commands::new()).collect::<Vec<_>>();
+
+    let shared_world = &*world;
+    let shared_resources = &*resources;
+    let mut results = batch.iter().map(|_| Ok(())).collect::<Vec<_>>();
+    ThreadPool::current().scope(|scope| {
+        let mut runs = batch
+            .iter_mut()
+            .zip(ticks.iter())
+            .zip(queues.iter_mut())
+            .zip(results.iter_mut())
+            .map(|(((entry, ticks), queue), result)| {
+                let ctx = SharedCtx {
+                    world: shared_world,
+                    resources: shared_resources,
+                    assets,
+                    ticks: *ticks,
+                };
+                move || *result = entry.system.run_shared(ctx, queue)
+            });
+        // UNWRAP: batches are never empty.
+        let mut first = runs.next().unwrap();
+        for run in runs {
+            scope.spawn(run);
+        }
+        first();
+    });
+
+    for (entry, ticks) in batch.iter_mut().zip(ticks) {
+        entry.last_run = ticks.this_run;
     }
+    let mut applied = Ok(());
+    for queue in queues.iter_mut() {
+        let result = queue.apply(world, resources);
+        if applied.is_ok() {
+            applied = result;
+        }
+    }
+    results.into_iter().collect::<Result<(), _>>().and(applied)
 }
 
 impl Default for Systems {
@@ -104,6 +452,20 @@ impl Default for Systems {
 /// Marks a type that is a system.
 pub trait System: Send + Sync + 'static {
     fn run<'a>(&mut self, ctx: Ctx<'a>) -> Result<(), ParsecError>;
+
+    /// Gets the data accessed by the system.
+    /// Systems are [exclusive][SystemAccess::exclusive] by default.
+    fn access(&self) -> SystemAccess { SystemAccess::exclusive() }
+
+    /// Runs the system with shared access to the engine state.
+    /// Called instead of [`System::run`] when [`System::access`] isn't exclusive.
+    fn run_shared<'a>(
+        &mut self,
+        _ctx: SharedCtx<'a>,
+        _commands: &mut Commands,
+    ) -> Result<(), ParsecError> {
+        Err(StrError("System requires exclusive access").into())
+    }
 }
 
 pub trait IntoSystem<Marker> {
@@ -173,3 +535,123 @@ pub trait SystemBundle {
     /// Inserts the bundle's systems into `systems`.
     fn insert(self, systems: &mut Systems);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread::{self, ThreadId};
+
+    use super::*;
+    use crate::ecs::system::param::{Res, ResMut};
+
+    struct First(Option<ThreadId>);
+    struct Second(Option<ThreadId>);
+
+    fn first(mut first: ResMut<First>) {
+        first.0 = Some(thread::current().id());
+    }
+
+    fn second(mut second: ResMut<Second>, _first: Res<u32>) {
+        second.0 = Some(thread::current().id());
+    }
+
+    fn conflicting(first: Res<First>, mut value: ResMut<u32>) {
+        *value = first.0.map_or(0, |_| 1);
+    }
+
+    fn reader(_value: Res<u32>) {}
+
+    #[test]
+    fn parallel_batches() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut systems = Systems::new();
+        resources.add(First(None));
+        resources.add(Second(None));
+        resources.add(0_u32);
+        systems.add(SystemTrigger::Update, first);
+        systems.add(SystemTrigger::Update, second);
+        systems.add(SystemTrigger::Update, conflicting);
+
+        let entries = &systems.systems[&SystemTrigger::Update].entries;
+        assert_eq!(batch_end(entries, 0), 2);
+        assert_eq!(batch_end(entries, 2), 3);
+
+        systems
+            .fire_trigger(
+                SystemTrigger::Update,
+                &mut world,
+                &mut resources,
+                &mut assets,
+            )
+            .unwrap();
+        let first = resources.get::<First>().unwrap().0.unwrap();
+        let second = resources.get::<Second>().unwrap().0.unwrap();
+        assert_eq!(first, thread::current().id());
+        assert_ne!(first, second);
+        assert_eq!(*resources.get::<u32>().unwrap(), 1);
+
+        // Ordered systems don't share a batch, even with compatible access.
+        systems.add(SystemTrigger::LateUpdate, reader.after("reader"));
+        systems.add(SystemTrigger::LateUpdate, reader.label("reader"));
+        systems.add(SystemTrigger::LateUpdate, reader);
+        systems
+            .fire_trigger(
+                SystemTrigger::LateUpdate,
+                &mut world,
+                &mut resources,
+                &mut assets,
+            )
+            .unwrap();
+        let entries = &systems.systems[&SystemTrigger::LateUpdate].entries;
+        assert_eq!(batch_end(entries, 0), 1);
+        assert_eq!(batch_end(entries, 1), 3);
+    }
+
+    fn push(name: &'static str) -> impl FnMut(Ctx) {
+        move |ctx: Ctx| ctx.resources.get_mut::<Vec<&str>>().unwrap().push(name)
+    }
+
+    #[test]
+    fn system_ordering() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut systems = Systems::new();
+        resources.add(Vec::<&str>::new());
+        systems.add(SystemTrigger::Update, push("c").label("c").after("b"));
+        systems.add(SystemTrigger::Update, push("b").label("b").in_set("s"));
+        systems.add(SystemTrigger::Update, push("a").label("a"));
+        systems.configure_set(
+            SystemTrigger::Update,
+            SetConfig::new("s").after("a"),
+        );
+        systems.add(SystemTrigger::Update, push("unordered").before("missing"));
+
+        systems
+            .fire_trigger(
+                SystemTrigger::Update,
+                &mut world,
+                &mut resources,
+                &mut assets,
+            )
+            .unwrap();
+        assert_eq!(*resources.get::<Vec<&str>>().unwrap(), vec![
+            "a",
+            "b",
+            "c",
+            "unordered"
+        ]);
+
+        systems.add(SystemTrigger::Update, push("d").after("c").before("a"));
+        let err = systems
+            .fire_trigger(
+                SystemTrigger::Update,
+                &mut world,
+                &mut resources,
+                &mut assets,
+            )
+            .unwrap_err();
+        assert!(format!("{err}").contains("ordering cycle"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Module responsible for typed system parameters.
+
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use parsec_engine_macros::{
+    impl_into_system, impl_system_param, multiple_tuples,
+};
+
+use crate::{
+    assets::AssetLibrary,
+    ctx::{Ctx, SharedCtx},
+    ecs::{
+        commands::Commands,
+        resources::{
+            ResourceError, ResourceMarker,
+            resource::{Resource, ResourceMut},
+        },
+        system::{IntoSystem, System, access::SystemAccess},
+        world::{
+            fetch::Fetch,
+            filter::Filter,
+            query::{Query, QueryState},
+        },
+    },
+    error::ParsecError,
+};
+
+/// Represents a type that can be used as a parameter of a system function.
+/// It is implemented for [`Res`], [`ResMut`], [`Query`], `&mut` [`Commands`],
+/// [`Local`], `&` [`AssetLibrary`] and all tuples containing up to 16 values
+/// that implement [`SystemParam`].
+pub trait SystemParam {
+    /// Data kept by the system between runs.
+    type State: Send + Sync + 'static;
+    /// Type passed to the system function.
+    type Item<'w, 's>;
+    /// Creates the state before the first run of the system.
+    fn init_state() -> Self::State;
+    /// Adds the components and resources used by this parameter to `access`.
+    fn access(access: &mut SystemAccess);
+    /// Gets the parameter for a single run of the system.
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError>;
+    /// Queues operations stored in `state` after the system finishes.
+    fn apply(_state: &mut Self::State, _commands: &mut Commands) {}
+}
+
+/// Shared access to the resource of type `R`.
+pub type Res<'a, R> = Resource<'a, R>;
+/// Mutable access to the resource of type `R`.
+pub type ResMut<'a, R> = ResourceMut<'a, R>;
+
+impl<'a, R: ResourceMarker> SystemParam for Resource<'a, R> {
+    type State = ();
+    type Item<'w, 's> = Resource<'w, R>;
+
+    fn init_state() -> Self::State {}
+
+    fn access(access: &mut SystemAccess) { access.read_resource::<R>(); }
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        Ok(ctx
+            .resources
+            .get::<R>()
+            .ok_or(ResourceError::ResourceNotFound(
+                std::any::type_name::<R>(),
+            ))?)
+    }
+}
+
+impl<'a, R: ResourceMarker> SystemParam for ResourceMut<'a, R> {
+    type State = ();
+    type Item<'w, 's> = ResourceMut<'w, R>;
+
+    fn init_state() -> Self::State {}
+
+    fn access(access: &mut SystemAccess) { access.write_resource::<R>(); }
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        Ok(ctx.resources.get_mut::<R>().ok_or(
+            ResourceError::ResourceNotFound(std::any::type_name::<R>()),
+        )?)
+    }
+}
+
+/// Matching archetypes are cached between runs of the system.
+impl<T: Fetch + 'static, F: Filter + 'static> SystemParam for Query<'_, T, F> {
+    type State = QueryState<T, F>;
+    type Item<'w, 's> = Query<'w, T, F>;
+
+    fn init_state() -> Self::State { QueryState::new() }
+
+    fn access(access: &mut SystemAccess) {
+        T::access(access);
+        F::access(access);
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        state.query_with_ticks(ctx.world, ctx.ticks)
+    }
+}
+
+/// Commands queued through this parameter are applied after the system finishes.
+impl SystemParam for &mut Commands {
+    type State = Commands;
+    type Item<'w, 's> = &'s mut Commands;
+
+    fn init_state() -> Self::State { Commands::new() }
+
+    fn access(_access: &mut SystemAccess) {}
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        _ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        Ok(state)
+    }
+
+    fn apply(state: &mut Self::State, commands: &mut Commands) {
+        commands.append(state);
+    }
+}
+
+impl SystemParam for &AssetLibrary {
+    type State = ();
+    type Item<'w, 's> = &'w AssetLibrary;
+
+    fn init_state() -> Self::State {}
+
+    fn access(_access: &mut SystemAccess) {}
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        Ok(ctx.assets)
+    }
+}
+
+/// Value owned by a single system and kept between its runs.
+/// Starts as [`Default::default`].
+#[derive(Debug)]
+pub struct Local<'a, T>(&'a mut T);
+
+impl<'a, T> Deref for Local<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target { self.0 }
+}
+
+impl<'a, T> DerefMut for Local<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target { self.0 }
+}
+
+impl<'a, T: Default + Send + Sync + 'static> SystemParam for Local<'a, T> {
+    type State = T;
+    type Item<'w, 's> = Local<'s, T>;
+
+    fn init_state() -> Self::State { T::default() }
+
+    fn access(_access: &mut SystemAccess) {}
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        _ctx: SharedCtx<'w>,
+    ) -> Result<Self::Item<'w, 's>, ParsecError> {
+        Ok(Local(state))
+    }
+}
+
+/// Represents a type that can be returned from a system function.
+pub trait SystemOutput {
+    fn into_result(self) -> Result<(), ParsecError>;
+}
+
+impl SystemOutput for () {
+    fn into_result(self) -> Result<(), ParsecError> { Ok(()) }
+}
+
+impl SystemOutput for Result<(), ParsecError> {
+    fn into_result(self) -> Result<(), ParsecError> { self }
+}
+
+/// System created from a function taking [`SystemParam`]s `P` and returning `Out`.
+pub struct ParamFunctionSystem<F, P: SystemParam, Out> {
+    function: F,
+    state: Option<P::State>,
+    _marker: PhantomData<fn() -> (P, Out)>,
+}
+
+impl<F, P: SystemParam, Out> ParamFunctionSystem<F, P, Out> {
+    pub fn new(function: F) -> ParamFunctionSystem<F, P, Out> {
+        ParamFunctionSystem {
+            function,
+            state: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+multiple_tuples!(impl_system_param, 16);
+multiple_tuples!(impl_into_system, 16);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        resources::Resources,
+        system::{SystemTrigger, Systems},
+        world::{World, fetch::Mut},
+    };
+
+    fn count(
+        mut counter: ResMut<u32>,
+        mut query: Query<Mut<f32>>,
+        commands: &mut Commands,
+        mut runs: Local<u32>,
+    ) {
+        *runs += 1;
+        for (_, mut value) in query.iter() {
+            *value += 1.0;
+            *counter += 1;
+        }
+        commands.spawn(*runs as f32);
+    }
+
+    #[test]
+    fn typed_params() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut systems = Systems::new();
+        resources.add(0_u32);
+        systems.add(SystemTrigger::Update, count);
+
+        for _ in 0..3 {
+            systems
+                .fire_trigger(
+                    SystemTrigger::Update,
+                    &mut world,
+                    &mut resources,
+                    &mut assets,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(*resources.get::<u32>().unwrap(), 3);
+        let mut query = world.query::<f32>();
+        let mut values = query.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort_by(f32::total_cmp);
+        assert_eq!(values, vec![3.0, 3.0, 3.0]);
+    }
+}
@@ -0,0 +1,292 @@
+//! Module responsible for states of the app and systems run when they change.
+
+use std::{any::Any, collections::HashMap, fmt::Debug, hash::Hash};
+
+use crate::{
+    assets::AssetLibrary,
+    ecs::{
+        commands::Commands,
+        resources::Resources,
+        system::{SystemTrigger, TriggerSystems},
+        world::World,
+    },
+    error::ParsecError,
+};
+
+/// Marks a type whose values are states of the app, e.g. an enum of screens.
+pub trait States: Debug + Clone + Eq + Hash + Send + Sync + 'static {}
+
+impl<T: Debug + Clone + Eq + Hash + Send + Sync + 'static> States for T {}
+
+/// Resource holding the current state of type `S`.
+/// It's changed only by applying transitions queued with [`NextState`].
+#[derive(Debug)]
+pub struct State<S: States>(S);
+
+impl<S: States> State<S> {
+    pub fn new(state: S) -> State<S> { State(state) }
+
+    /// Gets the current state.
+    pub fn get(&self) -> &S { &self.0 }
+}
+
+/// Resource queueing a transition to another state of type `S`.
+/// Transitions are applied at the start of the next update, see
+/// [`Systems::fire_trigger`][super::Systems::fire_trigger].
+#[derive(Debug)]
+pub struct NextState<S: States>(Option<S>);
+
+impl<S: States> Default for NextState<S> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<S: States> NextState<S> {
+    pub fn new() -> NextState<S> { NextState(None) }
+
+    /// Queues a transition to `state`, replacing the previously queued one.
+    /// Transitions to the current state are ignored.
+    pub fn set(&mut self, state: S) { self.0 = Some(state); }
+
+    /// Gets the state of the queued transition, if there is one.
+    pub fn get(&self) -> Option<&S> { self.0.as_ref() }
+}
+
+/// Whether systems run when entering or exiting a state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransitionKind {
+    Enter,
+    Exit,
+}
+
+/// Marks a schedule of systems run on a state transition.
+pub trait Transition {
+    type State: States;
+
+    /// Splits the schedule into its kind and state.
+    fn into_key(self) -> (TransitionKind, Self::State);
+}
+
+/// Systems scheduled with this run when the app enters the state.
+#[derive(Debug, Clone)]
+pub struct OnEnter<S: States>(pub S);
+
+impl<S: States> Transition for OnEnter<S> {
+    type State = S;
+
+    fn into_key(self) -> (TransitionKind, S) { (TransitionKind::Enter, self.0) }
+}
+
+/// Systems scheduled with this run when the app exits the state.
+#[derive(Debug, Clone)]
+pub struct OnExit<S: States>(pub S);
+
+impl<S: States> Transition for OnExit<S> {
+    type State = S;
+
+    fn into_key(self) -> (TransitionKind, S) { (TransitionKind::Exit, self.0) }
+}
+
+/// Applies transitions of states of a single type.
+pub(super) trait ApplyTransitions: Send + Sync {
+    /// Applies the transition queued in [`NextState`], running the systems
+    /// scheduled on it. `trigger` is the trigger being fired.
+    fn apply(
+        &mut self,
+        trigger: SystemTrigger,
+        world: &mut World,
+        resources: &mut Resources,
+        assets: &mut AssetLibrary,
+        commands: &mut Commands,
+    ) -> Result<(), ParsecError>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Systems run on transitions of states of type `S`.
+pub(super) struct StateSystems<S: States> {
+    schedules: HashMap<(TransitionKind, S), TriggerSystems>,
+    /// Set once the systems entering the initial state ran.
+    entered: bool,
+}
+
+impl<S: States> StateSystems<S> {
+    pub(super) fn new() -> StateSystems<S> {
+        StateSystems {
+            schedules: HashMap::new(),
+            entered: false,
+        }
+    }
+
+    /// Gets the systems run on transition `kind` of `state`.
+    pub(super) fn get_systems(
+        &mut self,
+        kind: TransitionKind,
+        state: S,
+    ) -> &mut TriggerSystems {
+        self.schedules.entry((kind, state)).or_default()
+    }
+
+    /// Runs the systems scheduled on the transition `key`.
+    fn run(
+        &mut self,
+        key: (TransitionKind, S),
+        trigger: SystemTrigger,
+        world: &mut World,
+        resources: &mut Resources,
+        assets: &mut AssetLibrary,
+        commands: &mut Commands,
+    ) -> Result<(), ParsecError> {
+        match self.schedules.get_mut(&key) {
+            Some(systems) => {
+                systems.run(trigger, world, resources, assets, commands)
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: States> ApplyTransitions for StateSystems<S> {
+    fn apply(
+        &mut self,
+        trigger: SystemTrigger,
+        world: &mut World,
+        resources: &mut Resources,
+        assets: &mut AssetLibrary,
+        commands: &mut Commands,
+    ) -> Result<(), ParsecError> {
+        let Some(current) = resources.get::<State<S>>().map(|s| s.0.clone())
+        else {
+            return Ok(());
+        };
+        if !self.entered {
+            self.entered = true;
+            self.run(
+                (TransitionKind::Enter, current.clone()),
+                trigger,
+                world,
+                resources,
+                assets,
+                commands,
+            )?;
+        }
+
+        let next = resources
+            .get_mut::<NextState<S>>()
+            .and_then(|mut next| next.0.take());
+        let Some(next) = next.filter(|next| *next != current) else {
+            return Ok(());
+        };
+        self.run(
+            (TransitionKind::Exit, current),
+            trigger,
+            world,
+            resources,
+            assets,
+            commands,
+        )?;
+        if let Some(mut state) = resources.get_mut::<State<S>>() {
+            state.0 = next.clone();
+        }
+        self.run(
+            (TransitionKind::Enter, next),
+            trigger,
+            world,
+            resources,
+            assets,
+            commands,
+        )
+    }
+
+    fn as_any(&self) -> &dyn Any { self }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ctx::Ctx,
+        ecs::system::{
+            Systems, condition::in_state, config::IntoSystemConfig,
+            param::ResMut,
+        },
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Screen {
+        MainMenu,
+        Loading,
+        InGame,
+        Paused,
+    }
+
+    fn log(name: &'static str) -> impl FnMut(Ctx) {
+        move |ctx: Ctx| ctx.resources.get_mut::<Vec<&str>>().unwrap().push(name)
+    }
+
+    fn finish_loading(mut next: ResMut<NextState<Screen>>) {
+        next.set(Screen::InGame);
+    }
+
+    #[test]
+    fn state_transitions() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let mut assets = AssetLibrary::new();
+        let mut systems = Systems::new();
+        resources.add(Vec::<&str>::new());
+        resources.add(State::new(Screen::MainMenu));
+        resources.add(NextState::<Screen>::new());
+        systems.add_on_transition(OnEnter(Screen::MainMenu), log("menu"));
+        systems.add_on_transition(OnExit(Screen::MainMenu), log("!menu"));
+        systems.add_on_transition(OnEnter(Screen::InGame), log("game"));
+        systems.add_on_transition(OnExit(Screen::InGame), log("!game"));
+        systems.add_on_transition(OnEnter(Screen::Paused), log("paused"));
+        systems.add(
+            SystemTrigger::Update,
+            finish_loading.run_if(in_state(Screen::Loading)),
+        );
+        systems.add(
+            SystemTrigger::Update,
+            log("update").run_if(in_state(Screen::InGame)),
+        );
+
+        let mut fire = |resources: &mut Resources, trigger| {
+            systems
+                .fire_trigger(trigger, &mut world, resources, &mut assets)
+                .unwrap()
+        };
+        let go_to = |resources: &mut Resources, screen| {
+            resources
+                .get_mut::<NextState<Screen>>()
+                .unwrap()
+                .set(screen);
+        };
+        fire(&mut resources, SystemTrigger::Update);
+        go_to(&mut resources, Screen::Loading);
+        fire(&mut resources, SystemTrigger::Update);
+        assert_eq!(
+            *resources.get::<State<Screen>>().unwrap().get(),
+            Screen::Loading
+        );
+        fire(&mut resources, SystemTrigger::Update);
+        fire(&mut resources, SystemTrigger::Update);
+        go_to(&mut resources, Screen::Paused);
+        // Other triggers don't apply transitions.
+        fire(&mut resources, SystemTrigger::MouseMovement);
+        assert_eq!(
+            *resources.get::<State<Screen>>().unwrap().get(),
+            Screen::InGame
+        );
+        fire(&mut resources, SystemTrigger::Update);
+        go_to(&mut resources, Screen::Paused);
+        fire(&mut resources, SystemTrigger::Update);
+
+        assert_eq!(*resources.get::<Vec<&str>>().unwrap(), vec![
+            "menu", "!menu", "game", "update", "update", "!game", "paused"
+        ]);
+    }
+}
@@ -1,22 +1,48 @@
 //! Module responsible for adding components to entities.
 
-use crate::ecs::world::{
-    archetype::{Archetype, ArchetypeError, ArchetypeId},
-    spawn::Spawn,
+use crate::ecs::{
+    entity::Entity,
+    world::{
+        archetype::{Archetype, ArchetypeError, ArchetypeId},
+        change_detection::Tick,
+        component::ComponentId,
+        sparse_set::SparseSets,
+        spawn::Spawn,
+    },
 };
 
 /// Marks a type that can be used to add components to an entity.
 /// It is automatically implemented for all types implementing [`Spawn`].
 pub trait AddComponent: Send + Sync + 'static {
     fn archetype_id(&self) -> Result<ArchetypeId, ArchetypeError>;
-    fn add_to(&self, archetype: &mut Archetype) -> Result<(), ArchetypeError>;
+    /// Appends ids of all added components to `ids`.
+    /// See [`Spawn::component_ids`].
+    fn component_ids(&self, ids: &mut Vec<ComponentId>);
+    /// Moves all components, inserted at `tick`, to `entity`, which is the last
+    /// entity of `archetype`. See [`Spawn::spawn`].
+    fn add_to(
+        self,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError>;
 }
 
 impl<T: Spawn> AddComponent for T {
     fn archetype_id(&self) -> Result<ArchetypeId, ArchetypeError> {
         self.archetype_id()
     }
-    fn add_to(&self, archetype: &mut Archetype) -> Result<(), ArchetypeError> {
-        Spawn::spawn(self, archetype)
+    fn component_ids(&self, ids: &mut Vec<ComponentId>) {
+        Spawn::component_ids(self, ids)
+    }
+    fn add_to(
+        self,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        Spawn::spawn(self, archetype, sparse_sets, entity, tick)
     }
 }
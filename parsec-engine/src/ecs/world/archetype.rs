@@ -1,10 +1,12 @@
 //! Module responsible for handling archetypes.
 
 use std::{
-    any::TypeId,
+    alloc::{Layout, alloc, dealloc, handle_alloc_error, realloc},
     collections::{HashMap, HashSet},
     fmt::Debug,
     hash::{DefaultHasher, Hash, Hasher},
+    mem::ManuallyDrop,
+    ptr::NonNull,
     sync::{Arc, RwLock},
 };
 
@@ -13,7 +15,12 @@ use thiserror::Error;
 
 use crate::ecs::{
     entity::Entity,
-    world::{component::Component, spawn::Spawn},
+    world::{
+        change_detection::{ComponentTicks, Tick},
+        component::{CloneFn, Component, ComponentId, StorageType},
+        sparse_set::SparseSets,
+        spawn::Spawn,
+    },
 };
 
 #[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,12 +39,36 @@ pub enum ArchetypeError {
     BundleCannotContainManyValuesOfTheSameTypeMerge,
     #[error("Archetype doesn't contain this type")]
     ArchetypeIdDoesntContainThisType,
+    #[error("Tag components don't store any data")]
+    TagHasNoData,
+    #[error("Component can not be cloned")]
+    ComponentNotCloneable,
+    #[error("Component is not registered")]
+    ComponentNotRegistered,
+    #[error("Component is already registered")]
+    ComponentAlreadyRegistered,
+    #[error("Component data has the wrong size")]
+    WrongComponentSize,
+    #[error("Query fetches sparse components or filters single rows")]
+    QueryNotChunkable,
+    #[error("Bundles spawned in a batch have different components")]
+    BatchArchetypeMismatch,
+}
+
+/// Checks if components of type `T` are tags. Tags are zero-sized components
+/// tracked only in [`ArchetypeId`]s, without any column storing them.
+pub fn is_tag<T: Component>() -> bool {
+    T::STORAGE == StorageType::Table
+        && std::mem::size_of::<T>() == 0
+        && !std::mem::needs_drop::<T>()
 }
 
 /// Unique identifier for an [`Archetype`].
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ArchetypeId {
-    component_types: HashSet<TypeId>,
+    component_types: HashSet<ComponentId>,
+    /// Component types that are tags and have no column.
+    tag_types: HashSet<ComponentId>,
     hash: u64,
 }
 
@@ -52,9 +83,9 @@ impl ArchetypeId {
     ///
     /// # Errors
     ///
-    /// - If `component_types` contains multiple instances of the same [`TypeId`].
+    /// - If `component_types` contains the same [`ComponentId`] multiple times.
     pub fn new(
-        component_types: Vec<TypeId>,
+        component_types: Vec<ComponentId>,
     ) -> Result<ArchetypeId, ArchetypeError> {
         let mut set = HashSet::new();
         let mut hash = 0_u64;
@@ -72,10 +103,18 @@ impl ArchetypeId {
 
         Ok(ArchetypeId {
             component_types: set,
+            tag_types: HashSet::new(),
             hash,
         })
     }
 
+    /// Creates a new [`ArchetypeId`] containing a single tag `tag_type`.
+    pub fn tag(tag_type: ComponentId) -> ArchetypeId {
+        let mut id = ArchetypeId::new(vec![tag_type]).unwrap();
+        id.tag_types.insert(tag_type);
+        id
+    }
+
     /// Merges this [`ArchetypeId`] with `self` and outputs the resulting id.
     ///
     /// # Errors
@@ -99,6 +138,11 @@ impl ArchetypeId {
 
         Ok(ArchetypeId {
             component_types: set,
+            tag_types: self
+                .tag_types
+                .union(&other.tag_types)
+                .copied()
+                .collect(),
             hash,
         })
     }
@@ -126,6 +170,11 @@ impl ArchetypeId {
 
         Ok(ArchetypeId {
             component_types: set,
+            tag_types: self
+                .tag_types
+                .difference(&other.tag_types)
+                .copied()
+                .collect(),
             hash,
         })
     }
@@ -144,13 +193,23 @@ impl ArchetypeId {
         true
     }
 
-    /// Checks if `self` contains a single component with [`TypeId`] `component_type`.
-    pub fn contains_single(&self, component_type: &TypeId) -> bool {
+    /// Checks if `self` contains a single component with id `component_type`.
+    pub fn contains_single(&self, component_type: &ComponentId) -> bool {
         self.component_types.contains(component_type)
     }
 
+    /// Checks if the component with id `component_type` is a tag.
+    pub fn is_tag(&self, component_type: &ComponentId) -> bool {
+        self.tag_types.contains(component_type)
+    }
+
     /// Gets the number of component ids inside `self`.
     pub fn component_count(&self) -> usize { self.component_types.len() }
+
+    /// Iterates over all component ids inside `self`, including tags.
+    pub fn component_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.component_types.iter().copied()
+    }
 }
 
 /// Specifies the type of access that is currently possible for an [`ArchetypeColumn`].
@@ -174,7 +233,7 @@ pub struct BorrowingStats {
 }
 
 impl BorrowingStats {
-    fn new() -> BorrowingStats {
+    pub(super) fn new() -> BorrowingStats {
         BorrowingStats {
             access: ArchetypeColumnAccess::ReadWrite,
             count: 0,
@@ -198,30 +257,203 @@ impl BorrowingStats {
     }
 }
 
+/// Memory layout, drop and clone functions of a component type.
+/// Lets columns store components without knowing their type.
+#[derive(Debug, Clone, Copy)]
+pub struct ComponentInfo {
+    layout: Layout,
+    /// Drops a component in place. `None` if the type doesn't need dropping.
+    drop: Option<unsafe fn(*mut u8)>,
+    /// Clones a component. `None` if the type can't be cloned.
+    clone: Option<CloneFn>,
+}
+
+impl ComponentInfo {
+    /// Info of a column that has never stored any component.
+    const EMPTY: ComponentInfo = ComponentInfo {
+        layout: Layout::new::<()>(),
+        drop: None,
+        clone: None,
+    };
+
+    /// Gets the info of components of type `T`.
+    pub fn of<T: Component>() -> ComponentInfo {
+        unsafe fn drop_ptr<T>(ptr: *mut u8) {
+            unsafe { ptr.cast::<T>().drop_in_place() }
+        }
+
+        ComponentInfo {
+            layout: Layout::new::<T>(),
+            drop: std::mem::needs_drop::<T>()
+                .then_some(drop_ptr::<T> as unsafe fn(*mut u8)),
+            clone: T::CLONE,
+        }
+    }
+
+    /// Gets the info of components defined at runtime.
+    pub(super) fn dynamic(
+        layout: Layout,
+        drop: Option<unsafe fn(*mut u8)>,
+    ) -> ComponentInfo {
+        ComponentInfo {
+            layout,
+            drop,
+            clone: None,
+        }
+    }
+
+    /// Gets a dangling pointer aligned for the component type.
+    fn dangling(&self) -> NonNull<u8> {
+        // SAFETY: alignment is never zero.
+        unsafe {
+            NonNull::new_unchecked(std::ptr::without_provenance_mut(
+                self.layout.align(),
+            ))
+        }
+    }
+}
+
+/// A single component cut out of an [`Archetype`], owning its value.
+/// The value is dropped along with `self` unless it's moved into another
+/// archetype with [`Archetype::add_raw`].
+#[derive(Debug)]
+pub struct RawComponent {
+    info: ComponentInfo,
+    /// Component value, allocated with `info.layout`.
+    data: NonNull<u8>,
+    /// When the component was added and last changed.
+    ticks: ComponentTicks,
+}
+
+// SAFETY: components are `Send` and `Sync`.
+unsafe impl Send for RawComponent {}
+unsafe impl Sync for RawComponent {}
+
+impl RawComponent {
+    /// Allocates space for a component described by `info`.
+    fn alloc(info: ComponentInfo) -> NonNull<u8> {
+        if info.layout.size() == 0 {
+            return info.dangling();
+        }
+        // SAFETY: the layout has non-zero size.
+        let ptr = unsafe { alloc(info.layout) };
+        NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(info.layout))
+    }
+
+    /// Moves the component stored at `src` into a new allocation.
+    ///
+    /// # Safety
+    ///
+    /// `src` has to point to a valid component described by `info`.
+    /// The caller must not drop the value at `src` afterwards.
+    unsafe fn read(
+        info: ComponentInfo,
+        src: *const u8,
+        ticks: ComponentTicks,
+    ) -> RawComponent {
+        let data = Self::alloc(info);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                src,
+                data.as_ptr(),
+                info.layout.size(),
+            )
+        };
+        RawComponent { info, data, ticks }
+    }
+
+    /// Clones the component stored at `src` into a new allocation.
+    ///
+    /// # Safety
+    ///
+    /// `src` has to point to a valid component described by `info`.
+    unsafe fn clone_from(
+        info: ComponentInfo,
+        clone: CloneFn,
+        src: *const u8,
+        ticks: ComponentTicks,
+    ) -> RawComponent {
+        let data = Self::alloc(info);
+        unsafe { clone(src, data.as_ptr()) };
+        RawComponent { info, data, ticks }
+    }
+
+    /// Copies a component from raw bytes into a new allocation, marking it as
+    /// added at `tick`.
+    ///
+    /// # Errors
+    ///
+    /// - If `bytes` doesn't have the size of components described by `info`.
+    pub(super) fn from_bytes(
+        info: ComponentInfo,
+        bytes: &[u8],
+        tick: Tick,
+    ) -> Result<RawComponent, ArchetypeError> {
+        if bytes.len() != info.layout.size() {
+            return Err(ArchetypeError::WrongComponentSize);
+        }
+        // SAFETY: `bytes` holds a component of the right size, and copying it
+        // doesn't need its alignment.
+        Ok(unsafe {
+            RawComponent::read(info, bytes.as_ptr(), ComponentTicks::new(tick))
+        })
+    }
+
+    /// Releases the allocation without dropping the value, after it was moved out.
+    fn forget(self) {
+        let this = ManuallyDrop::new(self);
+        if this.info.layout.size() != 0 {
+            unsafe { dealloc(this.data.as_ptr(), this.info.layout) };
+        }
+    }
+}
+
+impl Drop for RawComponent {
+    fn drop(&mut self) {
+        if let Some(drop) = self.info.drop {
+            unsafe { drop(self.data.as_ptr()) };
+        }
+        if self.info.layout.size() != 0 {
+            unsafe { dealloc(self.data.as_ptr(), self.info.layout) };
+        }
+    }
+}
+
 /// Stores the data for a single type inside of an [`Archetype`].
 #[derive(Debug)]
 pub struct ArchetypeColumn {
-    /// Raw components data.
-    data: Vec<u8>,
+    /// Components data, allocated for `capacity` components.
+    data: NonNull<u8>,
+    /// Number of components that fit in the allocation.
+    capacity: usize,
+    /// Change ticks of every component.
+    ticks: Vec<ComponentTicks>,
     /// Current borrowing state.
     borrow: Arc<RwLock<BorrowingStats>>,
     /// Number of components.
     rows: usize,
-    /// Size of a single component.
-    component_size: usize,
+    /// Layout and drop function of stored components.
+    info: ComponentInfo,
 }
 
+// SAFETY: components are `Send` and `Sync`, and access to them is guarded
+// by `borrow`.
+unsafe impl Send for ArchetypeColumn {}
+unsafe impl Sync for ArchetypeColumn {}
+
 impl ArchetypeColumn {
-    fn new() -> ArchetypeColumn {
+    pub(super) fn new() -> ArchetypeColumn {
         ArchetypeColumn {
-            data: Vec::new(),
+            data: ComponentInfo::EMPTY.dangling(),
+            capacity: 0,
+            ticks: Vec::new(),
             borrow: Arc::new(RwLock::new(BorrowingStats::new())),
             rows: 0,
-            component_size: 1,
+            info: ComponentInfo::EMPTY,
         }
     }
 
-    fn is_mutable(&self) -> bool {
+    pub(super) fn is_mutable(&self) -> bool {
         let access = self.borrow.read().unwrap().access;
         access == ArchetypeColumnAccess::ReadWrite
     }
@@ -232,123 +464,279 @@ impl ArchetypeColumn {
             || access == ArchetypeColumnAccess::ReadWrite
     }
 
-    /// Sets component size to the size of `T`.
-    fn set_component_size<T: Component>(&mut self) {
-        self.component_size = size_of::<T>();
+    /// Sets the info of stored components, if `self` hasn't allocated yet.
+    fn set_info(&mut self, info: ComponentInfo) {
+        if self.capacity == 0 {
+            self.info = info;
+            self.data = info.dangling();
+        }
     }
 
-    /// Adds a component to `self`.
+    /// Gets a pointer to the component in row `row`.
+    pub(super) fn ptr_at(&self, row: usize) -> *mut u8 {
+        // SAFETY: callers only pass rows inside the allocation.
+        unsafe { self.data.as_ptr().add(row * self.info.layout.size()) }
+    }
+
+    /// Makes sure there is space for `additional` more components.
+    fn reserve(&mut self, additional: usize) {
+        let required = self
+            .rows
+            .checked_add(additional)
+            .expect("column allocation too large");
+        self.ticks.reserve(additional);
+        if required <= self.capacity {
+            return;
+        }
+        let size = self.info.layout.size();
+        if size == 0 {
+            self.capacity = usize::MAX;
+            return;
+        }
+
+        let new_capacity = (self.capacity * 2).max(required).max(4);
+        let new_layout = Layout::from_size_align(
+            size * new_capacity,
+            self.info.layout.align(),
+        )
+        .expect("column allocation too large");
+        let ptr = if self.capacity == 0 {
+            unsafe { alloc(new_layout) }
+        } else {
+            let old_layout = Layout::from_size_align(
+                size * self.capacity,
+                self.info.layout.align(),
+            )
+            .unwrap();
+            unsafe {
+                realloc(self.data.as_ptr(), old_layout, new_layout.size())
+            }
+        };
+        self.data =
+            NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(new_layout));
+        self.capacity = new_capacity;
+    }
+
+    /// Adds a component inserted at `tick` to `self`.
     ///
     /// # Errors
     ///
     /// - If the column is not writable <=> `self.borrow.access` != [`ArchetypeColumnAccess::ReadWrite`].
-    fn push<T: Component>(&mut self, value: T) -> Result<(), ArchetypeError> {
-        self.set_component_size::<T>();
-
+    pub(super) fn push<T: Component>(
+        &mut self,
+        value: T,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
         if !self.is_mutable() {
             return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
 
-        let bytes = unsafe {
-            std::slice::from_raw_parts(
-                &value as *const T as *const u8,
-                self.component_size,
-            )
-        };
-
-        self.data.extend_from_slice(bytes);
+        self.set_info(ComponentInfo::of::<T>());
+        self.reserve(1);
+        unsafe { self.ptr_at(self.rows).cast::<T>().write(value) };
+        self.ticks.push(ComponentTicks::new(tick));
         self.rows += 1;
 
         Ok(())
     }
 
-    /// Adds raw component data to `self`.
+    /// Moves a component cut out of another archetype into `self`.
     ///
     /// # Errors
     ///
     /// - If the column is not writable (`self.borrow.access` != [`ArchetypeColumnAccess::ReadWrite`]).
-    fn push_raw(&mut self, data: Vec<u8>) -> Result<(), ArchetypeError> {
+    pub(super) fn push_raw(
+        &mut self,
+        component: RawComponent,
+    ) -> Result<(), ArchetypeError> {
         if !self.is_mutable() {
             return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
 
-        self.data.extend_from_slice(&data);
+        self.set_info(component.info);
+        self.reserve(1);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                component.data.as_ptr(),
+                self.ptr_at(self.rows),
+                self.info.layout.size(),
+            )
+        };
+        self.ticks.push(component.ticks);
         self.rows += 1;
+        component.forget();
 
         Ok(())
     }
 
-    /// Removes the last component from `self`.
+    /// Checks if stored components can be cloned.
+    pub(super) fn is_cloneable(&self) -> bool { self.info.clone.is_some() }
+
+    /// Clones the component in row `row`, marking the clone as added at `tick`.
     ///
     /// # Errors
     ///
-    /// - If the column is not writable (`self.borrow.access` != [`ArchetypeColumnAccess::ReadWrite`]).
-    fn pop(&mut self) -> Result<(), ArchetypeError> {
-        if !self.is_mutable() {
-            return Err(ArchetypeError::ArchetypeColumnNotWritable);
+    /// - If `row` is larger than `self.rows` (out of bounds).
+    /// - If the column is not readable (`self.borrow.access` == [`ArchetypeColumnAccess::None`]).
+    /// - If stored components can't be cloned.
+    pub(super) fn get_raw(
+        &self,
+        row: usize,
+        tick: Tick,
+    ) -> Result<RawComponent, ArchetypeError> {
+        if !self.is_readable() {
+            return Err(ArchetypeError::ArchetypeColumnNotReadable);
         }
 
-        if self.rows == 0 {
-            return Ok(());
+        if row >= self.rows {
+            return Err(ArchetypeError::EntityNotFound);
         }
 
-        let _ = self.data.split_off(self.data.len() - self.component_size);
-        Ok(())
+        let clone = self
+            .info
+            .clone
+            .ok_or(ArchetypeError::ComponentNotCloneable)?;
+        Ok(unsafe {
+            RawComponent::clone_from(
+                self.info,
+                clone,
+                self.ptr_at(row),
+                ComponentTicks::new(tick),
+            )
+        })
     }
 
-    unsafe fn pop_unchecked(&mut self) {
-        if self.rows == 0 {
-            return;
+    /// Moves the last component into row `row`, which has to be vacated first.
+    ///
+    /// # Safety
+    ///
+    /// The component in row `row` has to be already dropped or moved out.
+    unsafe fn fill_gap(&mut self, row: usize) {
+        let last_row = self.rows - 1;
+        if row != last_row {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    self.ptr_at(last_row),
+                    self.ptr_at(row),
+                    self.info.layout.size(),
+                )
+            };
         }
-
-        let _ = self.data.split_off(self.data.len() - self.component_size);
+        self.ticks.swap_remove(row);
+        self.rows -= 1;
     }
 
-    /// Copies component from row `from` to row `to`.
+    /// Drops the component in row `row` and moves the last component in its place.
     ///
     /// # Errors
     ///
-    /// - If `from` or `to` are larger than `self.rows` (out of bounds).
+    /// - If `row` is larger than `self.rows` (out of bounds).
     /// - If the column is not writable (`self.borrow.access` != [`ArchetypeColumnAccess::ReadWrite`]).
-    fn copy(&mut self, from: usize, to: usize) -> Result<(), ArchetypeError> {
+    pub(super) fn swap_remove(
+        &mut self,
+        row: usize,
+    ) -> Result<(), ArchetypeError> {
         if !self.is_mutable() {
             return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
 
-        if from == to {
-            return Ok(());
+        if row >= self.rows {
+            return Err(ArchetypeError::EntityNotFound);
         }
 
-        if from >= self.rows || to >= self.rows {
-            return Err(ArchetypeError::EntityNotFound);
+        if let Some(drop) = self.info.drop {
+            unsafe { drop(self.ptr_at(row)) };
         }
+        unsafe { self.fill_gap(row) };
+        Ok(())
+    }
 
-        let copy_from =
-            from * self.component_size..(from + 1) * self.component_size;
-        let copy_to = to * self.component_size;
+    /// Moves the component in row `row` to the end of `other` and moves the
+    /// last component in its place.
+    ///
+    /// # Errors
+    ///
+    /// - If `row` is larger than `self.rows` (out of bounds).
+    /// - If either column is not writable (`borrow.access` != [`ArchetypeColumnAccess::ReadWrite`]).
+    fn swap_move(
+        &mut self,
+        row: usize,
+        other: &mut ArchetypeColumn,
+    ) -> Result<(), ArchetypeError> {
+        if !self.is_mutable() || !other.is_mutable() {
+            return Err(ArchetypeError::ArchetypeColumnNotWritable);
+        }
 
-        self.data.copy_within(copy_from, copy_to);
+        if row >= self.rows {
+            return Err(ArchetypeError::EntityNotFound);
+        }
 
+        other.set_info(self.info);
+        other.reserve(1);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.ptr_at(row),
+                other.ptr_at(other.rows),
+                self.info.layout.size(),
+            )
+        };
+        other.ticks.push(self.ticks[row]);
+        other.rows += 1;
+        unsafe { self.fill_gap(row) };
         Ok(())
     }
 
-    /// Gets the raw data for the component in row `idx`.
+    /// Drops the last component, regardless of the borrowing state.
+    unsafe fn pop_unchecked(&mut self) {
+        if self.rows == 0 {
+            return;
+        }
+
+        self.rows -= 1;
+        self.ticks.pop();
+        if let Some(drop) = self.info.drop {
+            unsafe { drop(self.ptr_at(self.rows)) };
+        }
+    }
+
+    /// Gets a pointer to the change ticks of the component in row `row`.
+    pub(super) fn ticks_at(&self, row: usize) -> *mut ComponentTicks {
+        // SAFETY: callers only pass rows of stored components.
+        unsafe { (self.ticks.as_ptr() as *mut ComponentTicks).add(row) }
+    }
+
+    /// Borrows `self` immutably until the returned lock is released.
     ///
     /// # Errors
     ///
-    /// - If `idx` are larger than `self.rows` (out of bounds).
-    /// - If the column is not readable (`self.borrow.access` != [`ArchetypeColumnAccess::Read`]).
-    fn get_raw(&self, idx: usize) -> Result<&[u8], ArchetypeError> {
+    /// - If the column is not readable (`self.borrow.access` == [`ArchetypeColumnAccess::None`]).
+    pub(super) fn lock(
+        &self,
+    ) -> Result<Arc<RwLock<BorrowingStats>>, ArchetypeError> {
         if !self.is_readable() {
             return Err(ArchetypeError::ArchetypeColumnNotReadable);
         }
 
-        if idx >= self.rows {
-            return Err(ArchetypeError::EntityNotFound);
+        let mut borrow = self.borrow.write().unwrap();
+        borrow.count += 1;
+        borrow.access = ArchetypeColumnAccess::Read;
+        Ok(self.borrow.clone())
+    }
+
+    /// Borrows `self` mutably until the returned lock is released.
+    ///
+    /// # Errors
+    ///
+    /// - If the column is not writable (`self.borrow.access` != [`ArchetypeColumnAccess::ReadWrite`]).
+    pub(super) fn lock_mut(
+        &self,
+    ) -> Result<Arc<RwLock<BorrowingStats>>, ArchetypeError> {
+        if !self.is_mutable() {
+            return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
 
-        Ok(&self.data
-            [self.component_size * idx..self.component_size * (idx + 1)])
+        self.borrow.write().unwrap().access = ArchetypeColumnAccess::None;
+        Ok(self.borrow.clone())
     }
 
     /// Gets a slice of stored components.
@@ -364,7 +752,7 @@ impl ArchetypeColumn {
         let t_slice = unsafe {
             std::slice::from_raw_parts(
                 self.data.as_ptr() as *const T,
-                self.data.len() / self.component_size,
+                self.rows,
             )
         };
 
@@ -384,7 +772,7 @@ impl ArchetypeColumn {
         let t_slice = unsafe {
             std::slice::from_raw_parts_mut(
                 self.data.as_ptr() as *mut T,
-                self.data.len() / self.component_size,
+                self.rows,
             )
         };
 
@@ -392,32 +780,142 @@ impl ArchetypeColumn {
     }
 }
 
+impl Drop for ArchetypeColumn {
+    fn drop(&mut self) {
+        while self.rows > 0 {
+            unsafe { self.pop_unchecked() };
+        }
+        let size = self.info.layout.size();
+        if size != 0 && self.capacity != 0 {
+            let layout = Layout::from_size_align(
+                size * self.capacity,
+                self.info.layout.align(),
+            )
+            .unwrap();
+            unsafe { dealloc(self.data.as_ptr(), layout) };
+        }
+    }
+}
+
+/// Pointers to a borrowed column of components viewed as raw bytes.
+#[derive(Debug)]
+pub struct ColumnBytes {
+    data: *mut u8,
+    /// Size of a single component.
+    size: usize,
+    ticks: *mut ComponentTicks,
+    /// Borrow lock of the column.
+    pub access: Arc<RwLock<BorrowingStats>>,
+}
+
+impl ColumnBytes {
+    /// Gets the bytes of the component in `row`.
+    ///
+    /// # Safety
+    ///
+    /// `row` has to be in bounds and the column has to be still borrowed.
+    pub unsafe fn row(&self, row: usize) -> *mut [u8] {
+        let data = unsafe { self.data.add(row * self.size) };
+        std::ptr::slice_from_raw_parts_mut(data, self.size)
+    }
+
+    /// Gets the change ticks of the component in `row`.
+    ///
+    /// # Safety
+    ///
+    /// `row` has to be in bounds and the column has to be still borrowed.
+    pub unsafe fn ticks(&self, row: usize) -> *mut ComponentTicks {
+        unsafe { self.ticks.add(row) }
+    }
+}
+
+/// Pointers to a mutably borrowed column: components, their change ticks and the borrow lock.
+pub type ColumnMutAccess<T> =
+    (*mut [T], *mut [ComponentTicks], Arc<RwLock<BorrowingStats>>);
+
 /// Stores all data corresponding to entities containing a set of
 /// [`Components`][crate::ecs::world::component::Component], along with entity ids.
 #[derive(Debug)]
 pub struct Archetype {
-    columns: HashMap<TypeId, ArchetypeColumn>,
+    id: ArchetypeId,
+    columns: HashMap<ComponentId, ArchetypeColumn>,
+    /// Indices of archetypes entities move to when components are added.
+    add_edges: HashMap<Box<[ComponentId]>, usize>,
+    /// Indices of archetypes entities move to when components are removed.
+    remove_edges: HashMap<Box<[ComponentId]>, usize>,
     pub bundle_count: usize,
     pub entities: Vec<Entity>,
 }
 
 impl Archetype {
-    pub fn new() -> Archetype {
+    /// Creates a new empty archetype with a column for every type in `archetype_id`
+    /// that isn't a tag.
+    pub fn new(archetype_id: ArchetypeId) -> Archetype {
+        let columns = archetype_id
+            .component_types
+            .difference(&archetype_id.tag_types)
+            .map(|component_id| (*component_id, ArchetypeColumn::new()))
+            .collect();
         Archetype {
-            columns: HashMap::new(),
+            id: archetype_id,
+            columns,
+            add_edges: HashMap::new(),
+            remove_edges: HashMap::new(),
             bundle_count: 0,
             entities: Vec::new(),
         }
     }
 
-    /// Creates a new empty entity.
-    pub fn create_empty(&mut self, archetype_id: &ArchetypeId) {
-        for type_id in archetype_id.component_types.iter() {
-            self.columns.insert(*type_id, ArchetypeColumn::new());
+    /// Gets the [`ArchetypeId`] of `self`.
+    pub fn id(&self) -> &ArchetypeId { &self.id }
+
+    /// Gets the index of the archetype entities move to when components with
+    /// ids `component_ids` are added, if it was cached.
+    pub fn add_edge(&self, component_ids: &[ComponentId]) -> Option<usize> {
+        self.add_edges.get(component_ids).copied()
+    }
+
+    /// Caches the index of the archetype entities move to when components
+    /// with ids `component_ids` are added.
+    pub fn set_add_edge(
+        &mut self,
+        component_ids: &[ComponentId],
+        index: usize,
+    ) {
+        self.add_edges.insert(component_ids.into(), index);
+    }
+
+    /// Gets the index of the archetype entities move to when components with
+    /// ids `component_ids` are removed, if it was cached.
+    pub fn remove_edge(&self, component_ids: &[ComponentId]) -> Option<usize> {
+        self.remove_edges.get(component_ids).copied()
+    }
+
+    /// Caches the index of the archetype entities move to when components
+    /// with ids `component_ids` are removed.
+    pub fn set_remove_edge(
+        &mut self,
+        component_ids: &[ComponentId],
+        index: usize,
+    ) {
+        self.remove_edges.insert(component_ids.into(), index);
+    }
+
+    /// Gets a pointer to the component with id `component_id` in `row`.
+    /// Tags get a null pointer.
+    pub fn ptr(
+        &self,
+        component_id: ComponentId,
+        row: usize,
+    ) -> Option<*const u8> {
+        if self.id.is_tag(&component_id) {
+            return Some(std::ptr::null());
         }
+        let column = self.columns.get(&component_id)?;
+        Some(column.ptr_at(row) as *const u8)
     }
 
-    /// Adds a component to the last entity.
+    /// Adds a component inserted at `tick` to the last entity.
     ///
     /// # Errors
     ///
@@ -426,59 +924,103 @@ impl Archetype {
     pub fn add<T: Component>(
         &mut self,
         value: T,
+        tick: Tick,
     ) -> Result<(), ArchetypeError> {
-        let type_id = TypeId::of::<T>();
+        let component_id = ComponentId::of::<T>();
 
-        let column = match self.columns.get_mut(&type_id) {
+        let column = match self.columns.get_mut(&component_id) {
             Some(val) => val,
             None => return Err(ArchetypeError::TypeNotFound),
         };
 
-        column.push(value)
+        column.push(value, tick)
     }
 
     /// Adds raw component data to the last entity.
     ///
     /// # Errors
     ///
-    /// - If `self` doesn't store components of type `type_id`.
-    /// - If column storing components of type `type_id` is not writable.
+    /// - If `self` doesn't store components with id `component_id`.
+    /// - If column storing components with id `component_id` is not writable.
     pub fn add_raw(
         &mut self,
-        type_size: usize,
-        type_id: TypeId,
-        data: Vec<u8>,
+        component_id: ComponentId,
+        component: RawComponent,
     ) -> Result<(), ArchetypeError> {
-        let column = match self.columns.get_mut(&type_id) {
+        let column = match self.columns.get_mut(&component_id) {
             Some(val) => val,
             None => return Err(ArchetypeError::TypeNotFound),
         };
-        column.component_size = type_size;
 
-        column.push_raw(data)
+        column.push_raw(component)
+    }
+
+    /// Makes sure there is space for `additional` more entities.
+    /// Columns that never stored a component don't know the layout of their
+    /// components yet and are skipped.
+    pub fn reserve(&mut self, additional: usize) {
+        for column in self.columns.values_mut() {
+            if column.capacity != 0 {
+                column.reserve(additional);
+            }
+        }
+        self.entities.reserve(additional);
     }
 
-    /// Adds a new entity.
+    /// Checks if every column stores a component of every entity.
+    ///
+    /// # Errors
+    ///
+    /// - If some column is missing the component of the last entity.
+    pub fn check_columns(&self) -> Result<(), ArchetypeError> {
+        if self.columns.values().all(|c| c.rows == self.entities.len()) {
+            Ok(())
+        } else {
+            Err(ArchetypeError::TypeNotFound)
+        }
+    }
+
+    /// Adds a new entity and returns its row.
     ///
     /// # Errors
     ///
     /// - If any column is not writable.
-    pub fn new_entity(&mut self, id: u32) -> Result<Entity, ArchetypeError> {
+    pub fn new_entity(
+        &mut self,
+        entity: Entity,
+    ) -> Result<usize, ArchetypeError> {
         if !self.are_all_columns_mutable() {
             return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
-        self.entities.push(Entity::new(id));
-        Ok(Entity::new(id))
+        self.entities.push(entity);
+        Ok(self.entities.len() - 1)
     }
 
-    /// Adds a moved entity.
-    pub fn moved_entity(&mut self, entity: Entity) {
-        self.entities.push(entity);
+    /// Drops components with ids in `component_ids` of the entity in `row` and
+    /// moves the last components in their place. Ids without a column are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// - If `row` is out of bounds.
+    /// - If any of the columns is not writable.
+    pub fn replace_row(
+        &mut self,
+        row: usize,
+        component_ids: &[ComponentId],
+    ) -> Result<(), ArchetypeError> {
+        for component_id in component_ids {
+            if let Some(column) = self.columns.get_mut(component_id) {
+                column.swap_remove(row)?;
+            }
+        }
+        Ok(())
     }
 
-    /// Checks if `entity` is stored in `self`.
-    pub fn check_entity(&self, entity: Entity) -> bool {
-        self.entities.iter().find(|x| **x == entity).is_some()
+    /// Adds a moved entity and returns its row.
+    pub fn moved_entity(&mut self, entity: Entity) -> usize {
+        self.entities.push(entity);
+        self.entities.len() - 1
     }
 
     /// Check if all columns all mutable. Useful for spawns/deletes/cuts.
@@ -491,93 +1033,117 @@ impl Archetype {
         true
     }
 
-    /// Deletes `entity` and all of it's data.
+    /// Removes the entity stored in `row` by moving the last entity in its place.
+    /// Returns the moved entity, if there was one.
+    fn swap_remove_entity(&mut self, row: usize) -> Option<Entity> {
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+
+    /// Deletes the entity in `row` and all of it's data.
+    /// Returns the entity that was moved into `row` to fill the gap.
     ///
     /// # Errors
     ///
-    /// - If `self` doesn`t store `entity`.
+    /// - If `row` is out of bounds.
     /// - If any column is not writable.
-    pub fn delete_entity(
+    pub fn delete_row(
         &mut self,
-        entity: Entity,
-    ) -> Result<(), ArchetypeError> {
+        row: usize,
+    ) -> Result<Option<Entity>, ArchetypeError> {
         if !self.are_all_columns_mutable() {
             return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
 
-        if self.entities.is_empty() {
+        if row >= self.entities.len() {
             return Err(ArchetypeError::EntityNotFound);
         }
 
-        let last_pos = self.entities.len() - 1;
-        let entity_pos = match self
-            .entities
-            .iter()
-            .enumerate()
-            .find(|(_, x)| **x == entity)
-        {
-            Some((pos, _)) => pos,
-            None => return Err(ArchetypeError::EntityNotFound),
-        };
-
-        for (_, column) in self.columns.iter_mut() {
-            column.copy(last_pos, entity_pos)?;
-            column.pop()?;
+        for column in self.columns.values_mut() {
+            column.swap_remove(row)?;
         }
-        self.entities[entity_pos] = self.entities[last_pos];
-        self.entities.pop();
 
-        Ok(())
+        Ok(self.swap_remove_entity(row))
     }
 
-    /// Cuts `entity` and returns it's data.
+    /// Moves the components of the entity in `row` to the end of `other`.
+    /// Components `other` doesn't store are dropped. The entity itself has to
+    /// be added to `other` with [`Archetype::moved_entity`].
+    /// Returns the entity that was moved into `row` to fill the gap.
     ///
     /// # Errors
     ///
-    /// - If `self` doesn`t store `entity`.
-    /// - If any column is not writable.
-    pub fn cut_entity(
+    /// - If `row` is out of bounds.
+    /// - If any column of either archetype is not writable.
+    pub fn move_row(
         &mut self,
-        entity: Entity,
-    ) -> Result<(Entity, HashMap<TypeId, (usize, Vec<u8>)>), ArchetypeError>
-    {
-        if !self.are_all_columns_mutable() {
+        row: usize,
+        other: &mut Archetype,
+    ) -> Result<Option<Entity>, ArchetypeError> {
+        if !self.are_all_columns_mutable() || !other.are_all_columns_mutable() {
             return Err(ArchetypeError::ArchetypeColumnNotWritable);
         }
 
-        let last_pos = self.entities.len() - 1;
-        let entity_pos = match self
-            .entities
-            .iter()
-            .enumerate()
-            .find(|(_, x)| **x == entity)
-        {
-            Some((pos, _)) => pos,
-            None => return Err(ArchetypeError::EntityNotFound),
-        };
+        if row >= self.entities.len() {
+            return Err(ArchetypeError::EntityNotFound);
+        }
 
+        for (component_id, column) in self.columns.iter_mut() {
+            match other.columns.get_mut(component_id) {
+                Some(other_column) => column.swap_move(row, other_column)?,
+                None => column.swap_remove(row)?,
+            }
+        }
+
+        Ok(self.swap_remove_entity(row))
+    }
+
+    /// Checks if all components stored in `self` can be cloned.
+    pub fn is_cloneable(&self) -> bool {
+        self.columns.values().all(|column| column.is_cloneable())
+    }
+
+    /// Clones all cloneable components of the entity in `row`, marking the
+    /// clones as added at `tick`. Tags are always cloned.
+    /// Returns the [`ArchetypeId`] of the clone along with its components.
+    ///
+    /// # Errors
+    ///
+    /// - If `row` is out of bounds.
+    /// - If any column is not readable.
+    pub fn clone_row(
+        &self,
+        row: usize,
+        tick: Tick,
+    ) -> Result<
+        (ArchetypeId, HashMap<ComponentId, RawComponent>),
+        ArchetypeError,
+    > {
+        if row >= self.entities.len() {
+            return Err(ArchetypeError::EntityNotFound);
+        }
+
+        let mut skipped = Vec::new();
         let mut ret = HashMap::new();
-        for (type_id, column) in self.columns.iter_mut() {
-            let bytes = column.get_raw(entity_pos)?.to_vec();
-            column.copy(last_pos, entity_pos)?;
-            column.pop()?;
-            ret.insert(*type_id, (column.component_size, bytes));
+        for (component_id, column) in self.columns.iter() {
+            if column.is_cloneable() {
+                ret.insert(*component_id, column.get_raw(row, tick)?);
+            } else {
+                skipped.push(*component_id);
+            }
         }
-        let ret_entity = self.entities[entity_pos];
-        self.entities[entity_pos] = self.entities[last_pos];
-        self.entities.pop();
 
-        Ok((ret_entity, ret))
+        let id = self.id.remove_from(ArchetypeId::new(skipped)?)?;
+        Ok((id, ret))
     }
 
     /// Makes all columns the same lenght (deletes the excess). Used only after a failed spawn or
     /// component add/remove.
     pub fn trim_columns(&mut self) {
-        let desired_len =
-            self.columns.iter().map(|x| x.1.rows).min().unwrap_or(0);
+        let desired_len = self.bundle_count;
 
         for (_, column) in self.columns.iter_mut() {
-            while column.rows < desired_len {
+            while column.rows > desired_len {
                 unsafe { column.pop_unchecked() };
             }
         }
@@ -593,7 +1159,7 @@ impl Archetype {
     ///
     /// - If `self` doesn't store components of type `T`.
     fn get_column<T: Component>(&self) -> Option<&ArchetypeColumn> {
-        self.columns.get(&TypeId::of::<T>())
+        self.columns.get(&ComponentId::of::<T>())
     }
 
     /// Gets column data needed to query this archetype's components.
@@ -604,14 +1170,11 @@ impl Archetype {
     /// - If column storing `T` components is not readable.
     pub fn get<T: Component>(
         &self,
-    ) -> Result<(*const [T], Arc<RwLock<BorrowingStats>>, usize), ArchetypeError>
-    {
+    ) -> Result<(*const [T], Arc<RwLock<BorrowingStats>>), ArchetypeError> {
         let column =
             self.get_column::<T>().ok_or(ArchetypeError::TypeNotFound)?;
         let slice = column.get_slice::<T>()?;
-        column.borrow.write().unwrap().count += 1;
-        column.borrow.write().unwrap().access = ArchetypeColumnAccess::Read;
-        Ok((slice, column.borrow.clone(), slice.len()))
+        Ok((slice, column.lock()?))
     }
 
     /// Gets column data needed to mutably query this archetype's components.
@@ -622,13 +1185,74 @@ impl Archetype {
     /// - If column storing `T` components is not writable.
     pub fn get_mut<T: Component>(
         &self,
-    ) -> Result<(*mut [T], Arc<RwLock<BorrowingStats>>, usize), ArchetypeError>
-    {
+    ) -> Result<ColumnMutAccess<T>, ArchetypeError> {
         let column =
             self.get_column::<T>().ok_or(ArchetypeError::TypeNotFound)?;
         let slice = column.get_mut_slice::<T>()?;
-        column.borrow.write().unwrap().access = ArchetypeColumnAccess::None;
-        Ok((slice, column.borrow.clone(), slice.len()))
+        let ticks = std::ptr::slice_from_raw_parts_mut(
+            column.ticks.as_ptr() as *mut ComponentTicks,
+            column.ticks.len(),
+        );
+        Ok((slice, ticks, column.lock_mut()?))
+    }
+
+    /// Gets the components with id `component_id` as raw bytes, borrowing
+    /// their column immutably.
+    ///
+    /// # Errors
+    ///
+    /// - If `self` doesn't store components with id `component_id`.
+    /// - If the column is not readable.
+    pub fn get_bytes(
+        &self,
+        component_id: ComponentId,
+    ) -> Result<ColumnBytes, ArchetypeError> {
+        let column = self
+            .columns
+            .get(&component_id)
+            .ok_or(ArchetypeError::TypeNotFound)?;
+        Ok(ColumnBytes {
+            data: column.data.as_ptr(),
+            size: column.info.layout.size(),
+            ticks: column.ticks_at(0),
+            access: column.lock()?,
+        })
+    }
+
+    /// Gets the components with id `component_id` as raw bytes, borrowing
+    /// their column mutably.
+    ///
+    /// # Errors
+    ///
+    /// - If `self` doesn't store components with id `component_id`.
+    /// - If the column is not writable.
+    pub fn get_bytes_mut(
+        &self,
+        component_id: ComponentId,
+    ) -> Result<ColumnBytes, ArchetypeError> {
+        let column = self
+            .columns
+            .get(&component_id)
+            .ok_or(ArchetypeError::TypeNotFound)?;
+        Ok(ColumnBytes {
+            data: column.data.as_ptr(),
+            size: column.info.layout.size(),
+            ticks: column.ticks_at(0),
+            access: column.lock_mut()?,
+        })
+    }
+
+    /// Gets change ticks of all components of type `T`. Doesn't borrow the column.
+    ///
+    /// # Errors
+    ///
+    /// - If `self` doesn't store components of type `T`.
+    pub fn get_ticks<T: Component>(
+        &self,
+    ) -> Result<*const [ComponentTicks], ArchetypeError> {
+        let column =
+            self.get_column::<T>().ok_or(ArchetypeError::TypeNotFound)?;
+        Ok(column.ticks.as_slice())
     }
 
     /// Gets the number of entities stored in `self`.
@@ -0,0 +1,45 @@
+//! Module responsible for tracking when components were added or changed.
+
+/// A point in time measured in system runs.
+/// Comparisons between ticks handle wrapping around [`u32::MAX`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Tick(u32);
+
+impl Tick {
+    pub const fn new(tick: u32) -> Tick { Tick(tick) }
+
+    pub fn get(&self) -> u32 { self.0 }
+
+    /// Checks if `self` happened after `last_run`, as seen from `this_run`.
+    pub fn is_newer_than(&self, last_run: Tick, this_run: Tick) -> bool {
+        let since_last_run = this_run.0.wrapping_sub(last_run.0);
+        let since_self = this_run.0.wrapping_sub(self.0);
+        since_last_run > since_self
+    }
+}
+
+/// Stores when a single component was added and last changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: Tick,
+    pub changed: Tick,
+}
+
+impl ComponentTicks {
+    /// Creates ticks for a component inserted at `tick`.
+    pub fn new(tick: Tick) -> ComponentTicks {
+        ComponentTicks {
+            added: tick,
+            changed: tick,
+        }
+    }
+}
+
+/// Ticks used to decide what counts as a change for the currently running system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemTicks {
+    /// Tick at which the system ran previously.
+    pub last_run: Tick,
+    /// Tick of the current run.
+    pub this_run: Tick,
+}
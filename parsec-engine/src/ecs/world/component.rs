@@ -1,13 +1,130 @@
 //! Module responsible for defining components.
 
-/// Marks a type as a component. Used for simple data types.
-pub trait Component: Copy + Clone + Send + Sync + Sized + 'static {}
+use std::{alloc::Layout, any::TypeId};
+
+/// Marks a type as a component. Components are dropped along with the entity
+/// owning them or when they are removed from it.
+///
+/// The storage is chosen with `#[component(storage = "sparse")]` when deriving.
+/// Zero-sized table components that don't need dropping are tags, which are
+/// tracked by archetypes without storing any data.
+///
+/// Components can be cloned by [`World::clone_entity`][super::World::clone_entity]
+/// and [`Prefab`][super::prefab::Prefab]s if they derive with
+/// `#[component(clone)]`, which uses [`Clone`], or with
+/// `#[component(clone = "path::to::fn")]`, which uses a `fn(&Self) -> Self`.
+pub trait Component: Send + Sync + Sized + 'static {
+    /// Where components of this type are stored.
+    const STORAGE: StorageType = StorageType::Table;
+    /// Clones a component of this type. `None` if it can't be cloned.
+    const CLONE: Option<CloneFn> = None;
+}
 pub use parsec_engine_macros::Component;
 
+/// Identifies a kind of component. Components defined in Rust are identified
+/// by their [`TypeId`], components defined at runtime by the id they got when
+/// they were registered in a [`World`][super::World].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ComponentId {
+    Type(TypeId),
+    Dynamic(u32),
+}
+
+impl ComponentId {
+    /// Gets the id of components of type `T`.
+    pub fn of<T: 'static>() -> ComponentId {
+        ComponentId::Type(TypeId::of::<T>())
+    }
+}
+
+/// Describes a component defined at runtime, for example by a script.
+/// Such components are stored in archetype tables as raw bytes.
+#[derive(Debug, Clone)]
+pub struct ComponentDescriptor {
+    name: String,
+    layout: Layout,
+    drop: Option<unsafe fn(*mut u8)>,
+    serialize: Option<unsafe fn(*const u8) -> serde_json::Value>,
+}
+
+impl ComponentDescriptor {
+    /// Describes plain data components with the size and alignment of
+    /// `layout`. Any bytes of the right size are a valid component.
+    pub fn new(name: impl Into<String>, layout: Layout) -> ComponentDescriptor {
+        ComponentDescriptor {
+            name: name.into(),
+            layout,
+            drop: None,
+            serialize: None,
+        }
+    }
+
+    /// Sets the function dropping components in place.
+    ///
+    /// # Safety
+    ///
+    /// `drop` has to be safe to call on the bytes of every component spawned
+    /// with this descriptor, which then can't be arbitrary anymore.
+    pub unsafe fn with_drop(
+        mut self,
+        drop: unsafe fn(*mut u8),
+    ) -> ComponentDescriptor {
+        self.drop = Some(drop);
+        self
+    }
+
+    /// Sets the function converting components to JSON.
+    /// It's only called with pointers to components described by `self`.
+    pub fn with_serialize(
+        mut self,
+        serialize: unsafe fn(*const u8) -> serde_json::Value,
+    ) -> ComponentDescriptor {
+        self.serialize = Some(serialize);
+        self
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    pub fn layout(&self) -> Layout { self.layout }
+
+    pub fn drop_fn(&self) -> Option<unsafe fn(*mut u8)> { self.drop }
+
+    pub fn serialize_fn(
+        &self,
+    ) -> Option<unsafe fn(*const u8) -> serde_json::Value> {
+        self.serialize
+    }
+}
+
+/// Specifies how components of a type are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageType {
+    /// Stored in archetype columns. Fastest to iterate, but adding or
+    /// removing the component moves the entity to another archetype.
+    Table,
+    /// Stored in a sparse set outside of archetypes. Adding or removing the
+    /// component is cheap, which suits often toggled markers.
+    SparseSet,
+}
+
+/// Writes a clone of the component at the first pointer into the second one.
+pub type CloneFn = unsafe fn(*const u8, *mut u8);
+
+/// [`CloneFn`] of components implementing [`Clone`].
+///
+/// # Safety
+///
+/// `src` has to point to a valid `T` and `dst` has to be valid for writing a `T`.
+pub unsafe fn clone_component<T: Clone>(src: *const u8, dst: *mut u8) {
+    unsafe { dst.cast::<T>().write((*src.cast::<T>()).clone()) }
+}
+
 macro_rules! impl_component_for_primitives {
     ( $( $t:ty ),* ) => {
         $(
-            impl Component for $t {}
+            impl Component for $t {
+                const CLONE: Option<CloneFn> = Some(clone_component::<$t>);
+            }
         )*
     }
 }
@@ -29,5 +146,6 @@ impl_component_for_primitives!(
     f64,
     bool,
     char,
-    &'static str
+    &'static str,
+    String
 );
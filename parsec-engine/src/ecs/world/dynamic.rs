@@ -0,0 +1,528 @@
+//! Module responsible for components defined at runtime.
+
+use std::collections::HashMap;
+
+use crate::ecs::{
+    entity::Entity,
+    world::{
+        RawBundle, World, WorldError,
+        archetype::{
+            ArchetypeError, ArchetypeId, ColumnBytes, ComponentInfo,
+            RawComponent,
+        },
+        change_detection::Tick,
+        component::{ComponentDescriptor, ComponentId},
+        entity_ref::{ComponentRef, ComponentRefMut},
+        fetch::ComponentMut,
+    },
+};
+
+/// Descriptors of components registered at runtime, indexed by their id.
+#[derive(Debug, Default)]
+pub struct DynamicComponents {
+    descriptors: Vec<ComponentDescriptor>,
+    names: HashMap<String, ComponentId>,
+}
+
+impl DynamicComponents {
+    pub fn new() -> DynamicComponents { DynamicComponents::default() }
+
+    /// Gets the descriptor of the component with id `component_id`.
+    pub fn get(
+        &self,
+        component_id: ComponentId,
+    ) -> Option<&ComponentDescriptor> {
+        match component_id {
+            ComponentId::Dynamic(index) => self.descriptors.get(index as usize),
+            ComponentId::Type(_) => None,
+        }
+    }
+
+    /// Gets the info of columns storing components with id `component_id`.
+    ///
+    /// # Errors
+    ///
+    /// - If `component_id` isn't registered.
+    fn info(
+        &self,
+        component_id: ComponentId,
+    ) -> Result<ComponentInfo, ArchetypeError> {
+        let descriptor = self
+            .get(component_id)
+            .ok_or(ArchetypeError::ComponentNotRegistered)?;
+        Ok(ComponentInfo::dynamic(
+            descriptor.layout(),
+            descriptor.drop_fn(),
+        ))
+    }
+}
+
+impl World {
+    /// Registers a component defined at runtime and returns its id.
+    ///
+    /// # Errors
+    ///
+    /// - If a component with the same name is already registered.
+    pub fn register_component(
+        &mut self,
+        descriptor: ComponentDescriptor,
+    ) -> Result<ComponentId, WorldError> {
+        let components = &mut self.dynamic_components;
+        if components.names.contains_key(descriptor.name()) {
+            return Err(WorldError::RegisterComponentError {
+                kind: ArchetypeError::ComponentAlreadyRegistered,
+            });
+        }
+        let component_id =
+            ComponentId::Dynamic(components.descriptors.len() as u32);
+        components
+            .names
+            .insert(descriptor.name().to_string(), component_id);
+        components.descriptors.push(descriptor);
+        Ok(component_id)
+    }
+
+    /// Gets the id of the component registered under `name`.
+    pub fn component_id(&self, name: &str) -> Option<ComponentId> {
+        self.dynamic_components.names.get(name).copied()
+    }
+
+    /// Gets the descriptor of the component registered under `component_id`.
+    pub fn component_descriptor(
+        &self,
+        component_id: ComponentId,
+    ) -> Option<&ComponentDescriptor> {
+        self.dynamic_components.get(component_id)
+    }
+
+    /// Spawns a new entity with components defined at runtime, given as raw
+    /// bytes.
+    ///
+    /// # Errors
+    ///
+    /// - If any component isn't registered or `components` contains it twice.
+    /// - If any component has the wrong size.
+    /// - If the [archetype][super::archetype::Archetype] is already borrowed
+    ///   in some way.
+    pub fn spawn_dynamic(
+        &mut self,
+        components: &[(ComponentId, &[u8])],
+    ) -> Result<Entity, WorldError> {
+        let archetype_id =
+            ArchetypeId::new(components.iter().map(|(id, _)| *id).collect())
+                .map_err(|e| WorldError::SpawnError { kind: e })?;
+        let components = components
+            .iter()
+            .map(|(component_id, bytes)| {
+                let info = self.dynamic_components.info(*component_id)?;
+                let component =
+                    RawComponent::from_bytes(info, bytes, self.change_tick)?;
+                Ok((*component_id, component))
+            })
+            .collect::<Result<HashMap<_, _>, ArchetypeError>>()
+            .map_err(|e| WorldError::SpawnError { kind: e })?;
+        self.spawn_raw(RawBundle {
+            archetype_id,
+            components,
+            sparse: Vec::new(),
+        })
+        .map_err(|e| WorldError::SpawnError { kind: e })
+    }
+
+    /// Adds a component defined at runtime, given as raw bytes, to an
+    /// already existing entity.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist or already has the component.
+    /// - If the component isn't registered or `bytes` has the wrong size.
+    /// - If either the original [archetype][super::archetype::Archetype]
+    ///   containing `entity` or the destination one is already borrowed in
+    ///   some way.
+    pub fn insert_dynamic(
+        &mut self,
+        entity: Entity,
+        component_id: ComponentId,
+        bytes: &[u8],
+    ) -> Result<(), WorldError> {
+        let map_err = |e| WorldError::AddComponentError { kind: e };
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(map_err(ArchetypeError::EntityNotFound))?;
+        let info = self
+            .dynamic_components
+            .info(component_id)
+            .map_err(map_err)?;
+        let component = RawComponent::from_bytes(info, bytes, self.change_tick)
+            .map_err(map_err)?;
+        let new_index = self
+            .add_transition(location.archetype, &[component_id], || {
+                ArchetypeId::new(vec![component_id])
+            })
+            .map_err(map_err)?;
+
+        self.move_entity(entity, location, new_index)
+            .map_err(map_err)?;
+        self.archetypes[new_index]
+            .add_raw(component_id, component)
+            .map_err(map_err)
+    }
+
+    /// Removes a component defined at runtime from an entity.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist or doesn't have the component.
+    /// - If either the original [archetype][super::archetype::Archetype]
+    ///   containing `entity` or the destination one is already borrowed in
+    ///   some way.
+    pub fn remove_dynamic(
+        &mut self,
+        entity: Entity,
+        component_id: ComponentId,
+    ) -> Result<(), WorldError> {
+        let map_err = |e| WorldError::DeleteComponentError { kind: e };
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(map_err(ArchetypeError::EntityNotFound))?;
+        let new_index = self
+            .remove_transition(location.archetype, &[component_id], || {
+                ArchetypeId::new(vec![component_id])
+            })
+            .map_err(map_err)?;
+
+        self.move_entity(entity, location, new_index)
+            .map_err(map_err)
+    }
+
+    /// Checks if `entity` has a table component with id `component_id`.
+    pub fn has_dynamic(
+        &self,
+        entity: Entity,
+        component_id: ComponentId,
+    ) -> bool {
+        self.entities.location(entity).is_some_and(|location| {
+            self.archetypes[location.archetype]
+                .id()
+                .contains_single(&component_id)
+        })
+    }
+
+    /// Gets the bytes of the table component with id `component_id`
+    /// belonging to `entity`.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist or doesn't have the component.
+    /// - If the column storing the component is borrowed mutably.
+    pub fn get_dynamic(
+        &self,
+        entity: Entity,
+        component_id: ComponentId,
+    ) -> Result<ComponentRef<'_, [u8]>, WorldError> {
+        let location = self.entities.location(entity).ok_or(
+            WorldError::GetComponentError {
+                kind: ArchetypeError::EntityNotFound,
+            },
+        )?;
+        let column = self.archetypes[location.archetype]
+            .get_bytes(component_id)
+            .map_err(|e| WorldError::GetComponentError { kind: e })?;
+        let value = unsafe { &*column.row(location.row) };
+        Ok(ComponentRef::new(value, column.access))
+    }
+
+    /// Gets the bytes of the table component with id `component_id`
+    /// belonging to `entity` mutably.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist or doesn't have the component.
+    /// - If the column storing the component is already borrowed.
+    pub fn get_dynamic_mut(
+        &self,
+        entity: Entity,
+        component_id: ComponentId,
+    ) -> Result<ComponentRefMut<'_, [u8]>, WorldError> {
+        let location = self.entities.location(entity).ok_or(
+            WorldError::GetComponentError {
+                kind: ArchetypeError::EntityNotFound,
+            },
+        )?;
+        let column = self.archetypes[location.archetype]
+            .get_bytes_mut(component_id)
+            .map_err(|e| WorldError::GetComponentError { kind: e })?;
+        let value = unsafe {
+            ComponentMut::new(
+                &mut *column.row(location.row),
+                &mut *column.ticks(location.row),
+                self.change_tick,
+            )
+        };
+        Ok(ComponentRefMut::new(value, column.access))
+    }
+
+    /// Converts the component with id `component_id` belonging to `entity` to
+    /// JSON. Returns `None` if its descriptor has no serialize function.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist or doesn't have the component.
+    /// - If the column storing the component is borrowed mutably.
+    pub fn serialize_dynamic(
+        &self,
+        entity: Entity,
+        component_id: ComponentId,
+    ) -> Result<Option<serde_json::Value>, WorldError> {
+        let Some(serialize) = self
+            .component_descriptor(component_id)
+            .and_then(|descriptor| descriptor.serialize_fn())
+        else {
+            return Ok(None);
+        };
+        let bytes = self.get_dynamic(entity, component_id)?;
+        Ok(Some(unsafe { serialize(bytes.as_ptr()) }))
+    }
+
+    /// Creates a query for all entities having table components with ids
+    /// `component_ids`, viewed as raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// - If `component_ids` contains an id twice.
+    /// - If any matched column is borrowed mutably.
+    pub fn query_dynamic(
+        &self,
+        component_ids: &[ComponentId],
+    ) -> Result<DynamicQuery, WorldError> {
+        let columns = DynamicColumns::new(self, component_ids, false)
+            .map_err(|e| WorldError::GetComponentError { kind: e })?;
+        Ok(DynamicQuery { columns })
+    }
+
+    /// Creates a query for all entities having table components with ids
+    /// `component_ids`, viewed as mutable raw bytes. Returned components are
+    /// marked as changed.
+    ///
+    /// # Errors
+    ///
+    /// - If `component_ids` contains an id twice.
+    /// - If any matched column is already borrowed.
+    pub fn query_dynamic_mut(
+        &self,
+        component_ids: &[ComponentId],
+    ) -> Result<DynamicQueryMut, WorldError> {
+        let columns = DynamicColumns::new(self, component_ids, true)
+            .map_err(|e| WorldError::GetComponentError { kind: e })?;
+        Ok(DynamicQueryMut {
+            columns,
+            change_tick: self.change_tick,
+        })
+    }
+}
+
+/// Columns borrowed by a dynamic query. Released when dropped.
+struct DynamicColumns {
+    /// Columns of every matched archetype, in the order of the queried ids.
+    columns: Vec<Vec<ColumnBytes>>,
+    entities: Vec<Vec<Entity>>,
+}
+
+impl DynamicColumns {
+    fn new(
+        world: &World,
+        component_ids: &[ComponentId],
+        mutable: bool,
+    ) -> Result<DynamicColumns, ArchetypeError> {
+        let query_id = ArchetypeId::new(component_ids.to_vec())?;
+        let mut ret = DynamicColumns {
+            columns: Vec::new(),
+            entities: Vec::new(),
+        };
+        for archetype in world.archetypes.iter() {
+            if !archetype.id().contains(&query_id) {
+                continue;
+            }
+            let mut columns = Vec::new();
+            for component_id in component_ids {
+                let column = if mutable {
+                    archetype.get_bytes_mut(*component_id)
+                } else {
+                    archetype.get_bytes(*component_id)
+                };
+                match column {
+                    Ok(column) => columns.push(column),
+                    Err(e) => {
+                        // Pushed columns are released along with `ret`.
+                        ret.columns.push(columns);
+                        return Err(e);
+                    },
+                }
+            }
+            ret.columns.push(columns);
+            ret.entities.push(archetype.entities.clone());
+        }
+        Ok(ret)
+    }
+
+    /// Iterates over all matched rows.
+    fn rows(&self) -> impl Iterator<Item = (Entity, &[ColumnBytes], usize)> {
+        self.entities.iter().zip(self.columns.iter()).flat_map(
+            |(entities, columns)| {
+                entities
+                    .iter()
+                    .enumerate()
+                    .map(|(row, entity)| (*entity, columns.as_slice(), row))
+            },
+        )
+    }
+}
+
+impl Drop for DynamicColumns {
+    fn drop(&mut self) {
+        for column in self.columns.iter().flatten() {
+            column.access.write().unwrap().release_lock();
+        }
+    }
+}
+
+/// Read-only query over components identified by [`ComponentId`]s, created
+/// with [`World::query_dynamic`].
+pub struct DynamicQuery {
+    columns: DynamicColumns,
+}
+
+impl DynamicQuery {
+    /// Iterates over matched entities and the bytes of their components, in
+    /// the order of the queried ids.
+    pub fn iter(&mut self) -> impl Iterator<Item = (Entity, Vec<&[u8]>)> {
+        self.columns.rows().map(|(entity, columns, row)| {
+            let components = columns
+                .iter()
+                .map(|column| unsafe { &*column.row(row) })
+                .collect();
+            (entity, components)
+        })
+    }
+}
+
+/// Mutable query over components identified by [`ComponentId`]s, created
+/// with [`World::query_dynamic_mut`].
+pub struct DynamicQueryMut {
+    columns: DynamicColumns,
+    change_tick: Tick,
+}
+
+impl DynamicQueryMut {
+    /// Iterates over matched entities and the bytes of their components, in
+    /// the order of the queried ids. Returned components are marked as changed.
+    pub fn iter(&mut self) -> impl Iterator<Item = (Entity, Vec<&mut [u8]>)> {
+        let change_tick = self.change_tick;
+        self.columns.rows().map(move |(entity, columns, row)| {
+            let components = columns
+                .iter()
+                .map(|column| unsafe {
+                    (*column.ticks(row)).changed = change_tick;
+                    &mut *column.row(row)
+                })
+                .collect();
+            (entity, components)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::Layout,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    unsafe fn serialize_health(ptr: *const u8) -> serde_json::Value {
+        serde_json::json!(unsafe { ptr.cast::<u32>().read() })
+    }
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe fn count_drop(_: *mut u8) { DROPPED.fetch_add(1, Ordering::SeqCst); }
+
+    #[test]
+    fn dynamic_components() {
+        let mut world = World::new();
+        let health = world
+            .register_component(
+                ComponentDescriptor::new("Health", Layout::new::<u32>())
+                    .with_serialize(serialize_health),
+            )
+            .unwrap();
+        let name = world
+            .register_component(ComponentDescriptor::new(
+                "Name",
+                Layout::new::<[u8; 4]>(),
+            ))
+            .unwrap();
+        let token = unsafe {
+            ComponentDescriptor::new("Token", Layout::new::<u8>())
+                .with_drop(count_drop)
+        };
+        let token = world.register_component(token).unwrap();
+        assert!(
+            world
+                .register_component(ComponentDescriptor::new(
+                    "Health",
+                    Layout::new::<u8>()
+                ))
+                .is_err()
+        );
+        assert_eq!(world.component_id("Health"), Some(health));
+
+        let a = world
+            .spawn_dynamic(&[(health, &100_u32.to_ne_bytes()), (name, b"ship")])
+            .unwrap();
+        let b = world.spawn(1_u64).unwrap();
+        world.insert_dynamic(b, health, &50_u32.to_ne_bytes()).unwrap();
+        assert!(world.spawn_dynamic(&[(health, &[0])]).is_err());
+        assert!(
+            world
+                .insert_dynamic(b, ComponentId::of::<u32>(), &[0; 4])
+                .is_err()
+        );
+
+        let mut query = world.query_dynamic_mut(&[health]).unwrap();
+        for (_, mut components) in query.iter() {
+            let bytes = (*components[0]).try_into().unwrap();
+            let value = u32::from_ne_bytes(bytes);
+            components[0].copy_from_slice(&(value - 10).to_ne_bytes());
+        }
+        drop(query);
+        assert_eq!(&*world.get_dynamic(a, name).unwrap(), b"ship");
+        assert_eq!(
+            world.serialize_dynamic(b, health).unwrap(),
+            Some(serde_json::json!(40))
+        );
+        assert_eq!(world.serialize_dynamic(a, name).unwrap(), None);
+        assert_eq!(*world.get::<u64>(b).unwrap(), 1);
+
+        let mut query = world.query_dynamic(&[health, name]).unwrap();
+        let found = query
+            .iter()
+            .map(|(entity, components)| (entity, components[1].to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(a, b"ship".to_vec())]);
+        drop(query);
+
+        world.remove_dynamic(b, health).unwrap();
+        assert!(!world.has_dynamic(b, health));
+        assert!(world.has::<u64>(b));
+
+        let c = world.spawn_dynamic(&[(token, &[1])]).unwrap();
+        world.insert_dynamic(c, health, &[0; 4]).unwrap();
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+        world.delete(c).unwrap();
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+    }
+}
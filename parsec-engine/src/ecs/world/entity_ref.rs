@@ -0,0 +1,170 @@
+//! Module responsible for accessing components of a single entity.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, RwLock},
+};
+
+use crate::ecs::{
+    entity::Entity,
+    world::{
+        World, WorldError, add_component::AddComponent,
+        archetype::BorrowingStats, change_detection::ComponentTicks,
+        component::Component, fetch::ComponentMut,
+        remove_component::RemoveComponent,
+    },
+};
+
+/// Immutable reference to a single component.
+/// The column storing the component stays borrowed until this is dropped.
+pub struct ComponentRef<'a, T: ?Sized> {
+    value: &'a T,
+    access: Arc<RwLock<BorrowingStats>>,
+}
+
+impl<'a, T: ?Sized> ComponentRef<'a, T> {
+    pub(crate) fn new(
+        value: &'a T,
+        access: Arc<RwLock<BorrowingStats>>,
+    ) -> ComponentRef<'a, T> {
+        ComponentRef { value, access }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for ComponentRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target { self.value }
+}
+
+impl<'a, T: ?Sized> Drop for ComponentRef<'a, T> {
+    fn drop(&mut self) { self.access.write().unwrap().release_lock(); }
+}
+
+/// Mutable reference to a single component that marks it as changed when
+/// dereferenced mutably. The column storing the component stays borrowed until
+/// this is dropped.
+pub struct ComponentRefMut<'a, T: ?Sized> {
+    value: ComponentMut<'a, T>,
+    access: Arc<RwLock<BorrowingStats>>,
+}
+
+impl<'a, T: ?Sized> ComponentRefMut<'a, T> {
+    pub(crate) fn new(
+        value: ComponentMut<'a, T>,
+        access: Arc<RwLock<BorrowingStats>>,
+    ) -> ComponentRefMut<'a, T> {
+        ComponentRefMut { value, access }
+    }
+
+    /// Gets when the component was added and last changed.
+    pub fn ticks(&self) -> ComponentTicks { self.value.ticks() }
+}
+
+impl<'a, T: ?Sized> Deref for ComponentRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target { &self.value }
+}
+
+impl<'a, T: ?Sized> DerefMut for ComponentRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.value }
+}
+
+impl<'a, T: ?Sized> Drop for ComponentRefMut<'a, T> {
+    fn drop(&mut self) { self.access.write().unwrap().release_lock(); }
+}
+
+/// Read-only view of a single entity inside a [`World`].
+#[derive(Clone, Copy)]
+pub struct EntityRef<'a> {
+    world: &'a World,
+    entity: Entity,
+}
+
+impl<'a> EntityRef<'a> {
+    pub(crate) fn new(world: &'a World, entity: Entity) -> EntityRef<'a> {
+        EntityRef { world, entity }
+    }
+
+    /// Gets the viewed entity.
+    pub fn id(&self) -> Entity { self.entity }
+
+    /// Checks if the entity has a component of type `T`.
+    pub fn has<T: Component>(&self) -> bool { self.world.has::<T>(self.entity) }
+
+    /// Gets the component of type `T`.
+    ///
+    /// # Errors
+    ///
+    /// - If the entity doesn't have a component of type `T`.
+    /// - If the column storing `T` components is borrowed mutably.
+    pub fn get<T: Component>(&self) -> Result<ComponentRef<'a, T>, WorldError> {
+        self.world.get::<T>(self.entity)
+    }
+}
+
+/// Mutable view of a single entity inside a [`World`].
+pub struct EntityMut<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl<'a> EntityMut<'a> {
+    pub(crate) fn new(world: &'a mut World, entity: Entity) -> EntityMut<'a> {
+        EntityMut { world, entity }
+    }
+
+    /// Gets the viewed entity.
+    pub fn id(&self) -> Entity { self.entity }
+
+    /// Checks if the entity has a component of type `T`.
+    pub fn has<T: Component>(&self) -> bool { self.world.has::<T>(self.entity) }
+
+    /// Gets the component of type `T`.
+    ///
+    /// # Errors
+    ///
+    /// - If the entity doesn't have a component of type `T`.
+    /// - If the column storing `T` components is borrowed mutably.
+    pub fn get<T: Component>(&self) -> Result<ComponentRef<'_, T>, WorldError> {
+        self.world.get::<T>(self.entity)
+    }
+
+    /// Gets the component of type `T` mutably.
+    ///
+    /// # Errors
+    ///
+    /// - If the entity doesn't have a component of type `T`.
+    /// - If the column storing `T` components is already borrowed.
+    pub fn get_mut<T: Component>(
+        &mut self,
+    ) -> Result<ComponentRefMut<'_, T>, WorldError> {
+        self.world.get_mut::<T>(self.entity)
+    }
+
+    /// Adds components to the entity.
+    /// See [`World::add_components`] for possible errors.
+    pub fn add_components<T: AddComponent>(
+        &mut self,
+        bundle_extension: T,
+    ) -> Result<&mut Self, WorldError> {
+        self.world.add_components(self.entity, bundle_extension)?;
+        Ok(self)
+    }
+
+    /// Removes components from the entity.
+    /// See [`World::remove_components`] for possible errors.
+    pub fn remove_components<T: RemoveComponent>(
+        &mut self,
+    ) -> Result<&mut Self, WorldError> {
+        self.world.remove_components::<T>(self.entity)?;
+        Ok(self)
+    }
+
+    /// Deletes the entity.
+    /// See [`World::delete`] for possible errors.
+    pub fn delete(self) -> Result<(), WorldError> {
+        self.world.delete(self.entity)
+    }
+}
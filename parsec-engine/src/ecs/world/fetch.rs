@@ -1,16 +1,23 @@
 //! Module responsible for querying entities.
 
 use std::{
-    any::TypeId,
     marker::PhantomData,
+    ops::{Deref, DerefMut},
     sync::{Arc, RwLock},
 };
 
 use parsec_engine_macros::{impl_fetch, multiple_tuples};
 
-use crate::ecs::world::{
-    archetype::{Archetype, ArchetypeError, ArchetypeId, BorrowingStats},
-    component::Component,
+use crate::ecs::{
+    system::access::SystemAccess,
+    world::{
+        archetype::{
+            Archetype, ArchetypeError, ArchetypeId, BorrowingStats, is_tag,
+        },
+        change_detection::{ComponentTicks, SystemTicks, Tick},
+        component::{Component, ComponentId},
+        sparse_set::{SparseSets, SparseView, is_sparse},
+    },
 };
 
 /// Represents a type that can be used to query entities from [`World`][`crate::ecs::world::World`].
@@ -19,27 +26,64 @@ use crate::ecs::world::{
 pub trait Fetch: Sized {
     /// Type of elements returned when iterating over entites with a [`QueryIter`][`crate::ecs::world::query::QueryIter`].
     type Item<'a>
+    where
+        Self: 'a;
+    /// Type of elements returned when iterating over whole archetypes with
+    /// [`Query::iter_chunks`][`crate::ecs::world::query::Query::iter_chunks`].
+    type Chunk<'a>
     where
         Self: 'a;
     /// Type that stores borrowing info
     type State: Clone;
-    fn archetype_id() -> Result<ArchetypeId, ArchetypeError>;
+    /// Checks if entities stored in an archetype with `archetype_id` can be fetched.
+    /// Sparse components aren't part of archetypes, so they are checked per row
+    /// with [`Fetch::contains_row`].
+    fn matches(archetype_id: &ArchetypeId) -> bool;
+    /// Adds the components read or written by this fetch to `access`.
+    fn access(access: &mut SystemAccess);
     /// Creates the state used to later get specific entities.
-    fn prepare(archetype: &Archetype) -> Result<Self::State, ArchetypeError>;
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError>;
+    /// Checks if the entity in `row` has all fetched components.
+    fn contains_row(state: &Self::State, row: usize) -> bool;
     /// Releases the lock on borrowed types.
     fn release(state: Self::State) -> Result<(), ArchetypeError>;
     /// Gets n-th element from the state.
     fn get<'a>(state: Self::State, row: usize) -> Self::Item<'a>;
-    /// Gets the amount of entities stored in the state.
-    fn len(state: &Self::State) -> usize;
+    /// Gets the first `len` elements from the state as slices.
+    /// Returns [`None`] for sparse components, which aren't stored
+    /// contiguously.
+    fn get_chunk<'a>(state: Self::State, len: usize)
+    -> Option<Self::Chunk<'a>>;
 }
 
 /// Stores info about a non-mutable fetch.
-#[derive(Debug, Clone)]
-pub struct FetchState<T> {
-    ptr: *const [T],
-    len: usize,
-    access: Arc<RwLock<BorrowingStats>>,
+#[derive(Debug)]
+pub enum FetchState<T> {
+    Table {
+        ptr: *const [T],
+        access: Arc<RwLock<BorrowingStats>>,
+    },
+    /// Sparse sets are locked by the query itself.
+    Sparse(SparseView),
+    /// Tags have no column to borrow.
+    Tag,
+}
+
+impl<T> Clone for FetchState<T> {
+    fn clone(&self) -> Self {
+        match self {
+            FetchState::Table { ptr, access } => FetchState::Table {
+                ptr: *ptr,
+                access: access.clone(),
+            },
+            FetchState::Sparse(view) => FetchState::Sparse(view.clone()),
+            FetchState::Tag => FetchState::Tag,
+        }
+    }
 }
 
 impl<T: Component> Fetch for T {
@@ -47,74 +91,394 @@ impl<T: Component> Fetch for T {
         = &'a T
     where
         Self: 'a;
+    type Chunk<'a>
+        = &'a [T]
+    where
+        Self: 'a;
     type State = FetchState<T>;
 
-    fn archetype_id() -> Result<ArchetypeId, ArchetypeError> {
-        ArchetypeId::new(vec![TypeId::of::<T>()])
+    fn matches(archetype_id: &ArchetypeId) -> bool {
+        is_sparse::<T>()
+            || archetype_id.contains_single(&ComponentId::of::<T>())
+    }
+
+    fn access(access: &mut SystemAccess) { access.read_component::<T>(); }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        _ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        if is_sparse::<T>() {
+            let view = sparse_sets.view(ComponentId::of::<T>(), archetype);
+            return Ok(FetchState::Sparse(view));
+        }
+        if is_tag::<T>() {
+            return Ok(FetchState::Tag);
+        }
+        let (ptr, access) = archetype.get()?;
+        Ok(FetchState::Table { ptr, access })
     }
 
-    fn prepare(archetype: &Archetype) -> Result<Self::State, ArchetypeError> {
-        let (ptr, access, len) = archetype.get()?;
-        Ok(FetchState { ptr, access, len })
+    fn contains_row(state: &Self::State, row: usize) -> bool {
+        match state {
+            FetchState::Table { .. } | FetchState::Tag => true,
+            FetchState::Sparse(view) => view.contains(row),
+        }
     }
 
     fn release(state: Self::State) -> Result<(), ArchetypeError> {
-        let mut lock = state.access.write().unwrap();
-        lock.release_lock();
+        if let FetchState::Table { access, .. } = state {
+            access.write().unwrap().release_lock();
+        }
         Ok(())
     }
 
     fn get<'a>(state: Self::State, row: usize) -> Self::Item<'a> {
-        let ptr = state.ptr;
-        let array = unsafe { &*ptr };
-        &array[row]
+        match state {
+            FetchState::Table { ptr, .. } => {
+                let array = unsafe { &*ptr };
+                &array[row]
+            },
+            FetchState::Sparse(view) => unsafe { &*view.get::<T>(row).0 },
+            // SAFETY: tags are zero-sized, so any aligned pointer is valid.
+            FetchState::Tag => unsafe {
+                std::ptr::NonNull::dangling().as_ref()
+            },
+        }
     }
 
-    fn len(state: &Self::State) -> usize { state.len }
+    fn get_chunk<'a>(state: Self::State, len: usize) -> Option<&'a [T]> {
+        match state {
+            FetchState::Table { ptr, .. } => {
+                let array = unsafe { &*ptr };
+                Some(&array[..len])
+            },
+            FetchState::Sparse(_) => None,
+            // SAFETY: tags are zero-sized, so any aligned pointer is valid.
+            FetchState::Tag => Some(unsafe {
+                std::slice::from_raw_parts(
+                    std::ptr::NonNull::dangling().as_ptr(),
+                    len,
+                )
+            }),
+        }
+    }
 }
 
 /// Marks a type to be borrowed mutably inside a [`Query`][crate::ecs::world::query::Query].
+/// Tags can't be borrowed mutably, since they have no data.
 pub struct Mut<T> {
     _marker: PhantomData<T>,
 }
 
 /// Stores info about a mutable fetch.
-#[derive(Debug, Clone)]
-pub struct FetchMutState<T> {
-    ptr: *mut [T],
-    len: usize,
-    access: Arc<RwLock<BorrowingStats>>,
+#[derive(Debug)]
+pub enum FetchMutState<T> {
+    Table {
+        ptr: *mut [T],
+        ticks: *mut [ComponentTicks],
+        change_tick: Tick,
+        access: Arc<RwLock<BorrowingStats>>,
+    },
+    /// Sparse sets are locked by the query itself.
+    Sparse {
+        view: SparseView,
+        change_tick: Tick,
+    },
+}
+
+impl<T> Clone for FetchMutState<T> {
+    fn clone(&self) -> Self {
+        match self {
+            FetchMutState::Table {
+                ptr,
+                ticks,
+                change_tick,
+                access,
+            } => FetchMutState::Table {
+                ptr: *ptr,
+                ticks: *ticks,
+                change_tick: *change_tick,
+                access: access.clone(),
+            },
+            FetchMutState::Sparse { view, change_tick } => {
+                FetchMutState::Sparse {
+                    view: view.clone(),
+                    change_tick: *change_tick,
+                }
+            },
+        }
+    }
+}
+
+/// Mutable slice of components that marks all of them as changed when
+/// dereferenced mutably.
+pub struct ComponentSliceMut<'a, T> {
+    values: &'a mut [T],
+    ticks: &'a mut [ComponentTicks],
+    change_tick: Tick,
+}
+
+impl<'a, T> ComponentSliceMut<'a, T> {
+    pub fn new(
+        values: &'a mut [T],
+        ticks: &'a mut [ComponentTicks],
+        change_tick: Tick,
+    ) -> ComponentSliceMut<'a, T> {
+        ComponentSliceMut {
+            values,
+            ticks,
+            change_tick,
+        }
+    }
+
+    /// Gets when the components were added and last changed.
+    pub fn ticks(&self) -> &[ComponentTicks] { self.ticks }
+}
+
+impl<'a, T> Deref for ComponentSliceMut<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target { self.values }
+}
+
+impl<'a, T> DerefMut for ComponentSliceMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        for ticks in self.ticks.iter_mut() {
+            ticks.changed = self.change_tick;
+        }
+        self.values
+    }
+}
+
+/// Mutable reference to a component that marks it as changed when dereferenced mutably.
+pub struct ComponentMut<'a, T: ?Sized> {
+    value: &'a mut T,
+    ticks: &'a mut ComponentTicks,
+    change_tick: Tick,
+}
+
+impl<'a, T: ?Sized> ComponentMut<'a, T> {
+    pub fn new(
+        value: &'a mut T,
+        ticks: &'a mut ComponentTicks,
+        change_tick: Tick,
+    ) -> ComponentMut<'a, T> {
+        ComponentMut {
+            value,
+            ticks,
+            change_tick,
+        }
+    }
+
+    /// Gets when the component was added and last changed.
+    pub fn ticks(&self) -> ComponentTicks { *self.ticks }
+}
+
+impl<'a, T: ?Sized> Deref for ComponentMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target { self.value }
+}
+
+impl<'a, T: ?Sized> DerefMut for ComponentMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.ticks.changed = self.change_tick;
+        self.value
+    }
 }
 
 impl<T: Component> Fetch for Mut<T> {
     type Item<'a>
-        = &'a mut T
+        = ComponentMut<'a, T>
+    where
+        Self: 'a;
+    type Chunk<'a>
+        = ComponentSliceMut<'a, T>
     where
         Self: 'a;
     type State = FetchMutState<T>;
 
-    fn archetype_id() -> Result<ArchetypeId, ArchetypeError> {
-        ArchetypeId::new(vec![TypeId::of::<T>()])
+    fn matches(archetype_id: &ArchetypeId) -> bool {
+        is_sparse::<T>()
+            || archetype_id.contains_single(&ComponentId::of::<T>())
+    }
+
+    fn access(access: &mut SystemAccess) { access.write_component::<T>(); }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        if is_sparse::<T>() {
+            return Ok(FetchMutState::Sparse {
+                view: sparse_sets.view(ComponentId::of::<T>(), archetype),
+                change_tick: ticks.this_run,
+            });
+        }
+        if is_tag::<T>() {
+            return Err(ArchetypeError::TagHasNoData);
+        }
+        let (ptr, component_ticks, access) = archetype.get_mut()?;
+        Ok(FetchMutState::Table {
+            ptr,
+            ticks: component_ticks,
+            change_tick: ticks.this_run,
+            access,
+        })
     }
 
-    fn prepare(archetype: &Archetype) -> Result<Self::State, ArchetypeError> {
-        let (ptr, access, len) = archetype.get_mut()?;
-        Ok(FetchMutState { ptr, access, len })
+    fn contains_row(state: &Self::State, row: usize) -> bool {
+        match state {
+            FetchMutState::Table { .. } => true,
+            FetchMutState::Sparse { view, .. } => view.contains(row),
+        }
     }
 
     fn release(state: Self::State) -> Result<(), ArchetypeError> {
-        let mut lock = state.access.write().unwrap();
-        lock.release_lock();
+        if let FetchMutState::Table { access, .. } = state {
+            access.write().unwrap().release_lock();
+        }
         Ok(())
     }
 
     fn get<'a>(state: Self::State, row: usize) -> Self::Item<'a> {
-        let ptr = state.ptr;
-        let array = unsafe { &mut *ptr };
-        &mut array[row]
+        match state {
+            FetchMutState::Table {
+                ptr,
+                ticks,
+                change_tick,
+                ..
+            } => {
+                assert!(row < ptr.len());
+                // Rows are accessed through raw pointers, so that threads of
+                // `Query::par_for_each` never borrow the same rows.
+                unsafe {
+                    ComponentMut::new(
+                        &mut *(ptr as *mut T).add(row),
+                        &mut *(ticks as *mut ComponentTicks).add(row),
+                        change_tick,
+                    )
+                }
+            },
+            FetchMutState::Sparse { view, change_tick } => {
+                let (value, ticks) = unsafe { view.get::<T>(row) };
+                unsafe {
+                    ComponentMut::new(&mut *value, &mut *ticks, change_tick)
+                }
+            },
+        }
+    }
+
+    fn get_chunk<'a>(
+        state: Self::State,
+        len: usize,
+    ) -> Option<Self::Chunk<'a>> {
+        match state {
+            FetchMutState::Table {
+                ptr,
+                ticks,
+                change_tick,
+                ..
+            } => {
+                let array = unsafe { &mut *ptr };
+                let ticks = unsafe { &mut *ticks };
+                Some(ComponentSliceMut::new(
+                    &mut array[..len],
+                    &mut ticks[..len],
+                    change_tick,
+                ))
+            },
+            FetchMutState::Sparse { .. } => None,
+        }
+    }
+}
+
+/// Fetches `T` if the entity has it and [`None`] otherwise.
+impl<T: Fetch> Fetch for Option<T> {
+    type Item<'a>
+        = Option<T::Item<'a>>
+    where
+        Self: 'a;
+    type Chunk<'a>
+        = Option<T::Chunk<'a>>
+    where
+        Self: 'a;
+    type State = Option<T::State>;
+
+    fn matches(_archetype_id: &ArchetypeId) -> bool { true }
+
+    fn access(access: &mut SystemAccess) { T::access(access); }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        if !T::matches(archetype.id()) {
+            return Ok(None);
+        }
+        T::prepare(archetype, sparse_sets, ticks).map(Some)
+    }
+
+    fn contains_row(_state: &Self::State, _row: usize) -> bool { true }
+
+    fn release(state: Self::State) -> Result<(), ArchetypeError> {
+        match state {
+            Some(state) => T::release(state),
+            None => Ok(()),
+        }
+    }
+
+    fn get<'a>(state: Self::State, row: usize) -> Self::Item<'a> {
+        state
+            .filter(|state| T::contains_row(state, row))
+            .map(|state| T::get(state, row))
+    }
+
+    fn get_chunk<'a>(
+        state: Self::State,
+        len: usize,
+    ) -> Option<Self::Chunk<'a>> {
+        match state {
+            Some(state) => T::get_chunk(state, len).map(Some),
+            None => Some(None),
+        }
     }
+}
+
+/// Fetches nothing. Useful when only entity ids are needed.
+impl Fetch for () {
+    type Item<'a> = ();
+    type Chunk<'a> = ();
+    type State = ();
 
-    fn len(state: &Self::State) -> usize { state.len }
+    fn matches(_archetype_id: &ArchetypeId) -> bool { true }
+
+    fn access(_access: &mut SystemAccess) {}
+
+    fn prepare(
+        _archetype: &Archetype,
+        _sparse_sets: &SparseSets,
+        _ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        Ok(())
+    }
+
+    fn contains_row(_state: &Self::State, _row: usize) -> bool { true }
+
+    fn release(_state: Self::State) -> Result<(), ArchetypeError> { Ok(()) }
+
+    fn get<'a>(_state: Self::State, _row: usize) -> Self::Item<'a> {}
+
+    fn get_chunk<'a>(
+        _state: Self::State,
+        _len: usize,
+    ) -> Option<Self::Chunk<'a>> {
+        Some(())
+    }
 }
 
 multiple_tuples!(impl_fetch, 16);
@@ -0,0 +1,255 @@
+//! Module responsible for filtering queried entities.
+
+use std::marker::PhantomData;
+
+use parsec_engine_macros::{impl_filter, impl_or_filter, multiple_tuples};
+
+use crate::ecs::{
+    system::access::SystemAccess,
+    world::{
+        archetype::{Archetype, ArchetypeError, ArchetypeId, is_tag},
+        change_detection::{ComponentTicks, SystemTicks},
+        component::{Component, ComponentId},
+        sparse_set::{SparseSets, SparseView, is_sparse},
+    },
+};
+
+/// Represents a type that can be used to narrow down entities matched by a
+/// [`Query`][crate::ecs::world::query::Query] without fetching any data.
+/// It is implemented for [`With`], [`Without`], [`Or`], [`Added`], [`Changed`], `()`
+/// and all tuples containing up to 16 values that implement [`Filter`].
+pub trait Filter {
+    /// Type that stores per-archetype data needed to filter single rows.
+    type State: Clone;
+    /// Checks if entities stored in an archetype with `archetype_id` can pass the filter.
+    fn matches(archetype_id: &ArchetypeId) -> bool;
+    /// Adds the components read by this filter to `access`.
+    fn access(access: &mut SystemAccess);
+    /// Creates the state used to later filter specific entities.
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError>;
+    /// Checks if the entity in `row` passes the filter.
+    fn filter_row(state: &Self::State, row: usize) -> bool;
+    /// Checks if the filter depends only on archetypes, so that every row of
+    /// a matching archetype passes it.
+    fn is_archetypal() -> bool;
+}
+
+/// Passes entities that have a component of type `T`.
+pub struct With<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: Component> Filter for With<T> {
+    /// Set only for sparse components, which are checked per row.
+    type State = Option<SparseView>;
+
+    fn matches(archetype_id: &ArchetypeId) -> bool {
+        is_sparse::<T>()
+            || archetype_id.contains_single(&ComponentId::of::<T>())
+    }
+
+    fn access(access: &mut SystemAccess) {
+        if is_sparse::<T>() {
+            access.read_component::<T>();
+        }
+    }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        _ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        Ok(is_sparse::<T>()
+            .then(|| sparse_sets.view(ComponentId::of::<T>(), archetype)))
+    }
+
+    fn filter_row(state: &Self::State, row: usize) -> bool {
+        state.as_ref().is_none_or(|view| view.contains(row))
+    }
+
+    fn is_archetypal() -> bool { !is_sparse::<T>() }
+}
+
+/// Passes entities that don't have a component of type `T`.
+pub struct Without<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: Component> Filter for Without<T> {
+    /// Set only for sparse components, which are checked per row.
+    type State = Option<SparseView>;
+
+    fn matches(archetype_id: &ArchetypeId) -> bool {
+        is_sparse::<T>()
+            || !archetype_id.contains_single(&ComponentId::of::<T>())
+    }
+
+    fn access(access: &mut SystemAccess) {
+        if is_sparse::<T>() {
+            access.read_component::<T>();
+        }
+    }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        _ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        Ok(is_sparse::<T>()
+            .then(|| sparse_sets.view(ComponentId::of::<T>(), archetype)))
+    }
+
+    fn filter_row(state: &Self::State, row: usize) -> bool {
+        state.as_ref().is_none_or(|view| !view.contains(row))
+    }
+
+    fn is_archetypal() -> bool { !is_sparse::<T>() }
+}
+
+/// Passes entities that pass at least one of the filters in the tuple `T`.
+pub struct Or<T> {
+    _marker: PhantomData<T>,
+}
+
+/// Stores component ticks needed by [`Added`] and [`Changed`].
+#[derive(Debug, Clone)]
+pub struct TicksFilterState {
+    ticks: FilterTicks,
+    system_ticks: SystemTicks,
+}
+
+#[derive(Debug, Clone)]
+enum FilterTicks {
+    Table(*const [ComponentTicks]),
+    Sparse(SparseView),
+}
+
+impl TicksFilterState {
+    fn new<T: Component>(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        system_ticks: SystemTicks,
+    ) -> Result<TicksFilterState, ArchetypeError> {
+        if is_tag::<T>() {
+            return Err(ArchetypeError::TagHasNoData);
+        }
+        let ticks = if is_sparse::<T>() {
+            FilterTicks::Sparse(
+                sparse_sets.view(ComponentId::of::<T>(), archetype),
+            )
+        } else {
+            FilterTicks::Table(archetype.get_ticks::<T>()?)
+        };
+        Ok(TicksFilterState {
+            ticks,
+            system_ticks,
+        })
+    }
+
+    /// Gets the ticks of the component in `row`, if the entity has one.
+    fn get(&self, row: usize) -> Option<ComponentTicks> {
+        match &self.ticks {
+            FilterTicks::Table(ticks) => {
+                let ticks = unsafe { &**ticks };
+                Some(ticks[row])
+            },
+            FilterTicks::Sparse(view) => view.ticks(row),
+        }
+    }
+}
+
+/// Passes entities whose component of type `T` was added since the last run of the
+/// current system. Tags don't track change ticks, so `T` can't be a tag.
+pub struct Added<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: Component> Filter for Added<T> {
+    type State = TicksFilterState;
+
+    fn matches(archetype_id: &ArchetypeId) -> bool {
+        is_sparse::<T>()
+            || archetype_id.contains_single(&ComponentId::of::<T>())
+    }
+
+    fn access(access: &mut SystemAccess) { access.read_component::<T>(); }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        TicksFilterState::new::<T>(archetype, sparse_sets, ticks)
+    }
+
+    fn filter_row(state: &Self::State, row: usize) -> bool {
+        let SystemTicks { last_run, this_run } = state.system_ticks;
+        state
+            .get(row)
+            .is_some_and(|ticks| ticks.added.is_newer_than(last_run, this_run))
+    }
+
+    fn is_archetypal() -> bool { false }
+}
+
+/// Passes entities whose component of type `T` was added or mutably accessed since
+/// the last run of the current system. `T` can't be a tag.
+pub struct Changed<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: Component> Filter for Changed<T> {
+    type State = TicksFilterState;
+
+    fn matches(archetype_id: &ArchetypeId) -> bool {
+        is_sparse::<T>()
+            || archetype_id.contains_single(&ComponentId::of::<T>())
+    }
+
+    fn access(access: &mut SystemAccess) { access.read_component::<T>(); }
+
+    fn prepare(
+        archetype: &Archetype,
+        sparse_sets: &SparseSets,
+        ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        TicksFilterState::new::<T>(archetype, sparse_sets, ticks)
+    }
+
+    fn filter_row(state: &Self::State, row: usize) -> bool {
+        let SystemTicks { last_run, this_run } = state.system_ticks;
+        state.get(row).is_some_and(|ticks| {
+            ticks.changed.is_newer_than(last_run, this_run)
+        })
+    }
+
+    fn is_archetypal() -> bool { false }
+}
+
+/// Passes all entities.
+impl Filter for () {
+    type State = ();
+
+    fn matches(_archetype_id: &ArchetypeId) -> bool { true }
+
+    fn access(_access: &mut SystemAccess) {}
+
+    fn prepare(
+        _archetype: &Archetype,
+        _sparse_sets: &SparseSets,
+        _ticks: SystemTicks,
+    ) -> Result<Self::State, ArchetypeError> {
+        Ok(())
+    }
+
+    fn filter_row(_state: &Self::State, _row: usize) -> bool { true }
+
+    fn is_archetypal() -> bool { true }
+}
+
+multiple_tuples!(impl_filter, 16);
+multiple_tuples!(impl_or_filter, 16);
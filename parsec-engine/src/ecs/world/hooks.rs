@@ -0,0 +1,209 @@
+//! Module responsible for reacting to components being added to and removed
+//! from entities.
+
+use std::{collections::HashMap, fmt::Debug, ptr::NonNull};
+
+use crate::ecs::{
+    commands::Commands,
+    entity::Entity,
+    world::{
+        World,
+        component::{Component, ComponentId},
+    },
+};
+
+/// Moment in the lifecycle of a component at which a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+    /// The component was added to an entity that didn't have one.
+    Add,
+    /// The component is about to be overwritten by a new value.
+    Replace,
+    /// The component is about to be removed from its entity.
+    Remove,
+}
+
+/// Hook with the component type erased. Tags are passed as a null pointer.
+type ErasedHook = Box<dyn Fn(Entity, *const u8, &mut Commands) + Send + Sync>;
+
+/// Stores hooks registered for components.
+#[derive(Default)]
+pub struct Hooks {
+    hooks: HashMap<(ComponentId, HookKind), Vec<ErasedHook>>,
+}
+
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("hooks", &self.hooks.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Hooks {
+    pub fn new() -> Hooks { Hooks::default() }
+
+    /// Checks if no hooks are registered.
+    pub fn is_empty(&self) -> bool { self.hooks.is_empty() }
+
+    /// Registers `hook` to run at `kind` for components of type `T`.
+    fn insert<T: Component>(
+        &mut self,
+        kind: HookKind,
+        hook: impl Fn(Entity, &T, &mut Commands) + Send + Sync + 'static,
+    ) {
+        let hook: ErasedHook = Box::new(move |entity, ptr, commands| {
+            // SAFETY: `ptr` points to a `T`, or is null if `T` is a tag, in
+            // which case any aligned pointer is valid.
+            let value = unsafe {
+                if ptr.is_null() {
+                    NonNull::<T>::dangling().as_ref()
+                } else {
+                    &*(ptr as *const T)
+                }
+            };
+            hook(entity, value, commands);
+        });
+        self.hooks
+            .entry((ComponentId::of::<T>(), kind))
+            .or_default()
+            .push(hook);
+    }
+
+    /// Checks if any hook runs at `kind` for components with id
+    /// `component_id`.
+    fn contains(&self, component_id: ComponentId, kind: HookKind) -> bool {
+        self.hooks.contains_key(&(component_id, kind))
+    }
+
+    /// Runs all hooks registered at `kind` for components with id
+    /// `component_id`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` has to point to a valid component with id `component_id`, or be
+    /// null if the component is a tag.
+    unsafe fn run(
+        &self,
+        component_id: ComponentId,
+        kind: HookKind,
+        entity: Entity,
+        ptr: *const u8,
+        commands: &mut Commands,
+    ) {
+        for hook in self.hooks.get(&(component_id, kind)).into_iter().flatten()
+        {
+            hook(entity, ptr, commands);
+        }
+    }
+}
+
+impl World {
+    /// Registers `hook` to run when a component of type `T` is added to an
+    /// entity that didn't have one, including when the entity is spawned.
+    /// The hook gets the new component.
+    ///
+    /// Hooks can't access the world directly. Operations queued to the given
+    /// [`Commands`] are applied together with other commands, see
+    /// [`World::take_commands`].
+    pub fn on_add<T: Component>(
+        &mut self,
+        hook: impl Fn(Entity, &T, &mut Commands) + Send + Sync + 'static,
+    ) {
+        self.hooks.insert(HookKind::Add, hook);
+    }
+
+    /// Registers `hook` to run when a component of type `T` is about to be
+    /// overwritten by [`World::add_components`].
+    /// The hook gets the old component. See [`World::on_add`].
+    pub fn on_replace<T: Component>(
+        &mut self,
+        hook: impl Fn(Entity, &T, &mut Commands) + Send + Sync + 'static,
+    ) {
+        self.hooks.insert(HookKind::Replace, hook);
+    }
+
+    /// Registers `hook` to run when a component of type `T` is about to be
+    /// removed from an entity, including when the entity is deleted.
+    /// The hook gets the removed component. See [`World::on_add`].
+    pub fn on_remove<T: Component>(
+        &mut self,
+        hook: impl Fn(Entity, &T, &mut Commands) + Send + Sync + 'static,
+    ) {
+        self.hooks.insert(HookKind::Remove, hook);
+    }
+
+    /// Takes the operations queued by hooks.
+    /// [`Commands::apply`] applies them automatically after its own ones.
+    pub fn take_commands(&mut self) -> Commands {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Gets ids of all components of `entity` if any hooks are registered,
+    /// otherwise nothing.
+    pub(super) fn hooked_components(&self, entity: Entity) -> Vec<ComponentId> {
+        if self.hooks.is_empty() {
+            return Vec::new();
+        }
+        let Some(location) = self.entities.location(entity) else {
+            return Vec::new();
+        };
+        self.archetypes[location.archetype]
+            .id()
+            .component_ids()
+            .chain(self.sparse_sets.component_ids(entity))
+            .collect()
+    }
+
+    /// Runs hooks registered at `kind` for components of `entity` with ids in
+    /// `component_ids`. Components `entity` doesn't have are skipped.
+    pub(super) fn run_hooks(
+        &mut self,
+        entity: Entity,
+        kind: HookKind,
+        component_ids: &[ComponentId],
+    ) {
+        let mut queued = self.hook_commands(entity, kind, component_ids);
+        self.commands.append(&mut queued);
+    }
+
+    /// Same as [`World::run_hooks`], but returns the operations queued by the
+    /// hooks instead of keeping them. Used before changes that can fail, to
+    /// keep the operations only if the change succeeds.
+    pub(super) fn hook_commands(
+        &self,
+        entity: Entity,
+        kind: HookKind,
+        component_ids: &[ComponentId],
+    ) -> Commands {
+        let mut commands = Commands::new();
+        if self.hooks.is_empty() {
+            return commands;
+        }
+        let Some(location) = self.entities.location(entity) else {
+            return commands;
+        };
+        let archetype = &self.archetypes[location.archetype];
+        for component_id in component_ids.iter().copied() {
+            if !self.hooks.contains(component_id, kind) {
+                continue;
+            }
+            let ptr = archetype
+                .ptr(component_id, location.row)
+                .or_else(|| self.sparse_sets.ptr(component_id, entity));
+            if let Some(ptr) = ptr {
+                // SAFETY: the pointer comes from the storage of the component.
+                unsafe {
+                    self.hooks.run(
+                        component_id,
+                        kind,
+                        entity,
+                        ptr,
+                        &mut commands,
+                    );
+                }
+            }
+        }
+        commands
+    }
+}
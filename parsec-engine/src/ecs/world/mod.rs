@@ -1,29 +1,54 @@
 //! Module responsible for storing and querying entities and their data.
 
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
 
-use archetype::{Archetype, ArchetypeError, ArchetypeId};
+use archetype::{
+    Archetype, ArchetypeError, ArchetypeId, BorrowingStats, RawComponent,
+    is_tag,
+};
 use spawn::Spawn;
 use thiserror::Error;
 
 use crate::{
     create_counter,
     ecs::{
-        entity::Entity,
+        commands::Commands,
+        entity::{Entities, Entity, EntityLocation},
         world::{
-            add_component::AddComponent, fetch::Fetch, query::Query,
+            add_component::AddComponent,
+            change_detection::{SystemTicks, Tick},
+            component::{Component, ComponentId},
+            dynamic::DynamicComponents,
+            entity_ref::{ComponentRef, ComponentRefMut, EntityMut, EntityRef},
+            fetch::{ComponentMut, Fetch},
+            filter::Filter,
+            hooks::{HookKind, Hooks},
+            query::Query,
             remove_component::RemoveComponent,
+            sparse_set::{SparseSets, is_sparse},
         },
     },
+    utils::IdType,
 };
 
 pub mod add_component;
 mod archetype;
+pub mod change_detection;
 pub mod component;
+pub mod dynamic;
+pub mod entity_ref;
 pub mod fetch;
+pub mod filter;
+pub mod hooks;
+pub mod prefab;
 pub mod query;
 pub mod remove_component;
 pub mod spawn;
+mod sparse_set;
 
 #[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WorldError {
@@ -35,45 +60,346 @@ pub enum WorldError {
     AddComponentError { kind: ArchetypeError },
     #[error("Failed to delete components because of: {kind}")]
     DeleteComponentError { kind: ArchetypeError },
+    #[error("Failed to get a component because of: {kind}")]
+    GetComponentError { kind: ArchetypeError },
+    #[error("Entity can not become a child of itself or its descendant")]
+    HierarchyCycle,
+    #[error("Failed to clone an entity because of: {kind}")]
+    CloneError { kind: ArchetypeError },
+    #[error("Failed to register a component because of: {kind}")]
+    RegisterComponentError { kind: ArchetypeError },
+    #[error("Query state was already used with another world")]
+    QueryStateWorldMismatch,
+}
+
+/// Components cloned from an entity or created from raw bytes, ready to be
+/// moved to a new entity.
+struct RawBundle {
+    archetype_id: ArchetypeId,
+    components: HashMap<ComponentId, RawComponent>,
+    sparse: Vec<(ComponentId, RawComponent)>,
+}
+
+impl RawBundle {
+    /// Moves all components to `entity`, which has to be the last entity of
+    /// `archetype`.
+    fn spawn(
+        self,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+    ) -> Result<(), ArchetypeError> {
+        for (component_id, component) in self.components {
+            archetype.add_raw(component_id, component)?;
+        }
+        for (component_id, component) in self.sparse {
+            sparse_sets.insert_raw(component_id, entity, component)?;
+        }
+        Ok(())
+    }
 }
 
 /// Stores all data about components and entities.
 #[derive(Debug)]
 pub struct World {
-    /// Contains all archetypes indexed by their id.
-    archetypes: HashMap<ArchetypeId, Archetype>,
+    /// Unique among all worlds, so that [`QueryState`][query::QueryState]s
+    /// can check they are used with a single world.
+    id: IdType,
+    /// Contains all archetypes.
+    archetypes: Vec<Archetype>,
+    /// Maps archetype ids to indices in `archetypes`.
+    archetype_index: HashMap<ArchetypeId, usize>,
+    /// Allocates entities and stores their locations.
+    entities: Entities,
+    /// Stores components that aren't part of archetypes.
+    sparse_sets: SparseSets,
+    /// Descriptors of components defined at runtime.
+    dynamic_components: DynamicComponents,
+    /// Callbacks run when components are added or removed.
+    hooks: Hooks,
+    /// Operations queued by hooks.
+    commands: Commands,
+    /// Current change tick. Components added or changed now are marked with it.
+    change_tick: Tick,
+    /// Tick at which the currently running system ran previously.
+    last_change_tick: Tick,
 }
 
 impl Default for World {
     fn default() -> Self { Self::new() }
 }
 
-create_counter! {ENTITY_ID_COUNTER}
+create_counter! {WORLD_ID_COUNTER}
+
 impl World {
     pub fn new() -> Self {
         Self {
-            archetypes: HashMap::new(),
+            id: WORLD_ID_COUNTER.next(),
+            archetypes: Vec::new(),
+            archetype_index: HashMap::new(),
+            entities: Entities::new(),
+            sparse_sets: SparseSets::new(),
+            dynamic_components: DynamicComponents::new(),
+            hooks: Hooks::new(),
+            commands: Commands::new(),
+            change_tick: Tick::new(1),
+            last_change_tick: Tick::new(0),
+        }
+    }
+
+    /// Gets the id of the world, unique among all worlds.
+    pub fn id(&self) -> IdType { self.id }
+
+    /// Gets the current change tick.
+    pub fn change_tick(&self) -> Tick { self.change_tick }
+
+    /// Advances the current change tick and returns the new value.
+    pub fn increment_change_tick(&mut self) -> Tick {
+        self.change_tick = Tick::new(self.change_tick.get().wrapping_add(1));
+        self.change_tick
+    }
+
+    /// Gets the tick relative to which [`Added`][filter::Added] and
+    /// [`Changed`][filter::Changed] filters detect changes.
+    pub fn last_change_tick(&self) -> Tick { self.last_change_tick }
+
+    /// Sets the tick relative to which [`Added`][filter::Added] and
+    /// [`Changed`][filter::Changed] filters detect changes.
+    /// [`Systems`][crate::ecs::system::Systems] set it to the previous run of
+    /// each system before running it.
+    pub fn set_last_change_tick(&mut self, tick: Tick) {
+        self.last_change_tick = tick;
+    }
+
+    /// Gets the ticks used by queries created now.
+    pub fn system_ticks(&self) -> SystemTicks {
+        SystemTicks {
+            last_run: self.last_change_tick,
+            this_run: self.change_tick,
         }
     }
 
     /// Creates a query for all entities matching `T`. Panics on archetype errors.
-    pub fn query<T: Fetch>(&self) -> Query<T> {
+    pub fn query<T: Fetch>(&self) -> Query<'_, T> {
         Query::<T>::from_world(self).expect("query failed")
     }
 
-    /// Returns a mutable reference to the archetype stored under `archetype_id`.
-    /// If there was no such archetype, it is created, added to `self` and a mutable reference is returned.
-    fn get_archetype_mut(
+    /// Creates a query for all entities matching `T` that pass the [filter][Filter] `F`.
+    /// Panics on archetype errors.
+    pub fn query_filtered<T: Fetch, F: Filter>(&self) -> Query<'_, T, F> {
+        Query::<T, F>::from_world(self).expect("query failed")
+    }
+
+    /// Gets a counter increased whenever a new archetype is created.
+    /// Archetypes are never removed, so it's the number of archetypes and
+    /// archetypes created after a generation start at that index.
+    pub fn archetype_generation(&self) -> usize { self.archetypes.len() }
+
+    /// Returns the index of the archetype stored under `archetype_id`.
+    /// If there was no such archetype, it is created and added to `self`.
+    fn get_archetype_index(&mut self, archetype_id: &ArchetypeId) -> usize {
+        if let Some(index) = self.archetype_index.get(archetype_id) {
+            return *index;
+        }
+
+        let index = self.archetypes.len();
+        self.archetypes.push(Archetype::new(archetype_id.clone()));
+        self.archetype_index.insert(archetype_id.clone(), index);
+        index
+    }
+
+    /// Returns the index of the archetype entities in the archetype at `index`
+    /// move to when components with ids `component_ids` are added.
+    /// `bundle_id` is only called if the transition wasn't cached yet.
+    ///
+    /// # Errors
+    ///
+    /// - If `bundle_id` fails or produces an id that can't be merged.
+    fn add_transition(
         &mut self,
-        archetype_id: &ArchetypeId,
-    ) -> &mut Archetype {
-        if !self.archetypes.contains_key(archetype_id) {
-            let mut archetype = Archetype::new();
-            archetype.create_empty(archetype_id);
-            self.archetypes.insert(archetype_id.clone(), archetype);
+        index: usize,
+        component_ids: &[ComponentId],
+        bundle_id: impl FnOnce() -> Result<ArchetypeId, ArchetypeError>,
+    ) -> Result<usize, ArchetypeError> {
+        if let Some(new_index) = self.archetypes[index].add_edge(component_ids)
+        {
+            return Ok(new_index);
+        }
+        let new_archetype_id =
+            self.archetypes[index].id().merge_with(bundle_id()?)?;
+        let new_index = self.get_archetype_index(&new_archetype_id);
+        self.archetypes[index].set_add_edge(component_ids, new_index);
+        Ok(new_index)
+    }
+
+    /// Returns the index of the archetype entities in the archetype at `index`
+    /// move to when components with ids `component_ids` are removed.
+    /// `bundle_id` is only called if the transition wasn't cached yet.
+    ///
+    /// # Errors
+    ///
+    /// - If `bundle_id` fails or produces an id that can't be subtracted.
+    fn remove_transition(
+        &mut self,
+        index: usize,
+        component_ids: &[ComponentId],
+        bundle_id: impl FnOnce() -> Result<ArchetypeId, ArchetypeError>,
+    ) -> Result<usize, ArchetypeError> {
+        if let Some(new_index) =
+            self.archetypes[index].remove_edge(component_ids)
+        {
+            return Ok(new_index);
+        }
+        let new_archetype_id =
+            self.archetypes[index].id().remove_from(bundle_id()?)?;
+        let new_index = self.get_archetype_index(&new_archetype_id);
+        self.archetypes[index].set_remove_edge(component_ids, new_index);
+        Ok(new_index)
+    }
+
+    /// Checks if `entity` is alive.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// Gets the number of alive entities.
+    pub fn entity_count(&self) -> usize { self.entities.len() }
+
+    /// Iterates over all alive entities, grouped by archetype.
+    pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.archetypes
+            .iter()
+            .flat_map(|archetype| archetype.entities.iter().copied())
+    }
+
+    /// Checks if `entity` has a component of type `T`.
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        match self.entities.location(entity) {
+            Some(_) if is_sparse::<T>() => {
+                self.sparse_sets.contains::<T>(entity)
+            },
+            Some(location) => self.archetypes[location.archetype]
+                .id()
+                .contains_single(&ComponentId::of::<T>()),
+            None => false,
+        }
+    }
+
+    /// Gets the component of type `T` belonging to `entity`.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist.
+    /// - If `entity` doesn't have a component of type `T`.
+    /// - If the column storing `T` components is borrowed mutably.
+    pub fn get<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<ComponentRef<'_, T>, WorldError> {
+        let location = self.entities.location(entity).ok_or(
+            WorldError::GetComponentError {
+                kind: ArchetypeError::EntityNotFound,
+            },
+        )?;
+        if is_sparse::<T>() {
+            let (ptr, access) = self
+                .sparse_sets
+                .get::<T>(entity)
+                .map_err(|e| WorldError::GetComponentError { kind: e })?;
+            return Ok(ComponentRef::new(unsafe { &*ptr }, access));
+        }
+        if is_tag::<T>() {
+            if !self.has::<T>(entity) {
+                return Err(WorldError::GetComponentError {
+                    kind: ArchetypeError::TypeNotFound,
+                });
+            }
+            // SAFETY: tags are zero-sized, so any aligned pointer is valid.
+            let value = unsafe { std::ptr::NonNull::dangling().as_ref() };
+            let access = Arc::new(RwLock::new(BorrowingStats::new()));
+            return Ok(ComponentRef::new(value, access));
+        }
+        let (ptr, access) = self.archetypes[location.archetype]
+            .get::<T>()
+            .map_err(|e| WorldError::GetComponentError { kind: e })?;
+        let array = unsafe { &*ptr };
+        Ok(ComponentRef::new(&array[location.row], access))
+    }
+
+    /// Gets the component of type `T` belonging to `entity` mutably.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist.
+    /// - If `entity` doesn't have a component of type `T`.
+    /// - If `T` is a tag.
+    /// - If the column storing `T` components is already borrowed.
+    pub fn get_mut<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<ComponentRefMut<'_, T>, WorldError> {
+        let location = self.entities.location(entity).ok_or(
+            WorldError::GetComponentError {
+                kind: ArchetypeError::EntityNotFound,
+            },
+        )?;
+        if is_sparse::<T>() {
+            let (ptr, ticks, access) = self
+                .sparse_sets
+                .get_mut::<T>(entity)
+                .map_err(|e| WorldError::GetComponentError { kind: e })?;
+            let value = unsafe {
+                ComponentMut::new(&mut *ptr, &mut *ticks, self.change_tick)
+            };
+            return Ok(ComponentRefMut::new(value, access));
+        }
+        if is_tag::<T>() {
+            return Err(WorldError::GetComponentError {
+                kind: ArchetypeError::TagHasNoData,
+            });
         }
+        let (ptr, ticks, access) = self.archetypes[location.archetype]
+            .get_mut::<T>()
+            .map_err(|e| WorldError::GetComponentError { kind: e })?;
+        let array = unsafe { &mut *ptr };
+        let ticks = unsafe { &mut *ticks };
+        let value = ComponentMut::new(
+            &mut array[location.row],
+            &mut ticks[location.row],
+            self.change_tick,
+        );
+        Ok(ComponentRefMut::new(value, access))
+    }
 
-        self.archetypes.get_mut(archetype_id).unwrap()
+    /// Gets a read-only view of `entity`.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist.
+    pub fn entity(&self, entity: Entity) -> Result<EntityRef<'_>, WorldError> {
+        if !self.contains(entity) {
+            return Err(WorldError::GetComponentError {
+                kind: ArchetypeError::EntityNotFound,
+            });
+        }
+        Ok(EntityRef::new(self, entity))
+    }
+
+    /// Gets a mutable view of `entity`.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist.
+    pub fn entity_mut(
+        &mut self,
+        entity: Entity,
+    ) -> Result<EntityMut<'_>, WorldError> {
+        if !self.contains(entity) {
+            return Err(WorldError::GetComponentError {
+                kind: ArchetypeError::EntityNotFound,
+            });
+        }
+        Ok(EntityMut::new(self, entity))
     }
 
     /// Spawns a new entity.
@@ -86,51 +412,307 @@ impl World {
         let archetype_id = bundle
             .archetype_id()
             .map_err(|e| WorldError::SpawnError { kind: e })?;
-        let entity_id = ENTITY_ID_COUNTER.next();
-        let archetype = self.get_archetype_mut(&archetype_id);
-        let entity = archetype
-            .new_entity(entity_id)
-            .map_err(|e| WorldError::SpawnError { kind: e })?;
-        bundle
-            .spawn(archetype)
+        let archetype_index = self.get_archetype_index(&archetype_id);
+        self.spawn_in(archetype_index, bundle, true)
+            .map_err(|e| WorldError::SpawnError { kind: e })
+    }
+
+    /// Spawns new entities for all bundles in `bundles`, which have to
+    /// produce the same [`ArchetypeId`]. The archetype is looked up once and
+    /// its columns are grown once for the whole batch.
+    /// Returns the spawned entities in order.
+    ///
+    /// # Errors
+    ///
+    /// - If the bundles can't produce a valid [`ArchetypeId`].
+    /// - If a bundle has different components than the first bundle. Nothing
+    ///   is spawned then.
+    /// - If the [archetype][Archetype] is already borrowed in some way.
+    /// - If a bundle fails to move its components. Entities spawned before
+    ///   stay in the world.
+    pub fn spawn_batch<T: Spawn>(
+        &mut self,
+        bundles: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<Entity>, WorldError> {
+        let mut bundles = bundles.into_iter().peekable();
+        let Some(first) = bundles.peek() else {
+            return Ok(Vec::new());
+        };
+        let archetype_id = first
+            .archetype_id()
             .map_err(|e| WorldError::SpawnError { kind: e })?;
+        if T::is_archetype_fixed() {
+            return self.spawn_batch_in(&archetype_id, bundles);
+        }
+
+        // Boxed bundles can have different components, so all of them are
+        // checked before any is spawned.
+        let bundles = bundles.collect::<Vec<_>>();
+        for bundle in bundles.iter().skip(1) {
+            let bundle_id = bundle
+                .archetype_id()
+                .map_err(|e| WorldError::SpawnError { kind: e })?;
+            if bundle_id != archetype_id {
+                return Err(WorldError::SpawnError {
+                    kind: ArchetypeError::BatchArchetypeMismatch,
+                });
+            }
+        }
+        self.spawn_batch_in(&archetype_id, bundles)
+    }
+
+    /// Spawns all bundles in `bundles`, which have to have the same
+    /// `archetype_id`. Only the first bundle is checked against the columns.
+    fn spawn_batch_in<T: Spawn>(
+        &mut self,
+        archetype_id: &ArchetypeId,
+        bundles: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<Entity>, WorldError> {
+        let archetype_index = self.get_archetype_index(archetype_id);
+        let mut bundles = bundles.into_iter();
+        let (additional, _) = bundles.size_hint();
+        let mut spawned = Vec::with_capacity(additional + 1);
+        let mut check = true;
+        while let Some(bundle) = bundles.next() {
+            spawned.push(
+                self.spawn_in(archetype_index, bundle, check)
+                    .map_err(|e| WorldError::SpawnError { kind: e })?,
+            );
+            if check {
+                // Columns learn the layout of their components with the first
+                // bundle.
+                check = false;
+                let (additional, _) = bundles.size_hint();
+                self.archetypes[archetype_index].reserve(additional);
+                self.entities.reserve(additional);
+            }
+        }
+        Ok(spawned)
+    }
+
+    /// Spawns a new entity into the archetype at `archetype_index`, which has
+    /// to match the [`ArchetypeId`] of `bundle`.
+    /// The archetype is checked to be writable and to have all of its columns
+    /// filled by `bundle` only if `check` is set. It can be skipped right
+    /// after spawning a bundle with the same id.
+    fn spawn_in<T: Spawn>(
+        &mut self,
+        archetype_index: usize,
+        bundle: T,
+        check: bool,
+    ) -> Result<Entity, ArchetypeError> {
+        let change_tick = self.change_tick;
+        let archetype = &mut self.archetypes[archetype_index];
+        if check && !archetype.are_all_columns_mutable() {
+            return Err(ArchetypeError::ArchetypeColumnNotWritable);
+        }
+
+        let entity = self.entities.alloc();
+        let row = archetype.new_entity(entity)?;
+        if let Err(e) = bundle
+            .spawn(archetype, &mut self.sparse_sets, entity, change_tick)
+            .and_then(|()| match check {
+                true => archetype.check_columns(),
+                false => Ok(()),
+            })
+        {
+            archetype.trim_columns();
+            let _ = self.sparse_sets.remove_entity(entity);
+            self.entities.free(entity);
+            return Err(e);
+        }
         archetype.bundle_count += 1;
+        self.entities.set_location(entity, EntityLocation {
+            archetype: archetype_index,
+            row,
+        });
+        let component_ids = self.hooked_components(entity);
+        self.run_hooks(entity, HookKind::Add, &component_ids);
+        Ok(entity)
+    }
+
+    /// Spawns a copy of `entity` with clones of all of its components that can
+    /// be cloned. Other components, including [`Parent`][crate::ecs::hierarchy::Parent]
+    /// and [`Children`][crate::ecs::hierarchy::Children], are skipped, so the
+    /// copy is a root of the hierarchy.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't exist.
+    /// - If the [archetype][Archetype] containing `entity` or any sparse set
+    ///   storing its components is borrowed mutably.
+    /// - If the destination [archetype][Archetype] is already borrowed in some way.
+    pub fn clone_entity(
+        &mut self,
+        entity: Entity,
+    ) -> Result<Entity, WorldError> {
+        let cloned = self
+            .clone_components(entity, self.change_tick)
+            .map_err(|e| WorldError::CloneError { kind: e })?;
+        self.spawn_raw(cloned)
+            .map_err(|e| WorldError::CloneError { kind: e })
+    }
+
+    /// Clones all cloneable components of `entity`, marking the clones as
+    /// added at `tick`.
+    fn clone_components(
+        &self,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<RawBundle, ArchetypeError> {
+        let location = self
+            .entities
+            .location(entity)
+            .ok_or(ArchetypeError::EntityNotFound)?;
+        let (archetype_id, components) =
+            self.archetypes[location.archetype].clone_row(location.row, tick)?;
+        let sparse = self.sparse_sets.clone_entity(entity, tick)?;
+        Ok(RawBundle {
+            archetype_id,
+            components,
+            sparse,
+        })
+    }
+
+    /// Spawns a new entity owning the `bundle` components.
+    fn spawn_raw(
+        &mut self,
+        bundle: RawBundle,
+    ) -> Result<Entity, ArchetypeError> {
+        let archetype_index = self.get_archetype_index(&bundle.archetype_id);
+        let archetype = &mut self.archetypes[archetype_index];
+        if !archetype.are_all_columns_mutable() {
+            return Err(ArchetypeError::ArchetypeColumnNotWritable);
+        }
+
+        let entity = self.entities.alloc();
+        let row = archetype.new_entity(entity)?;
+        if let Err(e) = bundle.spawn(archetype, &mut self.sparse_sets, entity) {
+            archetype.trim_columns();
+            let _ = self.sparse_sets.remove_entity(entity);
+            self.entities.free(entity);
+            return Err(e);
+        }
+        archetype.bundle_count += 1;
+        self.entities.set_location(entity, EntityLocation {
+            archetype: archetype_index,
+            row,
+        });
+        let component_ids = self.hooked_components(entity);
+        self.run_hooks(entity, HookKind::Add, &component_ids);
         Ok(entity)
     }
 
     /// Deletes the given entity.
+    /// It's detached from its parent and its children become roots.
+    /// [Remove hooks][World::on_remove] run for all of its components first.
     ///
     /// # Errors
     ///
     /// - If `entity` doesn't exist.
-    /// - If the [archetype][Archetype] containing `entity` is already borrowed in some way.
+    /// - If the [archetype][Archetype] containing `entity` or any sparse set
+    ///   storing its components is already borrowed in some way.
     pub fn delete(&mut self, entity: Entity) -> Result<(), WorldError> {
-        for (_, archetype) in self.archetypes.iter_mut() {
-            if archetype.entities.contains(&entity)
-                && !archetype.are_all_columns_mutable()
-            {
-                return Err(WorldError::DeleteError {
-                    kind: ArchetypeError::ArchetypeColumnNotWritable,
-                });
-            }
-            match archetype.delete_entity(entity) {
-                Ok(()) => {
-                    archetype.bundle_count -= 1;
-                    return Ok(());
-                },
-                Err(ArchetypeError::EntityNotFound) => (),
-                Err(err) => return Err(WorldError::DeleteError { kind: err }),
-            };
+        let location =
+            self.entities
+                .location(entity)
+                .ok_or(WorldError::DeleteError {
+                    kind: ArchetypeError::EntityNotFound,
+                })?;
+        if !self.sparse_sets.is_entity_mutable(entity)
+            || !self.archetypes[location.archetype].are_all_columns_mutable()
+        {
+            return Err(WorldError::DeleteError {
+                kind: ArchetypeError::ArchetypeColumnNotWritable,
+            });
         }
-        Err(WorldError::DeleteError {
-            kind: ArchetypeError::EntityNotFound,
-        })
+        self.detach_hierarchy(entity)?;
+        // Detaching children can move `entity` to another row.
+        // UNWRAP: `entity` was checked to exist above.
+        let location = self.entities.location(entity).unwrap();
+        let component_ids = self.hooked_components(entity);
+        let mut queued =
+            self.hook_commands(entity, HookKind::Remove, &component_ids);
+        let archetype = &mut self.archetypes[location.archetype];
+        let moved_entity = archetype
+            .delete_row(location.row)
+            .map_err(|e| WorldError::DeleteError { kind: e })?;
+        archetype.bundle_count -= 1;
+        if let Some(moved_entity) = moved_entity {
+            self.entities.set_location(moved_entity, location);
+        }
+        self.sparse_sets
+            .remove_entity(entity)
+            .map_err(|e| WorldError::DeleteError { kind: e })?;
+        self.entities.free(entity);
+        self.commands.append(&mut queued);
+        Ok(())
+    }
+
+    /// Deletes all entities in `entities`.
+    /// See [`World::delete`].
+    ///
+    /// # Errors
+    ///
+    /// - If any entity doesn't exist.
+    /// - If the [archetype][Archetype] containing any entity or any sparse
+    ///   set storing its components is already borrowed in some way.
+    ///
+    /// Entities before the failing one are deleted, the rest are kept.
+    pub fn despawn_batch(
+        &mut self,
+        entities: impl IntoIterator<Item = Entity>,
+    ) -> Result<(), WorldError> {
+        for entity in entities {
+            self.delete(entity)?;
+        }
+        Ok(())
+    }
+
+    /// Moves `entity` from its current archetype to the archetype at `new_index`.
+    /// Only components stored by the destination archetype are moved.
+    /// Does nothing if `entity` is already stored in that archetype.
+    fn move_entity(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        new_index: usize,
+    ) -> Result<(), ArchetypeError> {
+        if location.archetype == new_index {
+            return Ok(());
+        }
+
+        let [old_archetype, new_archetype] = self
+            .archetypes
+            .get_disjoint_mut([location.archetype, new_index])
+            .expect("archetype indices are valid and distinct");
+        let moved_entity =
+            old_archetype.move_row(location.row, new_archetype)?;
+        old_archetype.bundle_count -= 1;
+        if let Some(moved_entity) = moved_entity {
+            self.entities.set_location(moved_entity, location);
+        }
+
+        new_archetype.bundle_count += 1;
+        let row = new_archetype.moved_entity(entity);
+        self.entities.set_location(entity, EntityLocation {
+            archetype: new_index,
+            row,
+        });
+
+        Ok(())
     }
 
     /// Add components to an already existing entity.
+    /// Components the entity already has are replaced, and adding only sparse
+    /// components doesn't move the entity to another archetype.
+    /// [Replace hooks][World::on_replace] run before the components are added
+    /// and [add hooks][World::on_add] after. Operations queued by the hooks are
+    /// kept only if adding succeeds.
     ///
     /// # Errors
     ///
+    /// - If `entity` doesn't exist.
     /// - If `T` can't produce a valid [`ArchetypeId`].
     /// - If `T` merged with the type of `entity` can't produce a valid [`ArchetypeId`].
     /// - If either the original [archetype][Archetype] containing `entity` or the destination [archetype][Archetype] is already borrowed in some way.
@@ -139,126 +721,633 @@ impl World {
         entity: Entity,
         bundle_extension: T,
     ) -> Result<(), WorldError> {
-        let (archetype_id, old_archetype) = match self
-            .archetypes
-            .iter_mut()
-            .find(|(_, x)| x.check_entity(entity))
-        {
-            Some(val) => val,
-            None => {
-                return Err(WorldError::DeleteError {
-                    kind: ArchetypeError::EntityNotFound,
-                });
+        let location = self.entities.location(entity).ok_or(
+            WorldError::AddComponentError {
+                kind: ArchetypeError::EntityNotFound,
             },
-        };
-        let t_archetype_id = bundle_extension
-            .archetype_id()
-            .map_err(|e| WorldError::AddComponentError { kind: e })?;
-        let new_archetype_id = archetype_id
-            .merge_with(t_archetype_id)
+        )?;
+        let mut component_ids = Vec::new();
+        bundle_extension.component_ids(&mut component_ids);
+        let archetype_id = self.archetypes[location.archetype].id();
+        let table_replaced = component_ids
+            .iter()
+            .copied()
+            .filter(|component_id| archetype_id.contains_single(component_id))
+            .collect::<Vec<_>>();
+        let new_index = self
+            .add_transition(location.archetype, &component_ids, || {
+                let bundle_id = bundle_extension.archetype_id()?;
+                if table_replaced.is_empty() {
+                    return Ok(bundle_id);
+                }
+                // Replaced components are already in the archetype.
+                bundle_id.remove_from(ArchetypeId::new(table_replaced.clone())?)
+            })
             .map_err(|e| WorldError::AddComponentError { kind: e })?;
 
-        let (old_entity, map) = old_archetype
-            .cut_entity(entity)
-            .map_err(|e| WorldError::AddComponentError { kind: e })?;
-        old_archetype.bundle_count -= 1;
-
-        let new_archetype = self.get_archetype_mut(&new_archetype_id);
-
-        if !new_archetype.are_all_columns_mutable() {
-            let (_, old_archetype) = self
-                .archetypes
-                .iter_mut()
-                .find(|(_, x)| x.check_entity(entity))
-                .unwrap();
-            for (type_id, (size, data)) in map.iter() {
-                old_archetype
-                    .add_raw(*size, *type_id, data.clone())
-                    .map_err(|e| WorldError::AddComponentError { kind: e })?;
-            }
-            return Err(WorldError::AddComponentError {
-                kind: ArchetypeError::ArchetypeColumnNotWritable,
+        let (replaced, added): (Vec<_>, Vec<_>) =
+            component_ids.into_iter().partition(|component_id| {
+                table_replaced.contains(component_id)
+                    || self.sparse_sets.ptr(*component_id, entity).is_some()
             });
-        }
+        let mut queued =
+            self.hook_commands(entity, HookKind::Replace, &replaced);
 
-        for (type_id, (size, data)) in map.iter() {
-            new_archetype
-                .add_raw(*size, *type_id, data.clone())
-                .map_err(|e| WorldError::AddComponentError { kind: e })?;
-        }
+        let change_tick = self.change_tick;
+        self.move_entity(entity, location, new_index)
+            .map_err(|e| WorldError::AddComponentError { kind: e })?;
+        let archetype = &mut self.archetypes[new_index];
         bundle_extension
-            .add_to(new_archetype)
+            .add_to(archetype, &mut self.sparse_sets, entity, change_tick)
             .map_err(|e| WorldError::AddComponentError { kind: e })?;
-        new_archetype.bundle_count += 1;
-        new_archetype.moved_entity(old_entity);
+        if !table_replaced.is_empty() {
+            // UNWRAP: `entity` was checked to exist above.
+            let row = self.entities.location(entity).unwrap().row;
+            archetype
+                .replace_row(row, &table_replaced)
+                .map_err(|e| WorldError::AddComponentError { kind: e })?;
+        }
+        self.commands.append(&mut queued);
+        self.run_hooks(entity, HookKind::Add, &added);
 
         Ok(())
     }
 
     /// Removes components from an entity.
+    /// [Remove hooks][World::on_remove] run before the components are removed.
+    /// Operations queued by the hooks are kept only if removing succeeds.
     ///
     /// # Errors
     ///
+    /// - If `entity` doesn't exist.
     /// - If `T` can't produce a valid [`ArchetypeId`].
     /// - If `T` subtracted from the type of `entity` can't produce a valid [`ArchetypeId`].
+    /// - If `entity` doesn't have one of the sparse components of `T`.
     /// - If either the original [archetype][Archetype] containing `entity` or the destination [archetype][Archetype] is already borrowed in some way.
     pub fn remove_components<T: RemoveComponent>(
         &mut self,
         entity: Entity,
     ) -> Result<(), WorldError> {
-        let t_archetype_id = T::archetype_id()
-            .map_err(|e| WorldError::DeleteComponentError { kind: e })?;
-        let (archetype_id, old_archetype) = match self
-            .archetypes
-            .iter_mut()
-            .find(|(_, x)| x.check_entity(entity))
-        {
-            Some(val) => val,
-            None => {
-                return Err(WorldError::DeleteComponentError {
-                    kind: ArchetypeError::EntityNotFound,
-                });
+        let location = self.entities.location(entity).ok_or(
+            WorldError::DeleteComponentError {
+                kind: ArchetypeError::EntityNotFound,
             },
-        };
-        let new_archetype_id = archetype_id
-            .remove_from(t_archetype_id)
+        )?;
+        let mut component_ids = Vec::new();
+        T::component_ids(&mut component_ids);
+        let new_index = self
+            .remove_transition(
+                location.archetype,
+                &component_ids,
+                T::archetype_id,
+            )
             .map_err(|e| WorldError::DeleteComponentError { kind: e })?;
 
-        let (old_entity, map) = old_archetype
-            .cut_entity(entity)
+        let mut queued =
+            self.hook_commands(entity, HookKind::Remove, &component_ids);
+        T::remove_sparse(&mut self.sparse_sets, entity)
             .map_err(|e| WorldError::DeleteComponentError { kind: e })?;
-        old_archetype.bundle_count -= 1;
+        self.move_entity(entity, location, new_index)
+            .map_err(|e| WorldError::DeleteComponentError { kind: e })?;
+        self.commands.append(&mut queued);
 
-        let new_archetype = self.get_archetype_mut(&new_archetype_id);
-
-        if !new_archetype.are_all_columns_mutable() {
-            let (_, old_archetype) = self
-                .archetypes
-                .iter_mut()
-                .find(|(_, x)| x.check_entity(entity))
-                .unwrap();
-            for (type_id, (size, data)) in map.iter() {
-                old_archetype
-                    .add_raw(*size, *type_id, data.clone())
-                    .map_err(|e| WorldError::DeleteComponentError { kind: e })?
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        resources::Resources,
+        world::{
+            fetch::Mut,
+            filter::{Added, Changed, Or, With, Without},
+            prefab::Prefab,
+            query::QueryState,
+        },
+    };
+
+    #[test]
+    fn query_filters() {
+        let mut world = World::new();
+        let a = world.spawn((1_u32, 1.0_f32)).unwrap();
+        world.spawn((2_u32, 'b')).unwrap();
+        let c = world.spawn((3_u32, 1.0_f32, 'c')).unwrap();
+        world.spawn(true).unwrap();
+
+        let mut query = world.query_filtered::<u32, With<f32>>();
+        let mut found = query.iter().map(|(e, _)| e).collect::<Vec<_>>();
+        found.sort_by_key(|e| e.id());
+        assert_eq!(found, vec![a, c]);
+        drop(query);
+
+        let mut query = world.query_filtered::<u32, Without<char>>();
+        let found = query.iter().map(|(e, _)| e).collect::<Vec<_>>();
+        assert_eq!(found, vec![a]);
+        drop(query);
+
+        let mut query =
+            world.query_filtered::<(), Or<(With<f32>, With<char>)>>();
+        assert_eq!(query.iter().count(), 3);
+    }
+
+    #[test]
+    fn cached_queries() {
+        let mut world = World::new();
+        let mut state = QueryState::<u32, Without<char>>::new();
+        world.spawn((1_u32, 'a')).unwrap();
+        world.spawn(2_u32).unwrap();
+        assert_eq!(state.query(&world).unwrap().iter().count(), 1);
+
+        // Archetypes created after the last query are checked too.
+        world.spawn((3_u32, 1.0_f32)).unwrap();
+        world.spawn((4_u32, 'd')).unwrap();
+        world.spawn(5_u64).unwrap();
+        let mut query = state.query(&world).unwrap();
+        let mut values = query.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![2, 3]);
+        assert!(state.query(&world).is_ok());
+        drop(query);
+
+        // A state is tied to the world it was first used with.
+        let other_world = World::new();
+        assert_ne!(world.id(), other_world.id());
+        assert!(state.query(&other_world).is_err());
+        assert!(state.query(&world).is_ok());
+    }
+
+    #[test]
+    fn chunked_queries() {
+        let mut world = World::new();
+        world
+            .spawn_batch((0..5000_u32).map(|i| (i, i as f32)))
+            .unwrap();
+        world.spawn_batch((0..100_u32).map(|i| (i, 'c'))).unwrap();
+        world.spawn((7_u32, Burning(1))).unwrap();
+
+        let mut query = world.query::<(u32, Mut<f32>)>();
+        for (entities, (values, mut floats)) in query.iter_chunks().unwrap() {
+            assert_eq!(entities.len(), values.len());
+            for (float, value) in floats.iter_mut().zip(values) {
+                *float += *value as f32;
             }
-            return Err(WorldError::DeleteComponentError {
-                kind: ArchetypeError::ArchetypeColumnNotWritable,
-            });
         }
+        drop(query);
+
+        let sum = std::sync::atomic::AtomicU64::new(0);
+        world.query::<u32>().par_for_each(|_, value| {
+            sum.fetch_add(*value as u64, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(sum.into_inner(), 4999 * 2500 + 99 * 50 + 7);
 
-        for (type_id, (size, data)) in map.iter() {
-            if new_archetype_id.contains_single(type_id) {
-                new_archetype
-                    .add_raw(*size, *type_id, data.clone())
-                    .map_err(|e| WorldError::DeleteComponentError {
-                        kind: e,
-                    })?;
+        world.query::<(u32, Mut<f32>)>().par_for_each(
+            |_, (value, mut float)| {
+                *float -= *value as f32;
+            },
+        );
+        let mut query = world.query::<(u32, f32)>();
+        let unchanged = query
+            .iter()
+            .filter(|(_, (value, float))| **value as f32 == **float)
+            .count();
+        assert_eq!(unchanged, 5000);
+        drop(query);
+
+        // Sparse components and changes are tracked per entity.
+        assert!(world.query::<Burning>().iter_chunks().is_err());
+        assert!(
+            world
+                .query_filtered::<u32, Changed<u32>>()
+                .iter_chunks()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn query_optional() {
+        let mut world = World::new();
+        world.spawn((1_u32, 1.0_f32)).unwrap();
+        world.spawn(2_u32).unwrap();
+
+        let mut query = world.query::<(Mut<u32>, Option<f32>)>();
+        let mut sum = 0.0;
+        for (_, (mut value, optional)) in query.iter() {
+            *value += 1;
+            sum += optional.copied().unwrap_or(10.0);
+        }
+        assert_eq!(sum, 11.0);
+        drop(query);
+
+        let mut query = world.query::<u32>();
+        let mut values = query.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![2, 3]);
+    }
+
+    #[test]
+    fn change_detection() {
+        let mut world = World::new();
+        world.spawn(1_u32).unwrap();
+        let b = world.spawn(2_u32).unwrap();
+
+        // Emulates a system that previously ran at `last_run`.
+        let mut last_run = Tick::default();
+        let mut next_run = |world: &mut World| {
+            let this_run = world.increment_change_tick();
+            world.set_last_change_tick(last_run);
+            last_run = this_run;
+        };
+
+        next_run(&mut world);
+        assert_eq!(world.query_filtered::<u32, Added<u32>>().iter().count(), 2);
+
+        next_run(&mut world);
+        assert_eq!(world.query_filtered::<u32, Added<u32>>().iter().count(), 0);
+
+        // Another system mutates `b`.
+        world.increment_change_tick();
+        for (entity, mut value) in world.query::<Mut<u32>>().iter() {
+            if entity == b {
+                *value += 1;
             }
         }
-        new_archetype.bundle_count += 1;
-        new_archetype.moved_entity(old_entity);
 
-        Ok(())
+        next_run(&mut world);
+        let mut query = world.query_filtered::<u32, Changed<u32>>();
+        let changed = query.iter().collect::<Vec<_>>();
+        assert_eq!(changed, vec![(b, &3)]);
+        drop(query);
+
+        next_run(&mut world);
+        assert_eq!(
+            world.query_filtered::<u32, Changed<u32>>().iter().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn stale_entities() {
+        let mut world = World::new();
+        let a = world.spawn((1_u32, 1.0_f32)).unwrap();
+        let b = world.spawn((2_u32, 2.0_f32)).unwrap();
+        let c = world.spawn((3_u32, 3.0_f32)).unwrap();
+
+        // `c` is swapped into the row of `a`.
+        world.delete(a).unwrap();
+        assert!(!world.contains(a));
+        assert!(world.delete(a).is_err());
+
+        // The id of `a` is reused, but the old handle stays stale.
+        let d = world.spawn(4_u32).unwrap();
+        assert_eq!(d.id(), a.id());
+        assert_ne!(d, a);
+        assert!(world.add_components(a, 'a').is_err());
+
+        world.add_components(c, 'c').unwrap();
+        world.add_components(b, 'b').unwrap();
+        assert_eq!(world.entity_count(), 3);
+
+        let mut query = world.query::<(u32, char)>();
+        let mut found = query
+            .iter()
+            .map(|(e, (value, tag))| (e, *value, *tag))
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(_, value, _)| *value);
+        assert_eq!(found, vec![(b, 2, 'b'), (c, 3, 'c')]);
+    }
+
+    #[test]
+    fn entity_access() {
+        let mut world = World::new();
+        let a = world.spawn((1_u32, 1.0_f32)).unwrap();
+        let b = world.spawn(2_u32).unwrap();
+
+        assert!(world.has::<f32>(a));
+        assert!(!world.has::<f32>(b));
+        assert_eq!(*world.get::<u32>(b).unwrap(), 2);
+        assert!(world.get::<f32>(b).is_err());
+
+        {
+            let first = world.get::<u32>(a).unwrap();
+            let second = world.get::<u32>(a).unwrap();
+            assert_eq!(*first + *second, 2);
+            assert!(world.get_mut::<u32>(a).is_err());
+        }
+
+        *world.get_mut::<u32>(a).unwrap() += 10;
+        {
+            let value = world.get_mut::<u32>(a).unwrap();
+            assert!(world.get::<u32>(a).is_err());
+            assert_eq!(value.ticks().changed, world.change_tick());
+        }
+
+        let mut entity = world.entity_mut(b).unwrap();
+        entity.add_components('b').unwrap();
+        assert_eq!(*entity.get::<char>().unwrap(), 'b');
+        entity.delete().unwrap();
+        assert!(world.entity(b).is_err());
+        assert_eq!(*world.entity(a).unwrap().get::<u32>().unwrap(), 11);
+    }
+
+    #[derive(Clone)]
+    struct Tracked(std::sync::Arc<()>);
+    impl Component for Tracked {}
+
+    #[test]
+    fn non_copy_components() {
+        let counter = std::sync::Arc::new(());
+        let tracked = || Tracked(counter.clone());
+        let mut world = World::new();
+        let a = world
+            .spawn((tracked(), String::from("ship"), 1_u32))
+            .unwrap();
+        let b = world.spawn((tracked(), String::from("moon"))).unwrap();
+        world.add_components(b, 2_u32).unwrap();
+        assert_eq!(std::sync::Arc::strong_count(&counter), 3);
+        assert_eq!(world.get::<String>(b).unwrap().as_str(), "moon");
+        // Moving `b` to another archetype kept its value.
+        let moved = world.get::<Tracked>(b).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&moved.0, &counter));
+        drop(moved);
+
+        world.remove_components::<Tracked>(a).unwrap();
+        assert_eq!(std::sync::Arc::strong_count(&counter), 2);
+        world.delete(b).unwrap();
+        assert_eq!(std::sync::Arc::strong_count(&counter), 1);
+        assert_eq!(world.get::<String>(a).unwrap().as_str(), "ship");
+
+        world.add_components(a, tracked()).unwrap();
+        drop(world);
+        assert_eq!(std::sync::Arc::strong_count(&counter), 1);
+    }
+
+    #[derive(Component, Debug, PartialEq)]
+    #[component(storage = "sparse")]
+    struct Selected(u32);
+
+    #[test]
+    fn sparse_components() {
+        let mut world = World::new();
+        let a = world.spawn((1_u32, Selected(1))).unwrap();
+        let b = world.spawn(2_u32).unwrap();
+        world.spawn(3_u32).unwrap();
+        let archetypes = world.archetypes.len();
+
+        // Toggling a sparse component doesn't move the entity.
+        let location = world.entities.location(b).unwrap();
+        world.add_components(b, Selected(2)).unwrap();
+        assert_eq!(world.entities.location(b), Some(location));
+        assert_eq!(world.archetypes.len(), archetypes);
+        assert!(world.has::<Selected>(b));
+        assert_eq!(*world.get::<Selected>(b).unwrap(), Selected(2));
+
+        let mut query = world.query::<(Mut<u32>, Selected)>();
+        let mut found = query
+            .iter()
+            .map(|(e, (mut value, selected))| {
+                *value += 10;
+                (e, selected.0)
+            })
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(e, _)| e.id());
+        assert_eq!(found, vec![(a, 1), (b, 2)]);
+        drop(query);
+
+        world.remove_components::<Selected>(a).unwrap();
+        assert!(world.remove_components::<Selected>(a).is_err());
+        assert!(world.get::<Selected>(a).is_err());
+        let mut query = world.query_filtered::<u32, Without<Selected>>();
+        let mut values = query.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![3, 11]);
+        drop(query);
+
+        {
+            let _selected = world.get::<Selected>(b).unwrap();
+            assert!(Query::<Mut<Selected>>::from_world(&world).is_err());
+        }
+        // Sparse components can't be borrowed twice by a single query.
+        assert!(
+            Query::<(Mut<Selected>, Selected)>::from_world(&world).is_err()
+        );
+        assert!(
+            Query::<(Selected, Mut<Selected>)>::from_world(&world).is_err()
+        );
+        assert!(
+            Query::<(Mut<Selected>, Mut<Selected>)>::from_world(&world).is_err()
+        );
+        assert!(Query::<(Selected, Selected)>::from_world(&world).is_ok());
+        assert!(
+            Query::<Mut<Selected>, With<Selected>>::from_world(&world).is_ok()
+        );
+        world.delete(b).unwrap();
+        assert_eq!(world.query::<Option<Selected>>().iter().count(), 2);
+        assert_eq!(world.query::<Selected>().iter().count(), 0);
+    }
+
+    #[derive(Component, Debug)]
+    struct Asteroid;
+
+    #[test]
+    fn tag_components() {
+        let mut world = World::new();
+        let a = world.spawn((1_u32, Asteroid)).unwrap();
+        let b = world.spawn(Asteroid).unwrap();
+        world.spawn(3_u32).unwrap();
+        world.add_components(b, 2_u32).unwrap();
+
+        let location = world.entities.location(a).unwrap();
+        let archetype = &world.archetypes[location.archetype];
+        assert!(archetype.id().is_tag(&ComponentId::of::<Asteroid>()));
+        assert!(archetype.get::<Asteroid>().is_err());
+
+        let mut query = world.query_filtered::<u32, With<Asteroid>>();
+        let mut values = query.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![1, 2]);
+        drop(query);
+        assert_eq!(world.query::<(u32, Asteroid)>().iter().count(), 2);
+
+        assert!(world.get::<Asteroid>(a).is_ok());
+        assert!(world.get_mut::<Asteroid>(a).is_err());
+        world.remove_components::<Asteroid>(a).unwrap();
+        assert!(world.get::<Asteroid>(a).is_err());
+        assert_eq!(
+            world.query_filtered::<u32, Without<Asteroid>>().iter().count(),
+            2
+        );
+    }
+
+    #[derive(Component, Debug, Clone, PartialEq)]
+    #[component(clone)]
+    struct Debris(String);
+
+    #[derive(Component, Debug, Clone, PartialEq)]
+    #[component(storage = "sparse", clone)]
+    struct Burning(u32);
+
+    #[test]
+    fn clone_entities() {
+        let counter = std::sync::Arc::new(());
+        let mut world = World::new();
+        let a = world
+            .spawn((
+                Debris(String::from("hull")),
+                1_u32,
+                Asteroid,
+                Burning(3),
+                Tracked(counter.clone()),
+            ))
+            .unwrap();
+        let b = world.clone_entity(a).unwrap();
+        assert_eq!(world.get::<Debris>(b).unwrap().0, "hull");
+        assert_eq!(*world.get::<u32>(b).unwrap(), 1);
+        assert!(world.has::<Asteroid>(b));
+        assert_eq!(*world.get::<Burning>(b).unwrap(), Burning(3));
+        // Components that can't be cloned are skipped.
+        assert!(!world.has::<Tracked>(b));
+        assert_eq!(std::sync::Arc::strong_count(&counter), 2);
+
+        assert!(Prefab::new(Tracked(counter.clone())).is_err());
+        let prefab = Prefab::new((Debris(String::from("rock")), 2_u32))
+            .unwrap()
+            .with(Burning(1))
+            .unwrap();
+        let copies = world.spawn_prefabs(&prefab, 100).unwrap();
+        drop(prefab);
+        assert_eq!(world.query::<(Debris, Burning)>().iter().count(), 102);
+        world.delete(copies[0]).unwrap();
+        assert_eq!(world.get::<Debris>(copies[99]).unwrap().0, "rock");
+        assert_eq!(*world.get::<Burning>(copies[99]).unwrap(), Burning(1));
+        assert_eq!(std::sync::Arc::strong_count(&counter), 2);
+    }
+
+    #[test]
+    fn batch_spawning() {
+        let mut world = World::new();
+        let stars = world
+            .spawn_batch((0..1000_u32).map(|i| (i, Debris(i.to_string()))))
+            .unwrap();
+        assert_eq!(stars.len(), 1000);
+        assert_eq!(world.get::<Debris>(stars[500]).unwrap().0, "500");
+
+        // Bundles with components different from the first one are rejected
+        // before anything is spawned.
+        let bundles: Vec<Box<dyn Spawn>> =
+            vec![Box::new((1_u64, 1_i32)), Box::new(2_u64)];
+        assert!(world.spawn_batch(bundles).is_err());
+        assert_eq!(world.entity_count(), 1000);
+        assert_eq!(world.query::<(u64, i32)>().iter().count(), 0);
+
+        #[derive(Component, Debug)]
+        struct Comet;
+        let bundles: Vec<Box<dyn Spawn>> =
+            vec![Box::new((1_u64, Asteroid)), Box::new((2_u64, Comet))];
+        assert!(world.spawn_batch(bundles).is_err());
+        assert_eq!(world.entity_count(), 1000);
+        assert_eq!(world.query::<(u64, Asteroid)>().iter().count(), 0);
+        assert_eq!(world.query::<Comet>().iter().count(), 0);
+        let bundles: Vec<Box<dyn Spawn>> =
+            vec![Box::new((1_u64, Asteroid)), Box::new((2_u64, Asteroid))];
+        assert_eq!(world.spawn_batch(bundles).unwrap().len(), 2);
+
+        world.despawn_batch(stars[..500].iter().copied()).unwrap();
+        assert_eq!(world.entity_count(), 502);
+        let mut values =
+            world.query::<u32>().iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, (500..1000).collect::<Vec<_>>());
+        assert!(world.despawn_batch([stars[0]]).is_err());
+    }
+
+    #[test]
+    fn component_toggles() {
+        let mut world = World::new();
+        let a = world.spawn((1_u32, Debris("a".into()))).unwrap();
+        let b = world.spawn((2_u32, Debris("b".into()))).unwrap();
+        world.add_components(a, 1.0_f32).unwrap();
+        world.remove_components::<f32>(a).unwrap();
+        let archetypes = world.archetype_generation();
+
+        for i in 0..100 {
+            world.add_components(a, i as f32).unwrap();
+            assert_eq!(*world.get::<f32>(a).unwrap(), i as f32);
+            world.remove_components::<f32>(a).unwrap();
+        }
+        assert!(world.remove_components::<f32>(a).is_err());
+        assert_eq!(world.archetype_generation(), archetypes);
+        assert_eq!(world.get::<Debris>(a).unwrap().0, "a");
+        assert_eq!(*world.get::<u32>(b).unwrap(), 2);
+        assert_eq!(world.get::<Debris>(b).unwrap().0, "b");
+    }
+
+    #[test]
+    fn component_hooks() {
+        let mut world = World::new();
+        let mut resources = Resources::new();
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let add_log = log.clone();
+        world.on_add::<u32>(move |_, value, _| {
+            add_log.lock().unwrap().push(("add", *value));
+        });
+        let replace_log = log.clone();
+        world.on_replace::<Burning>(move |_, burning, _| {
+            replace_log.lock().unwrap().push(("replace", burning.0));
+        });
+        let remove_log = log.clone();
+        world.on_remove::<Burning>(move |_, burning, _| {
+            remove_log.lock().unwrap().push(("remove", burning.0));
+        });
+        world.on_remove::<Asteroid>(|_, _, commands| {
+            commands.spawn(Debris(String::from("dust")));
+        });
+
+        let a = world.spawn((1_u32, Burning(1), Asteroid)).unwrap();
+        world.spawn(2_u64).unwrap();
+        world.add_components(a, Burning(2)).unwrap();
+        world.add_components(a, 3_u64).unwrap();
+        world.remove_components::<Burning>(a).unwrap();
+        world.add_components(a, Burning(4)).unwrap();
+        world.delete(a).unwrap();
+        assert_eq!(*log.lock().unwrap(), vec![
+            ("add", 1),
+            ("replace", 1),
+            ("remove", 2),
+            ("remove", 4),
+        ]);
+
+        // Operations queued by hooks are applied with other commands.
+        assert_eq!(world.query::<Debris>().iter().count(), 0);
+        Commands::new().apply(&mut world, &mut resources).unwrap();
+        assert_eq!(world.query::<Debris>().iter().count(), 1);
+        assert!(world.take_commands().is_empty());
+
+        // Table components are replaced too.
+        log.lock().unwrap().clear();
+        let replace_log = log.clone();
+        world.on_replace::<u32>(move |_, value, _| {
+            replace_log.lock().unwrap().push(("replace", *value));
+        });
+        let b = world.spawn((5_u32, Asteroid)).unwrap();
+        let c = world.spawn((7_u32, Asteroid)).unwrap();
+        world.add_components(b, 6_u32).unwrap();
+        assert_eq!(*world.get::<u32>(b).unwrap(), 6);
+        world.add_components(b, (8_u32, 'b')).unwrap();
+        assert_eq!(*world.get::<u32>(b).unwrap(), 8);
+        assert_eq!(*world.get::<char>(b).unwrap(), 'b');
+        assert_eq!(*world.get::<u32>(c).unwrap(), 7);
+        assert_eq!(*log.lock().unwrap(), vec![
+            ("add", 5),
+            ("add", 7),
+            ("replace", 5),
+            ("replace", 6),
+        ]);
+
+        // Hooks of failed changes don't queue anything.
+        assert!(world.remove_components::<(Asteroid, Burning)>(b).is_err());
+        assert!(world.has::<Asteroid>(b));
+        assert!(world.take_commands().is_empty());
     }
 }
@@ -0,0 +1,99 @@
+//! Module responsible for spawning many copies of a template entity.
+
+use crate::ecs::{
+    entity::Entity,
+    world::{
+        World, WorldError, add_component::AddComponent,
+        archetype::ArchetypeError, spawn::Spawn,
+    },
+};
+
+/// Template of an entity that can be spawned any number of times.
+/// Copies are made by cloning the template components straight into
+/// archetype columns, so every component of a prefab has to be cloneable
+/// (see [`Component`][super::component::Component]).
+#[derive(Debug)]
+pub struct Prefab {
+    /// Stores the template entity.
+    world: World,
+    template: Entity,
+}
+
+impl Prefab {
+    /// Creates a prefab whose copies get clones of components in `bundle`.
+    ///
+    /// # Errors
+    ///
+    /// - If `bundle` can't produce a valid [`ArchetypeId`][super::archetype::ArchetypeId].
+    /// - If any component of `bundle` can't be cloned.
+    pub fn new<T: Spawn>(bundle: T) -> Result<Prefab, WorldError> {
+        let mut world = World::new();
+        let template = world.spawn(bundle)?;
+        let prefab = Prefab { world, template };
+        prefab.check_cloneable()?;
+        Ok(prefab)
+    }
+
+    /// Adds components in `bundle` to the template.
+    ///
+    /// # Errors
+    ///
+    /// - If the template already has some of the table components of `bundle`.
+    /// - If any component of `bundle` can't be cloned.
+    pub fn with<T: AddComponent>(
+        mut self,
+        bundle: T,
+    ) -> Result<Prefab, WorldError> {
+        self.world.add_components(self.template, bundle)?;
+        self.check_cloneable()?;
+        Ok(self)
+    }
+
+    /// Checks if all components of the template can be cloned.
+    fn check_cloneable(&self) -> Result<(), WorldError> {
+        let location = self.world.entities.location(self.template).unwrap();
+        if !self.world.archetypes[location.archetype].is_cloneable()
+            || !self.world.sparse_sets.is_entity_cloneable(self.template)
+        {
+            return Err(WorldError::CloneError {
+                kind: ArchetypeError::ComponentNotCloneable,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl World {
+    /// Spawns a new copy of `prefab`.
+    ///
+    /// # Errors
+    ///
+    /// - If the destination [archetype][super::archetype::Archetype] is
+    ///   already borrowed in some way.
+    pub fn spawn_prefab(
+        &mut self,
+        prefab: &Prefab,
+    ) -> Result<Entity, WorldError> {
+        let cloned = prefab
+            .world
+            .clone_components(prefab.template, self.change_tick)
+            .map_err(|e| WorldError::CloneError { kind: e })?;
+        self.spawn_raw(cloned)
+            .map_err(|e| WorldError::SpawnError { kind: e })
+    }
+
+    /// Spawns `count` new copies of `prefab`.
+    ///
+    /// # Errors
+    ///
+    /// - If the destination [archetype][super::archetype::Archetype] is
+    ///   already borrowed in some way. Copies spawned before the error are
+    ///   kept.
+    pub fn spawn_prefabs(
+        &mut self,
+        prefab: &Prefab,
+        count: usize,
+    ) -> Result<Vec<Entity>, WorldError> {
+        (0..count).map(|_| self.spawn_prefab(prefab)).collect()
+    }
+}
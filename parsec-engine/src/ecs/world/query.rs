@@ -1,50 +1,198 @@
 //! Module responsible for querying entities.
 
-use std::marker::PhantomData;
+use std::{
+    marker::PhantomData,
+    ops::Range,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use crate::{
     ecs::{
         entity::Entity,
-        world::{World, fetch::Fetch},
+        system::access::SystemAccess,
+        world::{
+            World, WorldError,
+            archetype::{ArchetypeError, BorrowingStats},
+            change_detection::SystemTicks,
+            fetch::Fetch,
+            filter::Filter,
+            sparse_set::SparseSets,
+        },
     },
     error::ParsecError,
+    utils::{IdType, thread_pool::ThreadPool},
 };
 
-/// Stores the data needed to query entities from [`World`][crate::ecs::world::World].
-pub struct Query<T: Fetch + Fetch> {
-    fetches: Vec<T::State>,
-    entities: Vec<Vec<Entity>>,
+/// Caches the archetypes matching a [`Query`], so that creating it again
+/// doesn't need to look through all archetypes of the [`World`].
+/// Archetypes created since the last query are checked incrementally using
+/// [`World::archetype_generation`].
+///
+/// A state has to be used with a single [`World`].
+pub struct QueryState<T: Fetch, F: Filter = ()> {
+    /// Id of the world the state was first used with.
+    world_id: Option<IdType>,
+    /// Indices of the matching archetypes.
+    archetypes: Vec<usize>,
+    /// Archetype generation of the world when `archetypes` were last updated.
+    generation: usize,
+    /// Components accessed by `T` and `F`.
+    access: SystemAccess,
+    /// Set when `T` borrows a component mutably more than once.
+    aliased: bool,
+    _marker: PhantomData<fn() -> (T, F)>,
 }
 
-impl<T: Fetch> Query<T> {
-    pub fn from_world(world: &World) -> Result<Self, ParsecError> {
-        let archetype_id = T::archetype_id()?;
-        let archetypes = world
+impl<T: Fetch, F: Filter> Default for QueryState<T, F> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<T: Fetch, F: Filter> QueryState<T, F> {
+    pub fn new() -> QueryState<T, F> {
+        let mut access = SystemAccess::new();
+        T::access(&mut access);
+        // Filters don't borrow component data, so only fetches can alias.
+        let aliased = access.is_aliased();
+        F::access(&mut access);
+        QueryState {
+            world_id: None,
+            archetypes: Vec::new(),
+            generation: 0,
+            access,
+            aliased,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Checks archetypes of `world` created since the last update.
+    ///
+    /// # Panics
+    ///
+    /// - If the state was already used with another world.
+    pub fn update(&mut self, world: &World) {
+        let world_id = *self.world_id.get_or_insert(world.id());
+        assert_eq!(world_id, world.id(), "query state used with another world");
+        let generation = world.archetype_generation();
+        for (index, archetype) in
+            world.archetypes.iter().enumerate().skip(self.generation)
+        {
+            if T::matches(archetype.id()) && F::matches(archetype.id()) {
+                self.archetypes.push(index);
+            }
+        }
+        self.generation = generation;
+    }
+
+    /// Creates a query over `world`.
+    ///
+    /// # Errors
+    ///
+    /// - If any component accessed by `T` or `F` is borrowed in a
+    ///   conflicting way.
+    /// - If `T` fetches a component mutably and also fetches it again.
+    /// - If the state was already used with another world.
+    pub fn query<'w>(
+        &mut self,
+        world: &'w World,
+    ) -> Result<Query<'w, T, F>, ParsecError> {
+        self.query_with_ticks(world, world.system_ticks())
+    }
+
+    /// Creates a query detecting changes relative to `ticks` instead of the
+    /// ticks stored in `world`. Used by systems running in parallel.
+    ///
+    /// # Errors
+    ///
+    /// - If any component accessed by `T` or `F` is borrowed in a
+    ///   conflicting way.
+    /// - If `T` fetches a component mutably and also fetches it again.
+    /// - If the state was already used with another world.
+    pub fn query_with_ticks<'w>(
+        &mut self,
+        world: &'w World,
+        ticks: SystemTicks,
+    ) -> Result<Query<'w, T, F>, ParsecError> {
+        // Table columns are rejected when borrowed, but sparse sets are only
+        // locked once per query.
+        if self.aliased {
+            return Err(ArchetypeError::ArchetypeColumnNotWritable.into());
+        }
+        if self.world_id.is_some_and(|id| id != world.id()) {
+            return Err(WorldError::QueryStateWorldMismatch.into());
+        }
+        self.update(world);
+        let sparse_sets = &world.sparse_sets;
+        let sparse_locks = sparse_sets.lock(&self.access)?;
+
+        let archetypes = self
             .archetypes
             .iter()
-            .filter_map(|(id, arch)| {
-                if id.contains(&archetype_id) {
-                    Some(arch)
-                } else {
-                    None
-                }
-            })
+            .map(|index| &world.archetypes[*index])
             .collect::<Vec<_>>();
-        let fetches = archetypes
-            .iter()
-            .map(|arch| T::prepare(arch))
-            .collect::<Result<Vec<_>, _>>()?;
+        let prepare = || {
+            let filters = archetypes
+                .iter()
+                .map(|arch| F::prepare(arch, sparse_sets, ticks))
+                .collect::<Result<Vec<_>, _>>()?;
+            let fetches = archetypes
+                .iter()
+                .map(|arch| T::prepare(arch, sparse_sets, ticks))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok::<_, ArchetypeError>((filters, fetches))
+        };
+        let (filters, fetches) = match prepare() {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                SparseSets::release(sparse_locks);
+                return Err(e.into());
+            },
+        };
         let entities = archetypes
             .iter()
-            .map(|arch| arch.entities.clone())
+            .map(|arch| arch.entities.as_slice())
             .collect();
-        Ok(Query { fetches, entities })
+        Ok(Query {
+            fetches,
+            filters,
+            entities,
+            sparse_locks,
+        })
+    }
+}
+
+/// Stores the data needed to query entities from [`World`][crate::ecs::world::World].
+/// Only entities matching both `T` and the [filter][Filter] `F` are returned.
+pub struct Query<'w, T: Fetch, F: Filter = ()> {
+    fetches: Vec<T::State>,
+    filters: Vec<F::State>,
+    entities: Vec<&'w [Entity]>,
+    /// Locks on the sparse sets accessed by `T` and `F`.
+    sparse_locks: Vec<Arc<RwLock<BorrowingStats>>>,
+}
+
+impl<'w, T: Fetch, F: Filter> Query<'w, T, F> {
+    /// Creates a query looking through all archetypes of `world`.
+    /// Use [`QueryState`] to create the same query repeatedly.
+    pub fn from_world(world: &'w World) -> Result<Self, ParsecError> {
+        QueryState::new().query(world)
+    }
+
+    /// Creates a query detecting changes relative to `ticks` instead of the
+    /// ticks stored in `world`.
+    pub fn from_world_with_ticks(
+        world: &'w World,
+        ticks: SystemTicks,
+    ) -> Result<Self, ParsecError> {
+        QueryState::new().query_with_ticks(world, ticks)
     }
 
     /// Creates an iterator over [`self`].
-    pub fn iter<'a>(&'a mut self) -> QueryIter<'a, T> {
-        let inside_len = match self.fetches.first() {
-            Some(first_fetch) => T::len(first_fetch),
+    pub fn iter<'a>(&'a mut self) -> QueryIter<'a, 'w, T, F> {
+        let inside_len = match self.entities.first() {
+            Some(first_entities) => first_entities.len(),
             None => 0,
         };
         QueryIter {
@@ -58,44 +206,167 @@ impl<T: Fetch> Query<T> {
     }
 }
 
-impl<T: Fetch> Drop for Query<T> {
+impl<'w, T: Fetch + 'static, F: Filter> Query<'w, T, F> {
+    /// Gets the fetched components of every matching archetype as slices,
+    /// along with the entities owning them.
+    /// Mutably fetched slices mark all components as changed when
+    /// dereferenced mutably.
+    ///
+    /// Only data stored in archetype columns can be chunked. Queries fetching
+    /// sparse components, filtering by them or filtering by changes need to
+    /// check single rows, so they have to use [`Query::iter`] or
+    /// [`Query::par_for_each`] instead.
+    ///
+    /// # Errors
+    ///
+    /// - If `T` fetches sparse components, which aren't stored contiguously.
+    /// - If `F` filters single rows, like [`Changed`][super::filter::Changed].
+    pub fn iter_chunks<'a>(
+        &'a mut self,
+    ) -> Result<
+        impl Iterator<Item = (&'w [Entity], T::Chunk<'a>)> + 'a,
+        ParsecError,
+    > {
+        if !F::is_archetypal() {
+            return Err(ArchetypeError::QueryNotChunkable.into());
+        }
+        let mut chunks = Vec::with_capacity(self.entities.len());
+        for (fetch, entities) in self.fetches.iter().zip(self.entities.iter()) {
+            if entities.is_empty() {
+                continue;
+            }
+            let chunk = T::get_chunk(fetch.clone(), entities.len())
+                .ok_or(ArchetypeError::QueryNotChunkable)?;
+            chunks.push((*entities, chunk));
+        }
+        Ok(chunks.into_iter())
+    }
+
+    /// Calls `f` for every matching entity, splitting rows of the matching
+    /// archetypes across the current thread and workers of the
+    /// [current][ThreadPool::current] pool. The query holds its locks until
+    /// all of them finish.
+    pub fn par_for_each(
+        &mut self,
+        f: impl for<'a> Fn(Entity, T::Item<'a>) + Sync,
+    ) {
+        let pool = ThreadPool::current();
+        let threads = pool.threads() + 1;
+        let total = self.entities.iter().map(|e| e.len()).sum::<usize>();
+        let batch_size = total.div_ceil(threads).max(MIN_BATCH_SIZE);
+        let batches = self
+            .entities
+            .iter()
+            .enumerate()
+            .flat_map(|(archetype, entities)| {
+                (0..entities.len()).step_by(batch_size).map(move |start| {
+                    let end = (start + batch_size).min(entities.len());
+                    (archetype, start..end)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let query = SharedQuery(self);
+        let next = AtomicUsize::new(0);
+        let run = || {
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some((archetype, rows)) = batches.get(index) else {
+                    return;
+                };
+                query.for_each_in(*archetype, rows.clone(), &f);
+            }
+        };
+        let workers = threads.min(batches.len());
+        pool.scope(|scope| {
+            for _ in 1..workers {
+                scope.spawn(run);
+            }
+            run();
+        });
+    }
+}
+
+/// Smallest number of rows processed by a single thread of
+/// [`Query::par_for_each`].
+const MIN_BATCH_SIZE: usize = 1024;
+
+/// Shares a [`Query`] between threads of [`Query::par_for_each`].
+struct SharedQuery<'q, 'w, T: Fetch, F: Filter>(&'q Query<'w, T, F>);
+
+// SAFETY: threads access disjoint rows, and all fetched components are
+// `Send + Sync`.
+unsafe impl<T: Fetch, F: Filter> Sync for SharedQuery<'_, '_, T, F> {}
+
+impl<T: Fetch + 'static, F: Filter> SharedQuery<'_, '_, T, F> {
+    /// Calls `f` for every entity in `rows` of the archetype at index
+    /// `archetype` of the query that passes the filter.
+    fn for_each_in(
+        &self,
+        archetype: usize,
+        rows: Range<usize>,
+        f: &impl for<'a> Fn(Entity, T::Item<'a>),
+    ) {
+        let fetch = &self.0.fetches[archetype];
+        let filter = &self.0.filters[archetype];
+        let entities = self.0.entities[archetype];
+        for row in rows {
+            if F::filter_row(filter, row) && T::contains_row(fetch, row) {
+                f(entities[row], T::get(fetch.clone(), row));
+            }
+        }
+    }
+}
+
+impl<T: Fetch, F: Filter> Drop for Query<'_, T, F> {
     fn drop(&mut self) {
         for fetch in self.fetches.iter_mut() {
             T::release(fetch.clone()).unwrap();
         }
+        SparseSets::release(std::mem::take(&mut self.sparse_locks));
     }
 }
 
 /// Iterator created from [`Query`]
-pub struct QueryIter<'a, T: Fetch + 'static> {
+pub struct QueryIter<'a, 'w, T: Fetch + 'static, F: Filter = ()> {
     outside_len: usize,
     inside_len: usize,
     outside_idx: usize,
     inside_idx: usize,
-    query: &'a mut Query<T>,
+    query: &'a mut Query<'w, T, F>,
     _marker: PhantomData<&'a T>,
 }
 
-impl<'a, T: Fetch + 'static> Iterator for QueryIter<'a, T> {
+impl<'a, T: Fetch + 'static, F: Filter> Iterator for QueryIter<'a, '_, T, F> {
     type Item = (Entity, T::Item<'a>);
     fn next(&mut self) -> Option<Self::Item> {
-        if self.outside_idx >= self.outside_len {
-            return None;
-        }
-        while self.inside_idx >= self.inside_len {
-            self.outside_idx += 1;
+        loop {
             if self.outside_idx >= self.outside_len {
                 return None;
             }
-            self.inside_idx = 0;
-            self.inside_len = T::len(&self.query.fetches[self.outside_idx]);
+            while self.inside_idx >= self.inside_len {
+                self.outside_idx += 1;
+                if self.outside_idx >= self.outside_len {
+                    return None;
+                }
+                self.inside_idx = 0;
+                self.inside_len = self.query.entities[self.outside_idx].len();
+            }
+            let inside_idx = self.inside_idx;
+            self.inside_idx += 1;
+            if !F::filter_row(&self.query.filters[self.outside_idx], inside_idx)
+                || !T::contains_row(
+                    &self.query.fetches[self.outside_idx],
+                    inside_idx,
+                )
+            {
+                continue;
+            }
+            let state = self.query.fetches[self.outside_idx].clone();
+            return Some((
+                self.query.entities[self.outside_idx][inside_idx],
+                T::get(state, inside_idx),
+            ));
         }
-        let state = self.query.fetches[self.outside_idx].clone();
-        let inside_idx = self.inside_idx;
-        self.inside_idx += 1;
-        Some((
-            self.query.entities[self.outside_idx][inside_idx],
-            T::get(state, inside_idx),
-        ))
     }
 }
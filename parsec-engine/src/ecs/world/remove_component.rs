@@ -1,11 +1,59 @@
 //! Module responsible for deleting components from entites.
 
-use crate::ecs::world::archetype::{ArchetypeError, ArchetypeId};
+use parsec_engine_macros::{impl_remove_component, multiple_tuples};
+
+use crate::ecs::{
+    entity::Entity,
+    world::{
+        archetype::{ArchetypeError, ArchetypeId, is_tag},
+        component::{Component, ComponentId},
+        sparse_set::{SparseSets, is_sparse},
+    },
+};
 
 /// Represents a type that can be used to remove components from an entity.
-/// It is automatically implemented for all types implementing [`Spawn`].
+/// It is automatically implemented for all types implementing [`Component`]
+/// and all tuples containging up to 16 values that implement [`RemoveComponent`].
 pub trait RemoveComponent: Send + Sync + 'static {
     fn archetype_id() -> Result<ArchetypeId, ArchetypeError>
     where
         Self: Sized;
+    /// Appends ids of all removed components to `ids`, including sparse
+    /// components and tags.
+    fn component_ids(ids: &mut Vec<ComponentId>)
+    where
+        Self: Sized;
+    /// Drops the sparse components of the bundle belonging to `entity`.
+    fn remove_sparse(
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+    ) -> Result<(), ArchetypeError>
+    where
+        Self: Sized;
+}
+
+impl<T: Component> RemoveComponent for T {
+    fn archetype_id() -> Result<ArchetypeId, ArchetypeError> {
+        if is_sparse::<T>() {
+            return ArchetypeId::new(Vec::new());
+        }
+        if is_tag::<T>() {
+            return Ok(ArchetypeId::tag(ComponentId::of::<T>()));
+        }
+        ArchetypeId::new(vec![ComponentId::of::<T>()])
+    }
+    fn component_ids(ids: &mut Vec<ComponentId>) {
+        ids.push(ComponentId::of::<T>());
+    }
+    fn remove_sparse(
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+    ) -> Result<(), ArchetypeError> {
+        if is_sparse::<T>() && !sparse_sets.remove::<T>(entity)? {
+            return Err(ArchetypeError::ArchetypeIdDoesntContainThisType);
+        }
+        Ok(())
+    }
 }
+
+multiple_tuples!(impl_remove_component, 16);
@@ -0,0 +1,409 @@
+//! Module responsible for storing components outside of archetypes.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::ecs::{
+    entity::Entity,
+    system::access::SystemAccess,
+    world::{
+        archetype::{
+            Archetype, ArchetypeColumn, ArchetypeError, BorrowingStats,
+            RawComponent,
+        },
+        change_detection::{ComponentTicks, Tick},
+        component::{Component, ComponentId, StorageType},
+    },
+};
+
+/// Checks if components of type `T` are stored in [`SparseSets`].
+pub fn is_sparse<T: Component>() -> bool {
+    T::STORAGE == StorageType::SparseSet
+}
+
+/// Stores components of a single type, indexed by entity id.
+#[derive(Debug)]
+pub struct SparseSet {
+    /// Index into `entities` and `components` for every entity id.
+    sparse: Vec<Option<usize>>,
+    /// Entities owning the stored components.
+    entities: Vec<Entity>,
+    /// Components, in the same order as `entities`.
+    components: ArchetypeColumn,
+}
+
+impl SparseSet {
+    fn new() -> SparseSet {
+        SparseSet {
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            components: ArchetypeColumn::new(),
+        }
+    }
+
+    /// Gets the index of the component belonging to `entity`.
+    fn index(&self, entity: Entity) -> Option<usize> {
+        let index = (*self.sparse.get(entity.id() as usize)?)?;
+        (self.entities[index] == entity).then_some(index)
+    }
+
+    /// Adds a component inserted at `tick` to `entity`, replacing the
+    /// previous one.
+    ///
+    /// # Errors
+    ///
+    /// - If the set is borrowed in some way.
+    fn insert<T: Component>(
+        &mut self,
+        entity: Entity,
+        value: T,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        self.remove(entity)?;
+        self.components.push(value, tick)?;
+        self.push_entity(entity);
+        Ok(())
+    }
+
+    /// Adds a component cut out or cloned from elsewhere to `entity`,
+    /// replacing the previous one.
+    ///
+    /// # Errors
+    ///
+    /// - If the set is borrowed in some way.
+    fn insert_raw(
+        &mut self,
+        entity: Entity,
+        component: RawComponent,
+    ) -> Result<(), ArchetypeError> {
+        self.remove(entity)?;
+        self.components.push_raw(component)?;
+        self.push_entity(entity);
+        Ok(())
+    }
+
+    /// Records that the last component belongs to `entity`.
+    fn push_entity(&mut self, entity: Entity) {
+        let id = entity.id() as usize;
+        if self.sparse.len() <= id {
+            self.sparse.resize(id + 1, None);
+        }
+        self.sparse[id] = Some(self.entities.len());
+        self.entities.push(entity);
+    }
+
+    /// Drops the component belonging to `entity`.
+    /// Returns `false` if `entity` didn't have one.
+    ///
+    /// # Errors
+    ///
+    /// - If the set is borrowed in some way.
+    fn remove(&mut self, entity: Entity) -> Result<bool, ArchetypeError> {
+        let Some(index) = self.index(entity) else {
+            return Ok(false);
+        };
+        self.components.swap_remove(index)?;
+        self.entities.swap_remove(index);
+        self.sparse[entity.id() as usize] = None;
+        if let Some(moved) = self.entities.get(index) {
+            self.sparse[moved.id() as usize] = Some(index);
+        }
+        Ok(true)
+    }
+}
+
+/// Stores components with [`StorageType::SparseSet`]. Adding or removing them
+/// doesn't move entities between archetypes.
+#[derive(Debug, Default)]
+pub struct SparseSets {
+    /// Sets are boxed so that views created by queries stay valid when new
+    /// sets are added.
+    sets: HashMap<ComponentId, Box<SparseSet>>,
+}
+
+impl SparseSets {
+    pub fn new() -> SparseSets { SparseSets::default() }
+
+    /// Adds a component inserted at `tick` to `entity`, replacing the
+    /// previous one.
+    ///
+    /// # Errors
+    ///
+    /// - If the set storing `T` components is borrowed in some way.
+    pub fn insert<T: Component>(
+        &mut self,
+        entity: Entity,
+        value: T,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        self.sets
+            .entry(ComponentId::of::<T>())
+            .or_insert_with(|| Box::new(SparseSet::new()))
+            .insert(entity, value, tick)
+    }
+
+    /// Adds a component with id `component_id` cut out or cloned from
+    /// elsewhere to `entity`, replacing the previous one.
+    ///
+    /// # Errors
+    ///
+    /// - If the set storing `component_id` components is borrowed in some way.
+    pub fn insert_raw(
+        &mut self,
+        component_id: ComponentId,
+        entity: Entity,
+        component: RawComponent,
+    ) -> Result<(), ArchetypeError> {
+        self.sets
+            .entry(component_id)
+            .or_insert_with(|| Box::new(SparseSet::new()))
+            .insert_raw(entity, component)
+    }
+
+    /// Drops the component of type `T` belonging to `entity`.
+    /// Returns `false` if `entity` didn't have one.
+    ///
+    /// # Errors
+    ///
+    /// - If the set storing `T` components is borrowed in some way.
+    pub fn remove<T: Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<bool, ArchetypeError> {
+        match self.sets.get_mut(&ComponentId::of::<T>()) {
+            Some(set) => set.remove(entity),
+            None => Ok(false),
+        }
+    }
+
+    /// Checks if all components of `entity` can be removed.
+    pub fn is_entity_mutable(&self, entity: Entity) -> bool {
+        self.sets.values().all(|set| {
+            set.index(entity).is_none() || set.components.is_mutable()
+        })
+    }
+
+    /// Checks if all components of `entity` can be cloned.
+    pub fn is_entity_cloneable(&self, entity: Entity) -> bool {
+        self.sets.values().all(|set| {
+            set.index(entity).is_none() || set.components.is_cloneable()
+        })
+    }
+
+    /// Clones all cloneable components belonging to `entity`, marking the
+    /// clones as added at `tick`.
+    ///
+    /// # Errors
+    ///
+    /// - If any set storing a component of `entity` is borrowed mutably.
+    pub fn clone_entity(
+        &self,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<Vec<(ComponentId, RawComponent)>, ArchetypeError> {
+        let mut ret = Vec::new();
+        for (component_id, set) in self.sets.iter() {
+            if !set.components.is_cloneable() {
+                continue;
+            }
+            if let Some(index) = set.index(entity) {
+                ret.push((*component_id, set.components.get_raw(index, tick)?));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Drops all components belonging to `entity`.
+    ///
+    /// # Errors
+    ///
+    /// - If any set storing a component of `entity` is borrowed in some way.
+    pub fn remove_entity(
+        &mut self,
+        entity: Entity,
+    ) -> Result<(), ArchetypeError> {
+        if !self.is_entity_mutable(entity) {
+            return Err(ArchetypeError::ArchetypeColumnNotWritable);
+        }
+        for set in self.sets.values_mut() {
+            set.remove(entity)?;
+        }
+        Ok(())
+    }
+
+    /// Checks if `entity` has a component of type `T`.
+    pub fn contains<T: Component>(&self, entity: Entity) -> bool {
+        self.sets
+            .get(&ComponentId::of::<T>())
+            .is_some_and(|set| set.index(entity).is_some())
+    }
+
+    /// Gets a pointer to the component with id `component_id` belonging to
+    /// `entity`.
+    pub fn ptr(
+        &self,
+        component_id: ComponentId,
+        entity: Entity,
+    ) -> Option<*const u8> {
+        let set = self.sets.get(&component_id)?;
+        let index = set.index(entity)?;
+        Some(set.components.ptr_at(index) as *const u8)
+    }
+
+    /// Iterates over ids of all components belonging to `entity`.
+    pub fn component_ids(
+        &self,
+        entity: Entity,
+    ) -> impl Iterator<Item = ComponentId> + '_ {
+        self.sets
+            .iter()
+            .filter(move |(_, set)| set.index(entity).is_some())
+            .map(|(component_id, _)| *component_id)
+    }
+
+    /// Gets the component of type `T` belonging to `entity`, borrowing its set.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't have a component of type `T`.
+    /// - If the set storing `T` components is borrowed mutably.
+    pub fn get<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<(*const T, Arc<RwLock<BorrowingStats>>), ArchetypeError> {
+        let set = self
+            .sets
+            .get(&ComponentId::of::<T>())
+            .ok_or(ArchetypeError::TypeNotFound)?;
+        let index = set.index(entity).ok_or(ArchetypeError::TypeNotFound)?;
+        let access = set.components.lock()?;
+        Ok((set.components.ptr_at(index) as *const T, access))
+    }
+
+    /// Gets the component of type `T` belonging to `entity` and its change
+    /// ticks, borrowing its set mutably.
+    ///
+    /// # Errors
+    ///
+    /// - If `entity` doesn't have a component of type `T`.
+    /// - If the set storing `T` components is already borrowed.
+    pub fn get_mut<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<
+        (*mut T, *mut ComponentTicks, Arc<RwLock<BorrowingStats>>),
+        ArchetypeError,
+    > {
+        let set = self
+            .sets
+            .get(&ComponentId::of::<T>())
+            .ok_or(ArchetypeError::TypeNotFound)?;
+        let index = set.index(entity).ok_or(ArchetypeError::TypeNotFound)?;
+        let access = set.components.lock_mut()?;
+        Ok((
+            set.components.ptr_at(index) as *mut T,
+            set.components.ticks_at(index),
+            access,
+        ))
+    }
+
+    /// Borrows every set accessed by `access`, so that queries can use
+    /// [`SparseView`]s until the returned locks are released.
+    ///
+    /// # Errors
+    ///
+    /// - If any of the sets is already borrowed in a conflicting way.
+    pub fn lock(
+        &self,
+        access: &SystemAccess,
+    ) -> Result<Vec<Arc<RwLock<BorrowingStats>>>, ArchetypeError> {
+        let mut locks = Vec::new();
+        for (component_id, set) in self.sets.iter() {
+            let lock = if access.writes_component(component_id) {
+                set.components.lock_mut()
+            } else if access.reads_component(component_id) {
+                set.components.lock()
+            } else {
+                continue;
+            };
+            match lock {
+                Ok(lock) => locks.push(lock),
+                Err(e) => {
+                    Self::release(locks);
+                    return Err(e);
+                },
+            }
+        }
+        Ok(locks)
+    }
+
+    /// Releases locks returned by [`SparseSets::lock`].
+    pub fn release(locks: Vec<Arc<RwLock<BorrowingStats>>>) {
+        for lock in locks {
+            lock.write().unwrap().release_lock();
+        }
+    }
+
+    /// Creates a view of components with id `component_id` belonging to
+    /// entities stored in `archetype`.
+    pub fn view(
+        &self,
+        component_id: ComponentId,
+        archetype: &Archetype,
+    ) -> SparseView {
+        SparseView {
+            set: self
+                .sets
+                .get(&component_id)
+                .map(|set| &**set as *const SparseSet),
+            entities: archetype.entities.as_slice(),
+        }
+    }
+}
+
+/// Looks up components of entities stored in an archetype inside a sparse set.
+/// Only valid while the set is locked with [`SparseSets::lock`] and the
+/// archetype is borrowed, like during a query.
+#[derive(Debug, Clone)]
+pub struct SparseView {
+    /// `None` if no component of the type was ever added.
+    set: Option<*const SparseSet>,
+    /// Entities stored in the archetype, indexed by row.
+    entities: *const [Entity],
+}
+
+impl SparseView {
+    /// Gets the index of the component of the entity in `row` inside the set.
+    fn index(&self, row: usize) -> Option<usize> {
+        let set = unsafe { &*self.set? };
+        set.index(unsafe { (&*self.entities)[row] })
+    }
+
+    /// Checks if the entity in `row` has a component.
+    pub fn contains(&self, row: usize) -> bool { self.index(row).is_some() }
+
+    /// Gets the change ticks of the component of the entity in `row`.
+    pub fn ticks(&self, row: usize) -> Option<ComponentTicks> {
+        let index = self.index(row)?;
+        let set = unsafe { &*self.set? };
+        Some(unsafe { *set.components.ticks_at(index) })
+    }
+
+    /// Gets pointers to the component of the entity in `row` and its change
+    /// ticks.
+    ///
+    /// # Safety
+    ///
+    /// The entity in `row` has to have a component, and `T` has to be the type
+    /// stored in the set.
+    pub unsafe fn get<T>(&self, row: usize) -> (*mut T, *mut ComponentTicks) {
+        let index = self.index(row).expect("entity has no sparse component");
+        let set = unsafe { &*self.set.unwrap() };
+        (
+            set.components.ptr_at(index) as *mut T,
+            set.components.ticks_at(index),
+        )
+    }
+}
@@ -1,8 +1,13 @@
 //! Module responsible for creating new entities.
 
-use crate::ecs::world::{
-    archetype::{Archetype, ArchetypeError, ArchetypeId},
-    component::Component,
+use crate::ecs::{
+    entity::Entity,
+    world::{
+        archetype::{Archetype, ArchetypeError, ArchetypeId, is_tag},
+        change_detection::Tick,
+        component::{Component, ComponentId},
+        sparse_set::{SparseSets, is_sparse},
+    },
 };
 
 /// Represents a type that can be used as a bundle when spawning an entity.
@@ -10,15 +15,100 @@ use crate::ecs::world::{
 /// and all tuples containging up to 16 values that implement [`Spawn`].
 pub trait Spawn: Send + Sync + 'static {
     fn archetype_id(&self) -> Result<ArchetypeId, ArchetypeError>;
-    fn spawn(&self, archetype: &mut Archetype) -> Result<(), ArchetypeError>;
+    /// Appends ids of all components of the bundle to `ids`, including sparse
+    /// components and tags.
+    fn component_ids(&self, ids: &mut Vec<ComponentId>);
+    /// Checks if all bundles of this type produce the same [`ArchetypeId`],
+    /// which only boxed bundles don't.
+    fn is_archetype_fixed() -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+    /// Moves all components of the bundle, inserted at `tick`, to `entity`.
+    /// Table components go to the last entity of `archetype`, sparse
+    /// components go to `sparse_sets` and tags aren't stored at all.
+    fn spawn(
+        self,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError>
+    where
+        Self: Sized;
+    /// Same as [`Spawn::spawn`], callable on boxed bundles.
+    fn spawn_boxed(
+        self: Box<Self>,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError>;
 }
 
 impl<T: Component> Spawn for T {
     fn archetype_id(&self) -> Result<ArchetypeId, ArchetypeError> {
-        ArchetypeId::new(vec![std::any::TypeId::of::<T>()])
+        if is_sparse::<T>() {
+            return ArchetypeId::new(Vec::new());
+        }
+        if is_tag::<T>() {
+            return Ok(ArchetypeId::tag(ComponentId::of::<T>()));
+        }
+        ArchetypeId::new(vec![ComponentId::of::<T>()])
+    }
+    fn component_ids(&self, ids: &mut Vec<ComponentId>) {
+        ids.push(ComponentId::of::<T>());
+    }
+    fn spawn(
+        self,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        if is_sparse::<T>() {
+            return sparse_sets.insert(entity, self, tick);
+        }
+        if is_tag::<T>() {
+            return Ok(());
+        }
+        archetype.add(self, tick)
+    }
+    fn spawn_boxed(
+        self: Box<Self>,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        (*self).spawn(archetype, sparse_sets, entity, tick)
+    }
+}
+
+/// Spawns an entity without any components.
+impl Spawn for () {
+    fn archetype_id(&self) -> Result<ArchetypeId, ArchetypeError> {
+        ArchetypeId::new(Vec::new())
+    }
+    fn component_ids(&self, _ids: &mut Vec<ComponentId>) {}
+    fn spawn(
+        self,
+        _archetype: &mut Archetype,
+        _sparse_sets: &mut SparseSets,
+        _entity: Entity,
+        _tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        Ok(())
     }
-    fn spawn(&self, archetype: &mut Archetype) -> Result<(), ArchetypeError> {
-        archetype.add(*self)?;
+    fn spawn_boxed(
+        self: Box<Self>,
+        _archetype: &mut Archetype,
+        _sparse_sets: &mut SparseSets,
+        _entity: Entity,
+        _tick: Tick,
+    ) -> Result<(), ArchetypeError> {
         Ok(())
     }
 }
@@ -27,7 +117,26 @@ impl Spawn for Box<dyn Spawn> {
     fn archetype_id(&self) -> Result<ArchetypeId, ArchetypeError> {
         (**self).archetype_id()
     }
-    fn spawn(&self, archetype: &mut Archetype) -> Result<(), ArchetypeError> {
-        (**self).spawn(archetype)
+    fn is_archetype_fixed() -> bool { false }
+    fn component_ids(&self, ids: &mut Vec<ComponentId>) {
+        (**self).component_ids(ids)
+    }
+    fn spawn(
+        self,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        self.spawn_boxed(archetype, sparse_sets, entity, tick)
+    }
+    fn spawn_boxed(
+        self: Box<Self>,
+        archetype: &mut Archetype,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        tick: Tick,
+    ) -> Result<(), ArchetypeError> {
+        (*self).spawn_boxed(archetype, sparse_sets, entity, tick)
     }
 }
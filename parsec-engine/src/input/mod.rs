@@ -4,8 +4,13 @@ use keys::Keys;
 
 use crate::{
     ctx::Ctx,
-    ecs::system::{SystemBundle, SystemTrigger, Systems},
-    error::{OptionNoneErr, ParsecError},
+    ecs::{
+        event::EventReader,
+        system::{
+            SystemBundle, SystemTrigger, Systems,
+            param::{Res, ResMut},
+        },
+    },
     graphics::window::Window,
     input::{
         keys::KeyboardInputEvent,
@@ -39,84 +44,70 @@ impl Input {
 
 fn input_start(ctx: Ctx) { ctx.resources.add(Input::new()); }
 
-fn input_clear(ctx: Ctx) -> Result<(), ParsecError> {
-    let mut input = ctx.resources.get_mut::<Input>().none_err()?;
+fn input_clear(mut input: ResMut<Input>) {
     input.keys.clear();
     input.mouse.clear();
-    Ok(())
 }
 
-fn input_clear_all(ctx: Ctx) -> Result<(), ParsecError> {
-    let mut input = ctx.resources.get_mut::<Input>().none_err()?;
+fn input_clear_all(mut input: ResMut<Input>) {
     input.keys.clear_all();
     input.mouse.clear();
-    Ok(())
 }
 
-fn input_keyboard_event(ctx: Ctx) -> Result<(), ParsecError> {
-    let window = ctx.resources.get::<Window>().none_err()?;
+fn input_keyboard_event(
+    window: Res<Window>,
+    mut input_events: EventReader<KeyboardInputEvent>,
+    mut input: ResMut<Input>,
+) {
     if !window.focused() {
-        return Ok(());
+        input_events.clear();
+        return;
+    }
+    for input_event in input_events.read() {
+        input.keys.process_input_event(input_event.clone());
     }
-    let input_event = ctx
-        .resources
-        .get::<KeyboardInputEvent>()
-        .none_err()?
-        .clone();
-    ctx.resources
-        .get_mut::<Input>()
-        .none_err()?
-        .keys
-        .process_input_event(input_event);
-    Ok(())
 }
 
-fn input_mouse_movement(ctx: Ctx) -> Result<(), ParsecError> {
-    let window = ctx.resources.get::<Window>().none_err()?;
+fn input_mouse_movement(
+    window: Res<Window>,
+    mut movement_events: EventReader<MouseMovementEvent>,
+    mut input: ResMut<Input>,
+) {
     if !window.focused() {
-        return Ok(());
+        movement_events.clear();
+        return;
+    }
+    for movement_event in movement_events.read() {
+        input.mouse.process_movement(*movement_event);
     }
-    let movement_event = ctx
-        .resources
-        .get::<MouseMovementEvent>()
-        .none_err()?
-        .clone();
-    ctx.resources
-        .get_mut::<Input>()
-        .none_err()?
-        .mouse
-        .process_movement(movement_event);
-    Ok(())
 }
 
-fn input_mouse_button(ctx: Ctx) -> Result<(), ParsecError> {
-    let window = ctx.resources.get::<Window>().none_err()?;
+fn input_mouse_button(
+    window: Res<Window>,
+    mut button_events: EventReader<MouseButtonEvent>,
+    mut input: ResMut<Input>,
+) {
     if !window.focused() {
-        return Ok(());
+        button_events.clear();
+        return;
+    }
+    for button_event in button_events.read() {
+        input.mouse.process_button_event(*button_event);
     }
-    let button_event =
-        ctx.resources.get::<MouseButtonEvent>().none_err()?.clone();
-    ctx.resources
-        .get_mut::<Input>()
-        .none_err()?
-        .mouse
-        .process_button_event(button_event);
-    Ok(())
 }
 
-fn input_mouse_wheel(ctx: Ctx) -> Result<(), ParsecError> {
-    let window = ctx.resources.get::<Window>().none_err()?;
+fn input_mouse_wheel(
+    window: Res<Window>,
+    mut wheel_events: EventReader<MouseWheelEvent>,
+    mut input: ResMut<Input>,
+) {
     if !window.focused() {
-        return Ok(());
+        wheel_events.clear();
+        return;
+    }
+    for wheel_event in wheel_events.read() {
+        input.mouse.process_wheel_event(*wheel_event);
     }
-    let wheel_event =
-        ctx.resources.get::<MouseWheelEvent>().none_err()?.clone();
-    ctx.resources
-        .get_mut::<Input>()
-        .none_err()?
-        .mouse
-        .process_wheel_event(wheel_event);
-    Ok(())
 }
 
 pub struct InputBundle;
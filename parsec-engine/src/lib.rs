@@ -1,3 +1,6 @@
+// Lets derive macros refer to `::parsec_engine` from inside this crate.
+extern crate self as parsec_engine;
+
 pub mod app;
 pub mod assets;
 pub mod cli;
@@ -1,11 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, hash_map::Entry};
 
 use parsec_engine_math::mat::Matrix4f;
 
 use crate::{
     create_counter,
     ctx::Ctx,
-    ecs::world::fetch::Mut,
+    ecs::{resources::Resources, world::filter::Changed},
     error::{OptionNoneErr, ParsecError},
     graphics::{
         ActiveGraphicsBackend,
@@ -17,7 +17,10 @@ use crate::{
         },
         window::Window,
     },
-    renderer::components::camera::Camera,
+    renderer::{
+        RendererFramesInFlight, components::camera::Camera,
+        sync::RendererRetiredResources,
+    },
     utils::{
         IdType,
         identifiable::{IdStore, Identifiable},
@@ -34,6 +37,8 @@ pub struct CameraData {
 
 pub struct CameraDataManager {
     pub component_to_data: HashMap<u32, u32>,
+    /// Aspect ratio used for the last projection update.
+    pub aspect_ratio: f32,
 }
 
 create_counter! {ID_COUNTER}
@@ -84,66 +89,138 @@ impl Identifiable for CameraData {
     fn id(&self) -> IdType { self.camera_data_id }
 }
 
-pub fn add_camera_data(ctx: Ctx) -> Result<(), ParsecError> {
-    let window = ctx.resources.get::<Window>().none_err()?;
+/// Registers hooks creating [`CameraData`] when a [`Camera`] is added to an
+/// entity and destroying it when the camera is removed or its entity is
+/// deleted. Cameras spawned earlier get their data right away.
+pub fn init_camera_data(ctx: Ctx) -> Result<(), ParsecError> {
+    ctx.world.on_add::<Camera>(|_, camera, commands| {
+        let camera = *camera;
+        commands.push(move |_, resources| add_camera_data(resources, &camera));
+    });
+    ctx.world.on_remove::<Camera>(|_, camera, commands| {
+        let camera_id = camera.camera_id();
+        commands
+            .push(move |_, resources| remove_camera_data(resources, camera_id));
+    });
+
+    for (_, camera) in ctx.world.query::<Camera>().iter() {
+        add_camera_data(ctx.resources, camera)?;
+    }
+    Ok(())
+}
+
+/// Creates [`CameraData`] for `camera` if it doesn't have any yet.
+fn add_camera_data(
+    resources: &Resources,
+    camera: &Camera,
+) -> Result<(), ParsecError> {
+    let window = resources.get::<Window>().none_err()?;
     let mut backend =
-        ctx.resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
+        resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
     let mut cameras_data =
-        ctx.resources.get_mut::<IdStore<CameraData>>().none_err()?;
+        resources.get_mut::<IdStore<CameraData>>().none_err()?;
     let mut camera_data_manager =
-        ctx.resources.get_mut::<CameraDataManager>().none_err()?;
-    let mut cameras = ctx.world.query::<Mut<Camera>>();
-
-    for (_, camera) in cameras.iter() {
-        if let std::collections::hash_map::Entry::Vacant(e) =
-            camera_data_manager
-                .component_to_data
-                .entry(camera.camera_id())
-        {
-            let camera_data = CameraData::new(
-                &mut backend,
-                &window,
-                camera.vertical_fov,
-                camera.near_clipping_plane,
-                camera.far_clipping_plane,
-            );
-            let data_id = cameras_data.push(camera_data);
-            e.insert(data_id);
-        }
+        resources.get_mut::<CameraDataManager>().none_err()?;
+
+    if let Entry::Vacant(e) = camera_data_manager
+        .component_to_data
+        .entry(camera.camera_id())
+    {
+        let camera_data = CameraData::new(
+            &mut backend,
+            &window,
+            camera.vertical_fov,
+            camera.near_clipping_plane,
+            camera.far_clipping_plane,
+        );
+        let data_id = cameras_data.push(camera_data);
+        e.insert(data_id);
     }
+    Ok(())
+}
+
+/// Retires the [`CameraData`] of the camera with id `camera_id`.
+/// It's destroyed once frames that may still use it finish.
+fn remove_camera_data(
+    resources: &Resources,
+    camera_id: u32,
+) -> Result<(), ParsecError> {
+    let frames_in_flight =
+        resources.get::<RendererFramesInFlight>().none_err()?;
+    let mut retired =
+        resources.get_mut::<RendererRetiredResources>().none_err()?;
+    let mut cameras_data =
+        resources.get_mut::<IdStore<CameraData>>().none_err()?;
+    let mut camera_data_manager =
+        resources.get_mut::<CameraDataManager>().none_err()?;
 
+    if let Some(data_id) =
+        camera_data_manager.component_to_data.remove(&camera_id)
+        && let Some(camera_data) = cameras_data.remove(data_id)
+    {
+        retired
+            .retire(frames_in_flight.0, |backend| camera_data.destroy(backend));
+    }
     Ok(())
 }
 
+fn update_projection(
+    backend: &mut ActiveGraphicsBackend,
+    cameras_data: &mut IdStore<CameraData>,
+    camera_data_manager: &CameraDataManager,
+    camera: &Camera,
+) {
+    if let Some(data_id) = camera_data_manager
+        .component_to_data
+        .get(&camera.camera_id())
+    {
+        let camera_data = cameras_data.get_mut(*data_id).unwrap();
+        camera_data.projection_matrix = Matrix4f::perspective(
+            camera.vertical_fov,
+            camera_data_manager.aspect_ratio,
+            camera.near_clipping_plane,
+            camera.far_clipping_plane,
+        );
+        backend
+            .update_buffer(
+                camera_data.projection_buffer.handle(),
+                BufferContent::from_slice(&[camera_data.projection_matrix]),
+            )
+            .unwrap();
+    }
+}
+
 pub fn update_camera_data(ctx: Ctx) -> Result<(), ParsecError> {
     let window = ctx.resources.get::<Window>().none_err()?;
     let mut backend =
         ctx.resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
     let mut cameras_data =
         ctx.resources.get_mut::<IdStore<CameraData>>().none_err()?;
-    let camera_data_manager =
-        ctx.resources.get::<CameraDataManager>().none_err()?;
-    let mut cameras = ctx.world.query::<Camera>();
+    let mut camera_data_manager =
+        ctx.resources.get_mut::<CameraDataManager>().none_err()?;
 
     let aspect_ratio = window.aspect_ratio();
-    for (_, camera) in cameras.iter() {
-        if let Some(data_id) = camera_data_manager
-            .component_to_data
-            .get(&camera.camera_id())
-        {
-            let camera_data = cameras_data.get_mut(*data_id).unwrap();
-            camera_data.projection_matrix = Matrix4f::perspective(
-                camera.vertical_fov,
-                aspect_ratio,
-                camera.near_clipping_plane,
-                camera.far_clipping_plane,
+    if camera_data_manager.aspect_ratio != aspect_ratio {
+        // Every projection depends on the aspect ratio.
+        camera_data_manager.aspect_ratio = aspect_ratio;
+        let mut cameras = ctx.world.query::<Camera>();
+        for (_, camera) in cameras.iter() {
+            update_projection(
+                &mut backend,
+                &mut cameras_data,
+                &camera_data_manager,
+                camera,
+            );
+        }
+    } else {
+        let mut cameras = ctx.world.query_filtered::<Camera, Changed<Camera>>();
+        for (_, camera) in cameras.iter() {
+            update_projection(
+                &mut backend,
+                &mut cameras_data,
+                &camera_data_manager,
+                camera,
             );
-            backend
-                .update_buffer(
-                    camera_data.projection_buffer.handle(),
-                    BufferContent::from_slice(&[camera_data.projection_matrix]),
-                )
-                .unwrap();
         }
     }
 
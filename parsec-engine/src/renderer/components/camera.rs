@@ -1,7 +1,10 @@
 use crate::{create_counter, ecs::world::component::Component};
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, Copy, Component, serde::Serialize, serde::Deserialize)]
+#[component(clone = "clone_camera")]
 pub struct Camera {
+    /// Not saved, so that every loaded camera gets a new id.
+    #[serde(skip, default = "next_camera_id")]
     camera_id: u32,
     pub vertical_fov: f32,
     pub near_clipping_plane: f32,
@@ -9,6 +12,16 @@ pub struct Camera {
 }
 
 create_counter! {ID_COUNTER}
+fn next_camera_id() -> u32 { ID_COUNTER.next() }
+
+/// Clones get a new id, like loaded cameras.
+fn clone_camera(camera: &Camera) -> Camera {
+    Camera {
+        camera_id: next_camera_id(),
+        ..*camera
+    }
+}
+
 impl Camera {
     pub fn new(
         vertical_fov: f32,
@@ -16,7 +29,7 @@ impl Camera {
         far_clipping_plane: f32,
     ) -> Camera {
         Camera {
-            camera_id: ID_COUNTER.next(),
+            camera_id: next_camera_id(),
             vertical_fov,
             near_clipping_plane,
             far_clipping_plane,
@@ -0,0 +1,6 @@
+use crate::ecs::world::component::Component;
+
+/// Marks an entity that should be skipped by the renderer.
+#[derive(Debug, Clone, Copy, Component, serde::Serialize, serde::Deserialize)]
+#[component(clone)]
+pub struct Hidden;
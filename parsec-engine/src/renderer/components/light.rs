@@ -2,8 +2,11 @@ use parsec_engine_math::vec::Vec3f;
 
 use crate::{create_counter, ecs::world::component::Component};
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, Copy, Component, serde::Serialize, serde::Deserialize)]
+#[component(clone = "clone_light")]
 pub struct Light {
+    /// Not saved, so that every loaded light gets a new id.
+    #[serde(skip, default = "next_light_id")]
     light_id: u32,
     pub direction: Vec3f,
     pub up: Vec3f,
@@ -11,10 +14,20 @@ pub struct Light {
 }
 
 create_counter! {ID_COUNTER}
+fn next_light_id() -> u32 { ID_COUNTER.next() }
+
+/// Clones get a new id, like loaded lights.
+fn clone_light(light: &Light) -> Light {
+    Light {
+        light_id: next_light_id(),
+        ..*light
+    }
+}
+
 impl Light {
     pub fn new(direction: Vec3f, up: Vec3f, color: Vec3f) -> Self {
         Self {
-            light_id: ID_COUNTER.next(),
+            light_id: next_light_id(),
             direction,
             up,
             color,
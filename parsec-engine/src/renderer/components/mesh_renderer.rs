@@ -1,6 +1,9 @@
 use crate::{assets::{AssetHandle, core::mesh::Mesh}, ecs::world::component::Component};
 
-#[derive(Debug, Component)]
+/// Meshes are saved by asset name and have to be loaded into the
+/// [`AssetLibrary`][crate::assets::AssetLibrary] before rendering.
+#[derive(Debug, Clone, Copy, Component, serde::Serialize, serde::Deserialize)]
+#[component(clone)]
 pub struct MeshRenderer {
     pub mesh: AssetHandle<Mesh>,
     pub material_id: u32,
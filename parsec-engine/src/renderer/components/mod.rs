@@ -1,4 +1,24 @@
+use crate::{
+    ecs::scene::TypeRegistry,
+    renderer::components::{
+        camera::Camera, hidden::Hidden, light::Light,
+        mesh_renderer::MeshRenderer, transform::Transform,
+    },
+};
+
 pub mod camera;
+pub mod hidden;
 pub mod light;
 pub mod mesh_renderer;
 pub mod transform;
+
+/// Registers renderer components that can be saved in scenes.
+/// [`GlobalTransform`][transform::GlobalTransform]s aren't registered, since
+/// they are recomputed from [`Transform`]s.
+pub fn register_components(registry: &mut TypeRegistry) {
+    registry.register::<Transform>("Transform");
+    registry.register::<Camera>("Camera");
+    registry.register::<Light>("Light");
+    registry.register::<MeshRenderer>("MeshRenderer");
+    registry.register::<Hidden>("Hidden");
+}
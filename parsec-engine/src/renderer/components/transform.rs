@@ -1,9 +1,25 @@
 use parsec_engine_math::{quat::Quat, vec::Vec3f};
 
-use crate::{create_counter, ecs::world::component::Component};
+use crate::{
+    create_counter,
+    ctx::Ctx,
+    ecs::{
+        entity::Entity,
+        hierarchy::{Children, Parent},
+        world::{
+            World, WorldError,
+            component::Component,
+            filter::{Or, With, Without},
+        },
+    },
+    error::ParsecError,
+};
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, Copy, Component, serde::Serialize, serde::Deserialize)]
+#[component(clone = "clone_transform")]
 pub struct Transform {
+    /// Not saved, so that every loaded transform gets a new id.
+    #[serde(skip, default = "next_transform_id")]
     transform_id: u32,
     pub position: Vec3f,
     pub scale: Vec3f,
@@ -11,10 +27,20 @@ pub struct Transform {
 }
 
 create_counter! {ID_COUNTER}
+fn next_transform_id() -> u32 { ID_COUNTER.next() }
+
+/// Clones get a new id, like loaded transforms.
+fn clone_transform(transform: &Transform) -> Transform {
+    Transform {
+        transform_id: next_transform_id(),
+        ..*transform
+    }
+}
+
 impl Transform {
     pub fn new(position: Vec3f, scale: Vec3f, rotation: Quat) -> Transform {
         Transform {
-            transform_id: ID_COUNTER.next(),
+            transform_id: next_transform_id(),
             position,
             scale,
             rotation,
@@ -23,3 +49,181 @@ impl Transform {
 
     pub fn transform_id(&self) -> u32 { self.transform_id }
 }
+
+/// [`Transform`] composed with transforms of all ancestors of an entity.
+/// Added to every entity with a [`Transform`] and updated by
+/// [`propagate_transforms`].
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+#[component(clone)]
+pub struct GlobalTransform {
+    pub position: Vec3f,
+    pub scale: Vec3f,
+    pub rotation: Quat,
+}
+
+impl GlobalTransform {
+    /// Transform that doesn't move, scale or rotate anything.
+    pub const IDENTITY: GlobalTransform = GlobalTransform {
+        position: Vec3f::ZERO,
+        scale: Vec3f::ONE,
+        rotation: Quat::IDENTITY,
+    };
+
+    /// Applies a local `transform` of a child on top of `self`.
+    pub fn mul_transform(&self, transform: &Transform) -> GlobalTransform {
+        GlobalTransform {
+            position: transform.position * self.scale * self.rotation
+                + self.position,
+            scale: transform.scale * self.scale,
+            rotation: transform.rotation * self.rotation,
+        }
+    }
+}
+
+impl From<&Transform> for GlobalTransform {
+    fn from(transform: &Transform) -> Self {
+        GlobalTransform {
+            position: transform.position,
+            scale: transform.scale,
+            rotation: transform.rotation,
+        }
+    }
+}
+
+/// Matches roots of hierarchies that can contain [`Transform`]s.
+type RootFilter = (Without<Parent>, Or<(With<Transform>, With<Children>)>);
+
+/// Adds missing [`GlobalTransform`]s and updates them from hierarchies of
+/// [`Transform`]s. Entities without a [`Transform`] pass their parent's
+/// transform to their children unchanged, and roots without one pass the
+/// [identity][GlobalTransform::IDENTITY].
+pub fn propagate_transforms(ctx: Ctx) -> Result<(), ParsecError> {
+    let missing = ctx
+        .world
+        .query_filtered::<Transform, Without<GlobalTransform>>()
+        .iter()
+        .map(|(entity, transform)| (entity, GlobalTransform::from(transform)))
+        .collect::<Vec<_>>();
+    for (entity, global_transform) in missing {
+        ctx.world.add_components(entity, global_transform)?;
+    }
+
+    let roots = ctx
+        .world
+        .query_filtered::<Option<Transform>, RootFilter>()
+        .iter()
+        .map(|(entity, transform)| {
+            let global_transform =
+                transform.map_or(GlobalTransform::IDENTITY, Into::into);
+            (entity, global_transform)
+        })
+        .collect::<Vec<_>>();
+    for (entity, global_transform) in roots {
+        propagate(ctx.world, entity, global_transform)?;
+    }
+    Ok(())
+}
+
+/// Sets the [`GlobalTransform`] of `entity` and recursively of its children.
+fn propagate(
+    world: &World,
+    entity: Entity,
+    global_transform: GlobalTransform,
+) -> Result<(), WorldError> {
+    if let Ok(mut current) = world.get_mut::<GlobalTransform>(entity) {
+        // Only assign when different, so `Changed` filters stay accurate.
+        if *current != global_transform {
+            *current = global_transform;
+        }
+    }
+    for child in world.children(entity) {
+        let child_transform = match world.get::<Transform>(child) {
+            Ok(transform) => global_transform.mul_transform(&transform),
+            Err(_) => global_transform,
+        };
+        propagate(world, child, child_transform)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::{
+        assets::AssetLibrary,
+        ecs::{commands::Commands, resources::Resources},
+    };
+
+    fn assert_near(a: Vec3f, b: Vec3f) {
+        let mut difference = a - b;
+        assert!(difference.length_sqr() < 1e-8, "{a:?} != {b:?}");
+    }
+
+    fn propagate_in(world: &mut World) {
+        propagate_transforms(Ctx {
+            world,
+            resources: &mut Resources::new(),
+            assets: &mut AssetLibrary::new(),
+            commands: &mut Commands::new(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn nested_propagation() {
+        let transforms = [
+            Transform::new(
+                Vec3f::new(1.0, 0.0, 0.0),
+                Vec3f::ONE * 2.0,
+                Quat::from_euler(Vec3f::new(FRAC_PI_2, 0.0, 0.0)),
+            ),
+            Transform::new(
+                Vec3f::new(0.0, 1.0, 0.0),
+                Vec3f::ONE,
+                Quat::from_euler(Vec3f::new(0.0, FRAC_PI_2, 0.0)),
+            ),
+            Transform::new(
+                Vec3f::new(0.0, 0.0, 1.0),
+                Vec3f::ONE,
+                Quat::IDENTITY,
+            ),
+        ];
+        let mut world = World::new();
+        // The root has no transform, so it passes the identity.
+        let root = world.spawn(()).unwrap();
+        let mut parent = root;
+        let mut entities = Vec::new();
+        for transform in transforms {
+            let entity = world.spawn(transform).unwrap();
+            world.set_parent(entity, parent).unwrap();
+            entities.push(entity);
+            parent = entity;
+        }
+        propagate_in(&mut world);
+
+        // Applies the local transforms from the grandchild up to the root.
+        let to_world = |point: Vec3f| {
+            transforms.iter().rev().fold(point, |point, transform| {
+                point * transform.scale * transform.rotation
+                    + transform.position
+            })
+        };
+        let global = *world.get::<GlobalTransform>(entities[2]).unwrap();
+        assert_near(global.position, to_world(Vec3f::ZERO));
+        assert_near(global.scale, Vec3f::ONE * 2.0);
+        let direction = Vec3f::new(1.0, 2.0, 3.0);
+        assert_near(
+            direction * global.rotation * 2.0,
+            to_world(direction) - to_world(Vec3f::ZERO),
+        );
+        let swapped = transforms[0].rotation * transforms[1].rotation;
+        assert!(global.rotation != swapped);
+
+        world.get_mut::<Transform>(entities[0]).unwrap().position = Vec3f::ZERO;
+        propagate_in(&mut world);
+        let global = *world.get::<GlobalTransform>(entities[0]).unwrap();
+        assert_near(global.position, Vec3f::ZERO);
+    }
+}
@@ -1,9 +1,16 @@
 use std::marker::PhantomData;
 
 use crate::{
-    assets::core::mesh::Mesh,
+    assets::{AssetLibrary, core::mesh::Mesh},
     ctx::Ctx,
-    ecs::system::{SystemBundle, SystemTrigger, Systems},
+    ecs::{
+        system::{
+            SystemBundle, SystemTrigger, Systems,
+            config::{IntoSystemConfig, SetConfig},
+            param::{Res, ResMut},
+        },
+        world::{filter::Without, query::Query},
+    },
     error::{OptionNoneErr, ParsecError},
     graphics::{
         ActiveEventLoop, ActiveGraphicsBackend, backend::GraphicsBackend,
@@ -11,20 +18,38 @@ use crate::{
     },
     renderer::{
         ResizeFlag,
-        camera_data::{CameraDataManager, add_camera_data, update_camera_data},
+        camera_data::{CameraDataManager, init_camera_data, update_camera_data},
         components::{
-            camera::Camera, mesh_renderer::MeshRenderer, transform::Transform,
+            camera::Camera, hidden::Hidden, mesh_renderer::MeshRenderer,
+            transform::{Transform, propagate_transforms},
         },
         draw_queue::{Draw, MeshAndMaterial},
         init_renderer,
         light_data::update_light_data,
         queue_clear, render,
+        sync::RendererRetiredResources,
         transform_data::{
-            TransformDataManager, add_transform_data, update_transform_data,
+            TransformDataManager, init_transform_data, update_transform_data,
         },
+        window_visible,
     },
 };
 
+/// Names of the system sets run by [`GraphicsBundle`] on
+/// [`SystemTrigger::Render`], in execution order.
+pub struct RenderSet;
+
+impl RenderSet {
+    /// Uploads component data to the GPU.
+    pub const UPLOAD: &'static str = "render_upload";
+    /// Fills the draw queue.
+    pub const ENQUEUE: &'static str = "render_enqueue";
+    /// Records and submits draw commands.
+    pub const DRAW: &'static str = "render_draw";
+    /// Clears per-frame data.
+    pub const CLEANUP: &'static str = "render_cleanup";
+}
+
 pub struct GraphicsBundle<B: GraphicsBackend> {
     _marker: PhantomData<B>,
 }
@@ -53,36 +78,64 @@ impl<B: GraphicsBackend> SystemBundle for GraphicsBundle<B> {
             Ok(())
         });
         systems.add(SystemTrigger::LateStart, init_renderer);
-        systems.add(SystemTrigger::Render, update_camera_data);
-        systems.add(SystemTrigger::Render, update_transform_data);
-        systems.add(SystemTrigger::Render, update_light_data);
-        systems.add(SystemTrigger::Render, auto_enqueue);
-        systems.add(SystemTrigger::Render, render);
-        systems.add(SystemTrigger::Render, queue_clear);
+        systems.add(SystemTrigger::LateStart, init_camera_data);
+        systems.add(SystemTrigger::LateStart, init_transform_data);
+        systems.configure_set(
+            SystemTrigger::Render,
+            SetConfig::new(RenderSet::UPLOAD).before(RenderSet::ENQUEUE),
+        );
+        systems.configure_set(
+            SystemTrigger::Render,
+            SetConfig::new(RenderSet::ENQUEUE).before(RenderSet::DRAW),
+        );
+        systems.configure_set(
+            SystemTrigger::Render,
+            SetConfig::new(RenderSet::DRAW).before(RenderSet::CLEANUP),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            update_camera_data.in_set(RenderSet::UPLOAD),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            propagate_transforms.before(RenderSet::UPLOAD),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            update_transform_data.in_set(RenderSet::UPLOAD),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            update_light_data.in_set(RenderSet::UPLOAD),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            auto_enqueue.in_set(RenderSet::ENQUEUE),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            render.in_set(RenderSet::DRAW).run_if(window_visible),
+        );
+        systems.add(
+            SystemTrigger::Render,
+            queue_clear.in_set(RenderSet::CLEANUP),
+        );
         systems.add(SystemTrigger::Update, request_redraw);
-        systems.add(SystemTrigger::Update, add_camera_data);
-        systems.add(SystemTrigger::Update, add_transform_data);
         systems.add(SystemTrigger::End, end_wait_idle);
         systems.add(SystemTrigger::WindowResized, mark_resize);
     }
 }
 
-fn mark_resize(ctx: Ctx) -> Result<(), ParsecError> {
-    ctx.resources.get_mut::<ResizeFlag>().none_err()?.0 = true;
-    Ok(())
-}
+fn mark_resize(mut resize_flag: ResMut<ResizeFlag>) { resize_flag.0 = true; }
 
-fn request_redraw(ctx: Ctx) -> Result<(), ParsecError> {
-    ctx.resources.get::<Window>().none_err()?.request_redraw();
-    Ok(())
-}
+fn request_redraw(window: Res<Window>) { window.request_redraw(); }
 
-fn end_wait_idle(ctx: Ctx) -> Result<(), ParsecError> {
-    ctx.resources
-        .get::<ActiveGraphicsBackend>()
-        .none_err()?
-        .wait_idle();
-    Ok(())
+fn end_wait_idle(
+    mut backend: ResMut<ActiveGraphicsBackend>,
+    mut retired: ResMut<RendererRetiredResources>,
+) {
+    backend.wait_idle();
+    retired.destroy_all(&mut backend);
 }
 
 fn init_window(ctx: Ctx) -> Result<(), ParsecError> {
@@ -95,19 +148,18 @@ fn init_window(ctx: Ctx) -> Result<(), ParsecError> {
     Ok(())
 }
 
-fn auto_enqueue(ctx: Ctx) -> Result<(), ParsecError> {
-    let mut draw_queue = ctx.resources.get_mut::<Vec<Draw>>().none_err()?;
-    let camera_data_manager =
-        ctx.resources.get::<CameraDataManager>().none_err()?;
-    let transform_data_manager =
-        ctx.resources.get::<TransformDataManager>().none_err()?;
-    let mut cameras = ctx.world.query::<(Transform, Camera)>();
-    let mut mesh_renderers = ctx.world.query::<(Transform, MeshRenderer)>();
-
+fn auto_enqueue(
+    mut draw_queue: ResMut<Vec<Draw>>,
+    camera_data_manager: Res<CameraDataManager>,
+    transform_data_manager: Res<TransformDataManager>,
+    mut cameras: Query<(Transform, Camera)>,
+    mut mesh_renderers: Query<(Transform, MeshRenderer), Without<Hidden>>,
+    assets: &AssetLibrary,
+) -> Result<(), ParsecError> {
     for (_, (camera_transform, camera)) in cameras.iter() {
         for (_, (transform, mesh_renderer)) in mesh_renderers.iter() {
             let mesh_asset =
-                ctx.assets.get::<Mesh>(mesh_renderer.mesh).none_err()?;
+                assets.get::<Mesh>(mesh_renderer.mesh).none_err()?;
             if mesh_asset.data_id.is_none()
                 || !camera_data_manager
                     .component_to_data
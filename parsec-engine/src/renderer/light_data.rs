@@ -15,7 +15,7 @@ use crate::{
             PipelineResourceLayoutBuilder, PipelineShaderStage,
         },
     },
-    renderer::components::{light::Light, transform::Transform},
+    renderer::components::{light::Light, transform::GlobalTransform},
 };
 
 pub const MAX_LIGHT_COUNT: usize = 32;
@@ -136,7 +136,7 @@ pub fn update_light_data(ctx: Ctx) -> Result<(), ParsecError> {
     let mut backend =
         ctx.resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
     let mut light_data = ctx.resources.get_mut::<RendererLights>().none_err()?;
-    let mut lights = ctx.world.query::<(Light, Transform)>();
+    let mut lights = ctx.world.query::<(Light, GlobalTransform)>();
 
     light_data.clear_data();
     for (_, (light, transfrom)) in lights.iter() {
@@ -17,10 +17,11 @@ pub mod integrated_image;
 pub mod image_atlas;
 pub mod transform_data;
 
-use sync::{RendererFrameSync, RendererImageSync};
+use sync::{RendererFrameSync, RendererImageSync, RendererRetiredResources};
 
 use crate::{
     ctx::Ctx,
+    ecs::resources::Resources,
     error::{OptionNoneErr, ParsecError},
     graphics::{
         ActiveGraphicsBackend,
@@ -165,6 +166,7 @@ pub fn init_renderer(ctx: Ctx) -> Result<(), ParsecError> {
     ctx.resources.add(image_sync);
     ctx.resources.add(command_lists);
     ctx.resources.add(RendererCurrentFrame(0));
+    ctx.resources.add(RendererRetiredResources::default());
     ctx.resources
         .add(RendererFramesInFlight(frames_in_flight as u32));
     ctx.resources.add(ResizeFlag(false));
@@ -179,6 +181,7 @@ pub fn init_renderer(ctx: Ctx) -> Result<(), ParsecError> {
     });
     ctx.resources.add(CameraDataManager {
         component_to_data: HashMap::new(),
+        aspect_ratio: 0.0,
     });
     Ok(())
 }
@@ -226,6 +229,13 @@ fn recreate_size_dependent_components(
     framebuffers.append(&mut new_framebuffers);
 }
 
+/// Run condition that skips rendering while the window is minimized.
+pub fn window_visible(resources: &Resources) -> bool {
+    resources
+        .get::<Window>()
+        .is_some_and(|window| !window.minimized())
+}
+
 pub fn render(ctx: Ctx) -> Result<(), ParsecError> {
     let mut backend =
         ctx.resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
@@ -263,10 +273,10 @@ pub fn render(ctx: Ctx) -> Result<(), ParsecError> {
     let cameras_data = ctx.resources.get::<IdStore<CameraData>>().none_err()?;
     let shadows = ctx.resources.get::<RendererShadows>().none_err()?;
     let lights = ctx.resources.get::<RendererLights>().none_err()?;
-
-    if window.minimized() {
-        return Ok(());
-    }
+    let mut retired = ctx
+        .resources
+        .get_mut::<RendererRetiredResources>()
+        .none_err()?;
 
     if resize.0 {
         recreate_size_dependent_components(
@@ -396,6 +406,7 @@ pub fn render(ctx: Ctx) -> Result<(), ParsecError> {
     let command_buffer_fence =
         frame_sync[current_frame.0 as usize].command_buffer_fence;
     backend.wait_gpu_to_cpu_fence(command_buffer_fence).unwrap();
+    retired.frame_finished(&mut backend);
 
     current_frame.0 = (current_frame.0 + 1) % frames_in_flight.0;
     Ok(())
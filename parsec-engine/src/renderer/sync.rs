@@ -34,3 +34,45 @@ impl RendererImageSync {
         }
     }
 }
+
+type DestroyFn = Box<dyn FnOnce(&mut ActiveGraphicsBackend) + Send + Sync>;
+
+/// GPU resources removed while frames using them may still be in flight.
+/// They are destroyed once the fences of all those frames signalled.
+#[derive(Default)]
+pub struct RendererRetiredResources {
+    /// Destroy functions, with the number of frames still to wait for.
+    pending: Vec<(u32, DestroyFn)>,
+}
+
+impl RendererRetiredResources {
+    /// Queues `destroy` to run after `frames_in_flight` more frames finish.
+    pub fn retire<F>(&mut self, frames_in_flight: u32, destroy: F)
+    where
+        F: FnOnce(&mut ActiveGraphicsBackend) + Send + Sync + 'static,
+    {
+        self.pending.push((frames_in_flight, Box::new(destroy)));
+    }
+
+    /// Destroys resources no longer used by any frame.
+    /// Called after waiting for the fence of a frame.
+    pub fn frame_finished(&mut self, backend: &mut ActiveGraphicsBackend) {
+        for (frames, _) in self.pending.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        let (finished, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(frames, _)| *frames == 0);
+        self.pending = pending;
+        for (_, destroy) in finished {
+            destroy(backend);
+        }
+    }
+
+    /// Destroys all resources. The device has to be idle.
+    pub fn destroy_all(&mut self, backend: &mut ActiveGraphicsBackend) {
+        for (_, destroy) in self.pending.drain(..) {
+            destroy(backend);
+        }
+    }
+}
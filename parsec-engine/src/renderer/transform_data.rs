@@ -1,11 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, hash_map::Entry};
 
 use parsec_engine_math::{mat::Matrix4f, quat::Quat, vec::Vec3f};
 
 use crate::{
     create_counter,
     ctx::Ctx,
-    ecs::world::fetch::Mut,
+    ecs::{resources::Resources, world::filter::Changed},
     error::{OptionNoneErr, ParsecError},
     graphics::{
         ActiveGraphicsBackend,
@@ -16,7 +16,11 @@ use crate::{
             PipelineResourceLayoutBuilder, PipelineShaderStage,
         },
     },
-    renderer::components::transform::Transform,
+    renderer::{
+        RendererFramesInFlight,
+        components::transform::{GlobalTransform, Transform},
+        sync::RendererRetiredResources,
+    },
     utils::{
         IdType,
         identifiable::{IdStore, Identifiable},
@@ -180,30 +184,81 @@ impl Identifiable for TransformData {
     fn id(&self) -> IdType { self.transform_data_id }
 }
 
-pub fn add_transform_data(ctx: Ctx) -> Result<(), ParsecError> {
+/// Registers hooks creating [`TransformData`] when a [`Transform`] is added to
+/// an entity and destroying it when the transform is removed or its entity is
+/// deleted. Transforms spawned earlier get their data right away.
+pub fn init_transform_data(ctx: Ctx) -> Result<(), ParsecError> {
+    ctx.world.on_add::<Transform>(|_, transform, commands| {
+        let transform = *transform;
+        commands.push(move |_, resources| {
+            add_transform_data(resources, &transform)
+        });
+    });
+    ctx.world.on_remove::<Transform>(|_, transform, commands| {
+        let transform_id = transform.transform_id();
+        commands.push(move |_, resources| {
+            remove_transform_data(resources, transform_id)
+        });
+    });
+
+    for (_, transform) in ctx.world.query::<Transform>().iter() {
+        add_transform_data(ctx.resources, transform)?;
+    }
+    Ok(())
+}
+
+/// Creates [`TransformData`] for `transform` if it doesn't have any yet.
+/// It's updated from the [`GlobalTransform`] once that is propagated.
+fn add_transform_data(
+    resources: &Resources,
+    transform: &Transform,
+) -> Result<(), ParsecError> {
     let mut backend =
-        ctx.resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
+        resources.get_mut::<ActiveGraphicsBackend>().none_err()?;
     let mut transforms_data =
-        ctx.resources.get_mut::<IdStore<TransformData>>().none_err()?;
+        resources.get_mut::<IdStore<TransformData>>().none_err()?;
     let mut transforms_data_manager =
-        ctx.resources.get_mut::<TransformDataManager>().none_err()?;
-    let mut transforms = ctx.world.query::<Mut<Transform>>();
-
-    for (_, transform) in transforms.iter() {
-        if let std::collections::hash_map::Entry::Vacant(e) =
-            transforms_data_manager
-                .component_to_data
-                .entry(transform.transform_id())
-        {
-            let transform_data = TransformData::new(
-                &mut backend,
-                transform.position,
-                transform.scale,
-                transform.rotation,
-            );
-            let data_id = transforms_data.push(transform_data);
-            e.insert(data_id);
-        }
+        resources.get_mut::<TransformDataManager>().none_err()?;
+
+    if let Entry::Vacant(e) = transforms_data_manager
+        .component_to_data
+        .entry(transform.transform_id())
+    {
+        let transform_data = TransformData::new(
+            &mut backend,
+            transform.position,
+            transform.scale,
+            transform.rotation,
+        );
+        let data_id = transforms_data.push(transform_data);
+        e.insert(data_id);
+    }
+    Ok(())
+}
+
+/// Retires the [`TransformData`] of the transform with id `transform_id`.
+/// It's destroyed once frames that may still use it finish.
+fn remove_transform_data(
+    resources: &Resources,
+    transform_id: u32,
+) -> Result<(), ParsecError> {
+    let frames_in_flight =
+        resources.get::<RendererFramesInFlight>().none_err()?;
+    let mut retired =
+        resources.get_mut::<RendererRetiredResources>().none_err()?;
+    let mut transforms_data =
+        resources.get_mut::<IdStore<TransformData>>().none_err()?;
+    let mut transforms_data_manager =
+        resources.get_mut::<TransformDataManager>().none_err()?;
+
+    if let Some(data_id) = transforms_data_manager
+        .component_to_data
+        .remove(&transform_id)
+        && let Some(transform_data) = transforms_data.remove(data_id)
+    {
+        retired.retire(frames_in_flight.0, |backend| {
+            transform_data.destroy(backend)
+        });
     }
     Ok(())
 }
@@ -215,21 +270,25 @@ pub fn update_transform_data(ctx: Ctx) -> Result<(), ParsecError> {
         ctx.resources.get_mut::<IdStore<TransformData>>().none_err()?;
     let transforms_data_manager =
         ctx.resources.get::<TransformDataManager>().none_err()?;
-    let mut transforms = ctx.world.query::<Transform>();
+    let mut transforms = ctx.world.query_filtered::<
+        (Transform, GlobalTransform),
+        Changed<GlobalTransform>,
+    >();
 
-    for (_, transform) in transforms.iter() {
+    for (_, (transform, global_transform)) in transforms.iter() {
         if let Some(data_id) = transforms_data_manager
             .component_to_data
             .get(&transform.transform_id())
         {
             let data = transforms_data.get_mut(*data_id).unwrap();
-            data.translation_matrix = Matrix4f::translation(transform.position);
-            data.scale_matrix = Matrix4f::scale(transform.scale);
-            data.rotation_matrix = transform.rotation.into_matrix();
+            data.translation_matrix =
+                Matrix4f::translation(global_transform.position);
+            data.scale_matrix = Matrix4f::scale(global_transform.scale);
+            data.rotation_matrix = global_transform.rotation.into_matrix();
             data.look_at_matrix = Matrix4f::look_at(
-                transform.position,
-                Vec3f::FORWARD * transform.rotation,
-                Vec3f::UP * transform.rotation,
+                global_transform.position,
+                Vec3f::FORWARD * global_transform.rotation,
+                Vec3f::UP * global_transform.rotation,
             );
             data.update_buffers_from_data(&mut backend);
         }
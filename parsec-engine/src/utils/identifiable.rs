@@ -30,6 +30,10 @@ impl<T: Identifiable> IdStore<T> {
         id
     }
 
+    pub fn remove(&mut self, id: IdType) -> Option<T> {
+        self.elements.remove(&id)
+    }
+
     pub fn get(&self, id: IdType) -> Option<&T> { self.elements.get(&id) }
 
     pub fn get_mut(&mut self, id: IdType) -> Option<&mut T> {
@@ -6,3 +6,4 @@ pub mod id_counter;
 pub mod identifiable;
 pub mod small_vec;
 pub mod borrowing;
+pub mod thread_pool;
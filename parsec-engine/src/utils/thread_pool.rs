@@ -0,0 +1,268 @@
+//! Persistent worker threads running short jobs that borrow from the caller.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    panic::AssertUnwindSafe,
+    sync::{
+        Arc, Condvar, LazyLock, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::JoinHandle,
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// State shared between a pool and its workers.
+struct Shared {
+    /// Queued jobs, or `None` once the pool is shutting down.
+    queue: Mutex<Option<VecDeque<Job>>>,
+    /// Notified when a job is queued or finished.
+    changed: Condvar,
+}
+
+impl Shared {
+    /// Gets an identifier of the pool unique among living pools.
+    fn id(&self) -> usize { self as *const Shared as usize }
+}
+
+thread_local! {
+    /// Pool used by [`ThreadPool::current`] on this thread.
+    static CURRENT: RefCell<Option<Arc<ThreadPool>>> =
+        const { RefCell::new(None) };
+    /// Id of the pool this thread is a worker of, or 0.
+    static WORKER_OF: Cell<usize> = const { Cell::new(0) };
+}
+
+static GLOBAL: LazyLock<Arc<ThreadPool>> = LazyLock::new(|| {
+    let threads =
+        std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    Arc::new(ThreadPool::new(threads.saturating_sub(1)))
+});
+
+/// Fixed set of threads started once and reused for every job, so that short
+/// parallel work doesn't pay for spawning threads.
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    /// Starts a pool with `threads` workers, at least one.
+    pub fn new(threads: usize) -> ThreadPool {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Some(VecDeque::new())),
+            changed: Condvar::new(),
+        });
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("parsec-worker-{i}"))
+                    .spawn(move || work(&shared))
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+        ThreadPool { shared, workers }
+    }
+
+    /// Gets the pool shared by default by all
+    /// [`Systems`][crate::ecs::system::Systems], with a worker for every
+    /// available core except the current one.
+    pub fn global() -> Arc<ThreadPool> { GLOBAL.clone() }
+
+    /// Gets the pool entered on this thread with [`ThreadPool::enter`], or the
+    /// [global][ThreadPool::global] one.
+    pub fn current() -> Arc<ThreadPool> {
+        CURRENT
+            .with_borrow(|current| current.clone())
+            .unwrap_or_else(ThreadPool::global)
+    }
+
+    /// Makes `self` the [current][ThreadPool::current] pool of this thread
+    /// until the returned guard is dropped.
+    pub fn enter(self: &Arc<Self>) -> EnterGuard {
+        let previous = CURRENT.replace(Some(self.clone()));
+        EnterGuard {
+            previous,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the number of worker threads.
+    pub fn threads(&self) -> usize { self.workers.len() }
+
+    /// Runs `f`, which can queue jobs borrowing local data with
+    /// [`Scope::spawn`], and waits until all of them finish.
+    /// Jobs run with `self` as the [current][ThreadPool::current] pool.
+    ///
+    /// # Panics
+    ///
+    /// - If `f` or any of the jobs panics, after all jobs finish.
+    pub fn scope<'s, R>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&Scope<'s>) -> R,
+    ) -> R {
+        let scope = Scope {
+            pool: self.clone(),
+            pending: AtomicUsize::new(0),
+            panic: Mutex::new(None),
+            _marker: PhantomData,
+        };
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.wait();
+        if let Some(panic) = scope.panic.lock().unwrap().take() {
+            std::panic::resume_unwind(panic);
+        }
+        result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Queues `job` to run on a worker.
+    fn push(&self, job: Job) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        // UNWRAP: the queue is only taken when the pool is dropped.
+        queue.as_mut().unwrap().push_back(job);
+        drop(queue);
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().take();
+        self.shared.changed.notify_all();
+        if WORKER_OF.get() == self.shared.id() {
+            return;
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Runs jobs of the pool sharing `shared` until the pool is dropped.
+fn work(shared: &Shared) {
+    WORKER_OF.set(shared.id());
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        let Some(jobs) = queue.as_mut() else {
+            return;
+        };
+        match jobs.pop_front() {
+            Some(job) => {
+                drop(queue);
+                job();
+                queue = shared.queue.lock().unwrap();
+            },
+            None => queue = shared.changed.wait(queue).unwrap(),
+        }
+    }
+}
+
+/// Restores the previously [entered][ThreadPool::enter] pool when dropped.
+pub struct EnterGuard {
+    previous: Option<Arc<ThreadPool>>,
+    /// The guard has to be dropped on the thread that created it.
+    _marker: PhantomData<*const ()>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) { CURRENT.set(self.previous.take()); }
+}
+
+/// Jobs queued by [`ThreadPool::scope`], which may borrow data living
+/// for `'s`.
+pub struct Scope<'s> {
+    pool: Arc<ThreadPool>,
+    /// Number of queued jobs that didn't finish yet.
+    pending: AtomicUsize,
+    /// Payload of the first panicking job.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+    _marker: PhantomData<&'s mut &'s ()>,
+}
+
+impl<'s> Scope<'s> {
+    /// Queues `job` to run on a worker of the pool.
+    pub fn spawn(&self, job: impl FnOnce() + Send + 's) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let scope = self as *const Scope as usize;
+        let job: Box<dyn FnOnce() + Send + 's> = Box::new(move || {
+            // SAFETY: `ThreadPool::scope` doesn't return before all jobs
+            // finish, so the scope outlives the job.
+            let scope = unsafe { &*(scope as *const Scope) };
+            let guard = scope.pool.enter();
+            let result = std::panic::catch_unwind(AssertUnwindSafe(job));
+            drop(guard);
+            if let Err(panic) = result {
+                let mut first = scope.panic.lock().unwrap();
+                if first.is_none() {
+                    *first = Some(panic);
+                }
+            }
+            let shared = scope.pool.shared.clone();
+            scope.pending.fetch_sub(1, Ordering::SeqCst);
+            // The scope may be gone now, but the pool state is kept alive.
+            drop(shared.queue.lock().unwrap());
+            shared.changed.notify_all();
+        });
+        // SAFETY: `ThreadPool::scope` waits for the job to finish before any
+        // data it borrows goes out of scope.
+        let job = unsafe {
+            std::mem::transmute::<Box<dyn FnOnce() + Send + 's>, Job>(job)
+        };
+        self.pool.push(job);
+    }
+
+    /// Waits until all queued jobs finish. Workers of the pool run other
+    /// queued jobs meanwhile, so that nested scopes can't deadlock.
+    fn wait(&self) {
+        let shared = &self.pool.shared;
+        let helping = WORKER_OF.get() == shared.id();
+        let mut queue = shared.queue.lock().unwrap();
+        while self.pending.load(Ordering::SeqCst) != 0 {
+            let job = match queue.as_mut() {
+                Some(jobs) if helping => jobs.pop_front(),
+                _ => None,
+            };
+            match job {
+                Some(job) => {
+                    drop(queue);
+                    job();
+                    queue = shared.queue.lock().unwrap();
+                },
+                None => queue = shared.changed.wait(queue).unwrap(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_jobs() {
+        let pool = Arc::new(ThreadPool::new(2));
+        let mut values = vec![0; 64];
+        pool.scope(|scope| {
+            for (i, value) in values.iter_mut().enumerate() {
+                scope.spawn(move || {
+                    // Nested scopes run on the same workers.
+                    ThreadPool::current().scope(|scope| {
+                        scope.spawn(move || *value = i * 2);
+                    });
+                });
+            }
+        });
+        assert_eq!(values, (0..64).map(|i| i * 2).collect::<Vec<_>>());
+
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|scope| scope.spawn(|| panic!("job failed")));
+        }));
+        assert!(panicked.is_err());
+        pool.scope(|scope| scope.spawn(|| ()));
+    }
+}